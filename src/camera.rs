@@ -66,7 +66,9 @@ impl CameraBuilder {
         self
     }
 
-    pub fn build(self) -> Camera {
+    pub fn build(mut self) -> Camera {
+        // pitch and yaw may have been changed, keep front in sync with them
+        self.inner.front = self.inner.calc_front();
         self.inner
     }
 }
@@ -133,6 +135,21 @@ impl Camera {
         self.pos
     }
 
+    /// normalized view direction
+    pub fn get_front(&self) -> Vec3 {
+        self.front
+    }
+
+    /// normalized vector pointing to the right of the screen
+    pub fn get_right(&self) -> Vec3 {
+        glm::normalize(glm::cross(self.front, *UP))
+    }
+
+    /// normalized vector pointing to the top of the screen
+    pub fn get_up(&self) -> Vec3 {
+        glm::cross(self.get_right(), self.front)
+    }
+
     /// return normalized front vector
     fn calc_front(&self) -> Vector3<f32> {
         // (cos(yaw), tan(pitch), -sin(yaw))
@@ -34,6 +34,11 @@ impl CameraHandler {
         }
     }
 
+    /// while the camera holds the cursor the keys move it, shift included
+    pub fn is_cursor_grabbed(&self) -> bool {
+        self.cursor_grabed
+    }
+
     pub fn handle_event<T: 'static>(&mut self, event: &Event<T>, window: &Window) {
         match event {
             Event::WindowEvent { event, .. } => match event {
@@ -0,0 +1,209 @@
+use std::rc::Rc;
+
+use glium::{Display, DrawParameters, Surface, texture, uniform, VertexBuffer};
+use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
+use glium::index::IndexBufferAny;
+use glium::texture::SrgbTexture2d;
+use glium::uniforms::Sampler;
+use glm::{Mat4, Vec3};
+
+use crate::{Drawable, FrameState};
+use crate::hair_cube::Vertex;
+use crate::lights::{DEFAULT_MAX_LIGHTS, include_lights, LightRig};
+use crate::mesh::MeshData;
+use crate::shader::{ProgramBuilder, ShaderError};
+use crate::shader_source;
+use crate::shadows::{ShadowMaps, ShadowPass};
+use crate::transform::{mat3_uniform, mat4_uniform, normal_matrix, Transform};
+use crate::transparency::{BlendMode, BlendPrograms};
+
+/// textured Lambert surface, used for everything that is not hair
+pub struct DiffuseMesh {
+    name: String,
+    transform: Transform,
+    /// one program per pass, the blend mode picks
+    programs: BlendPrograms,
+    vertex_buffer: VertexBuffer<Vertex>,
+    indices: IndexBufferAny,
+    /// bounding sphere of the vertices
+    bounds: Option<(Vec3, f32)>,
+    texture: SrgbTexture2d,
+    base_color: [f32; 4],
+    blend: BlendMode,
+    /// size of the light array the program was built with
+    max_lights: usize,
+    lights: LightRig,
+    shadows: Rc<ShadowMaps>,
+}
+
+impl DiffuseMesh {
+    /// `texture` defaults to plain white, so `base_color` alone decides the colour
+    pub fn new(
+        display: &Display,
+        mesh: &MeshData,
+        texture: Option<SrgbTexture2d>,
+        base_color: [f32; 4],
+    ) -> Result<Self, ShaderError> {
+        let programs = Self::programs(display, DEFAULT_MAX_LIGHTS)?;
+        Ok(Self::from_shared(
+            display,
+            mesh,
+            texture,
+            base_color,
+            programs,
+            DEFAULT_MAX_LIGHTS,
+        ))
+    }
+
+    /// like [`new`](Self::new), drawing with `programs` from [`programs`](Self::programs)
+    /// built for `max_lights` lights
+    pub fn from_shared(
+        display: &Display,
+        mesh: &MeshData,
+        texture: Option<SrgbTexture2d>,
+        base_color: [f32; 4],
+        programs: BlendPrograms,
+        max_lights: usize,
+    ) -> Self {
+        let texture = texture.unwrap_or_else(|| {
+            let white = texture::RawImage2d::from_raw_rgba(vec![255u8; 4], (1, 1));
+            SrgbTexture2d::new(display, white).unwrap()
+        });
+
+        Self {
+            name: String::from("diffuse_mesh"),
+            transform: Transform::default(),
+            programs,
+            vertex_buffer: mesh.vertex_buffer(display).unwrap(),
+            indices: mesh.index_buffer(display).unwrap(),
+            bounds: mesh.bounding_sphere(),
+            texture,
+            base_color,
+            blend: BlendMode::Opaque,
+            max_lights,
+            lights: LightRig::default(),
+            shadows: Rc::new(ShadowMaps::disabled(display)),
+        }
+    }
+
+    /// the programs of diffuse surfaces lit by up to `max_lights` lights, meshes built with
+    /// [`from_shared`](Self::from_shared) can draw with the same ones
+    pub fn programs(display: &Display, max_lights: usize) -> Result<BlendPrograms, ShaderError> {
+        BlendPrograms::build(
+            display,
+            include_lights(
+                ProgramBuilder::new(shader_source!("shader.vert"), shader_source!("shader.frag")),
+                max_lights,
+            ),
+        )
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// rebuild the program for up to `max_lights` lights, the rest of a rig is ignored
+    pub fn with_max_lights(
+        mut self,
+        display: &Display,
+        max_lights: usize,
+    ) -> Result<Self, ShaderError> {
+        self.programs = Self::programs(display, max_lights)?;
+        self.max_lights = max_lights;
+        Ok(self)
+    }
+
+    /// what the alpha of the base colour does
+    pub fn with_blend_mode(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn set_base_color(&mut self, color: [f32; 4]) {
+        self.base_color = color;
+    }
+
+    fn draw_into<S: Surface>(
+        &self,
+        target: &mut S,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        let uniforms = uniform! {
+            camera: mat4_uniform(&camera_mat),
+            model: mat4_uniform(&model_mat),
+            normal_mat: mat3_uniform(&normal_matrix(&model_mat)),
+            base_color_texture:
+                Sampler::new(&self.texture)
+                        .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear),
+            base_color: self.base_color,
+            alpha_cutoff: self.blend.alpha_cutoff(),
+            alpha_coverage: self.blend.alpha_coverage(),
+        };
+
+        target
+            .draw(
+                &self.vertex_buffer,
+                &self.indices,
+                self.programs.get(self.blend),
+                &self
+                    .shadows
+                    .with_uniforms(self.lights.with_uniforms(uniforms)),
+                &self.blend.draw_parameters(dramparams),
+            )
+            .unwrap();
+    }
+}
+
+impl Drawable for DiffuseMesh {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn update(&mut self, state: &FrameState) {
+        self.lights.clone_from(state.lights);
+        self.shadows = Rc::clone(state.shadows);
+    }
+
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    fn draw_with_model(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        if !self.blend.is_transparent() {
+            self.draw_into(target, camera_mat, model_mat, dramparams);
+        }
+    }
+
+    fn draw_transparent(
+        &self,
+        target: &mut MultiOutputFrameBuffer,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        if self.blend.is_transparent() {
+            self.draw_into(target, camera_mat, model_mat, dramparams);
+        }
+    }
+
+    fn draw_shadow(&self, target: &mut SimpleFrameBuffer, pass: &ShadowPass, model_mat: Mat4) {
+        pass.draw(target, &self.vertex_buffer, &self.indices, model_mat);
+    }
+
+    fn bounds(&self) -> Option<(Vec3, f32)> {
+        self.bounds
+    }
+}
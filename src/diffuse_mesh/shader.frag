@@ -0,0 +1,29 @@
+#version 330 core
+
+#include "lights.glsl"
+#include "blend.glsl"
+
+in vec2 TexCoord;
+in vec3 Normal;
+in vec3 FragPos;
+
+uniform sampler2D base_color_texture;
+uniform vec4 base_color;
+
+void main() {
+    vec3 normal = normalize(Normal);
+
+    float ambient_strength = 0.2;
+    vec3 ambient = ambient_strength * ambient_light;
+
+    vec3 diffuse = vec3(0.0);
+    for (int i = 0; i < active_lights(); ++i) {
+        vec3 light_dir = light_direction(lights[i], FragPos);
+        float diff = max(dot(light_dir, normal), 0.0);
+        float shadow = light_shadow(lights[i], FragPos, normal);
+        diffuse += diff * shadow * light_radiance(lights[i], FragPos);
+    }
+
+    vec4 albedo = base_color * texture(base_color_texture, TexCoord);
+    write_color(vec4((ambient + diffuse) * albedo.rgb, albedo.a));
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 tex_coord;
+
+out vec2 TexCoord;
+out vec3 Normal;
+out vec3 FragPos;
+
+uniform mat4 camera;
+uniform mat4 model;
+uniform mat3 normal_mat;
+
+void main() {
+    vec4 world_pos = model * vec4(position, 1.0);
+    gl_Position = camera * world_pos;
+    TexCoord = tex_coord;
+    Normal = normalize(normal_mat * normal);
+    FragPos = vec3(world_pos);
+}
\ No newline at end of file
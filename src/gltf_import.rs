@@ -0,0 +1,590 @@
+//! glTF 2.0 import into a [`SceneGraph`].
+//!
+//! Every glTF node becomes a graph node with the same local transform, every triangle primitive
+//! becomes a child node holding a drawable. Primitives whose material carries hair parameters
+//! are drawn with the Kajiya-Kay material ([`HairCube`]), all others with [`DiffuseMesh`].
+//!
+//! Hair parameters come from the `KKD_hair_material` material extension or, for exporters that
+//! cannot write custom extensions, from a `kajiya_kay` object in the material extras:
+//!
+//! ```json
+//! "extensions": {
+//!     "KKD_hair_material": {
+//!         "shiftMapTexture": { "index": 1 },
+//!         "specularStrength": 1.5,
+//!         "specularExponent": 1024,
+//!         "shiftScale": 0.3
+//!     }
+//! }
+//! ```
+//!
+//! Every field is optional, missing ones keep the values of
+//! [`ImportOptions::hair_material`]. The specular strength and exponent belong to the primary
+//! lobe.
+//!
+//! The material's `alphaMode` becomes its [`BlendMode`]: `MASK` cuts out at `alphaCutoff`,
+//! `BLEND` draws through the order-independent transparency pass.
+
+use std::cell::OnceCell;
+use std::error::Error;
+use std::fmt::{self, Display as FmtDisplay, Formatter};
+use std::path::Path;
+
+use glium::Display;
+use glium::texture::{RawImage2d, SrgbTexture2d, Texture2d};
+use gltf::json::Value;
+use gltf::material::AlphaMode;
+use gltf::mesh::Mode;
+use glm::Vec3;
+
+use crate::diffuse_mesh::DiffuseMesh;
+use crate::hair_cube::{create_shift_map, HairCube, Vertex};
+use crate::hair_cube::material::HairMaterial;
+use crate::lights::DEFAULT_MAX_LIGHTS;
+use crate::mesh::MeshData;
+use crate::scene_graph::{NodeId, SceneGraph};
+use crate::shader::ShaderError;
+use crate::transform::Transform;
+use crate::transparency::{BlendMode, BlendPrograms};
+
+pub const HAIR_EXTENSION: &str = "KKD_hair_material";
+pub const HAIR_EXTRAS_KEY: &str = "kajiya_kay";
+
+#[derive(Debug)]
+pub enum GltfError {
+    Gltf(gltf::Error),
+    /// the file defines no scene
+    NoScene,
+    /// a primitive or material that cannot be imported
+    Invalid {
+        what: String,
+        message: String,
+    },
+    Shader(ShaderError),
+}
+
+impl FmtDisplay for GltfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfError::Gltf(err) => write!(f, "failed to load gltf file: {}", err),
+            GltfError::NoScene => write!(f, "gltf file contains no scene"),
+            GltfError::Invalid { what, message } => write!(f, "{}: {}", what, message),
+            GltfError::Shader(err) => write!(f, "gltf materials: {}", err),
+        }
+    }
+}
+
+impl Error for GltfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GltfError::Gltf(err) => Some(err),
+            GltfError::Shader(err) => Some(err),
+            GltfError::Invalid { .. } | GltfError::NoScene => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for GltfError {
+    fn from(err: gltf::Error) -> Self {
+        GltfError::Gltf(err)
+    }
+}
+
+impl From<ShaderError> for GltfError {
+    fn from(err: ShaderError) -> Self {
+        GltfError::Shader(err)
+    }
+}
+
+/// what the rest of the scene is drawn with, applied to the imported drawables
+#[derive(Clone, Copy, Debug)]
+pub struct ImportOptions {
+    /// base of every hair material, the file's hair parameters override its fields
+    pub hair_material: HairMaterial,
+    /// replaces the `alphaMode` of hair materials
+    pub hair_blend: Option<BlendMode>,
+    pub max_lights: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            hair_material: HairMaterial::default(),
+            hair_blend: None,
+            max_lights: DEFAULT_MAX_LIGHTS,
+        }
+    }
+}
+
+/// import the default scene (or the first one) of a `.gltf` / `.glb` file below `parent`,
+/// returning the ids of the imported root nodes
+pub fn load_gltf(
+    display: &Display,
+    path: impl AsRef<Path>,
+    graph: &mut SceneGraph,
+    parent: Option<NodeId>,
+    options: &ImportOptions,
+) -> Result<Vec<NodeId>, GltfError> {
+    let (document, buffers, images) = gltf::import(path)?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or(GltfError::NoScene)?;
+
+    let importer = Importer {
+        display,
+        document: &document,
+        buffers: &buffers,
+        images: &images,
+        options,
+        hair_programs: OnceCell::new(),
+        diffuse_programs: OnceCell::new(),
+    };
+    scene
+        .nodes()
+        .map(|node| importer.import_node(node, graph, parent))
+        .collect()
+}
+
+/// hair parameters found on a material
+#[derive(Debug, Default, PartialEq)]
+struct HairParams {
+    /// index into the document textures
+    shift_map: Option<usize>,
+    specular_strength: Option<f32>,
+    specular_exponent: Option<f32>,
+    shift_scale: Option<f32>,
+}
+
+impl HairParams {
+    fn from_material(material: &gltf::Material) -> Result<Option<Self>, GltfError> {
+        let invalid = |message: String| GltfError::Invalid {
+            what: format!("material {}", material.name().unwrap_or("<unnamed>")),
+            message,
+        };
+
+        let extras: Option<Value> = match material.extras() {
+            Some(raw) => Some(
+                gltf::json::deserialize::from_str(raw.get())
+                    .map_err(|e| invalid(format!("invalid extras: {}", e)))?,
+            ),
+            None => None,
+        };
+        let value = material
+            .extension_value(HAIR_EXTENSION)
+            .or_else(|| extras.as_ref().and_then(|e| e.get(HAIR_EXTRAS_KEY)));
+        let value = match value {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if !value.is_object() {
+            return Err(invalid(String::from("hair parameters must be an object")));
+        }
+
+        let number = |key: &str| -> Result<Option<f32>, GltfError> {
+            match value.get(key) {
+                None => Ok(None),
+                Some(v) => v
+                    .as_f64()
+                    .map(|n| Some(n as f32))
+                    .ok_or_else(|| invalid(format!("`{}` must be a number", key))),
+            }
+        };
+        let shift_map = match value.get("shiftMapTexture") {
+            None => None,
+            Some(info) => Some(
+                info.get("index")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| invalid(String::from("`shiftMapTexture` needs an `index`")))?
+                    as usize,
+            ),
+        };
+
+        Ok(Some(Self {
+            shift_map,
+            specular_strength: number("specularStrength")?,
+            specular_exponent: number("specularExponent")?,
+            shift_scale: number("shiftScale")?,
+        }))
+    }
+}
+
+struct Importer<'a> {
+    display: &'a Display,
+    document: &'a gltf::Document,
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    options: &'a ImportOptions,
+    /// built for the first primitive that needs them and shared by the others
+    hair_programs: OnceCell<BlendPrograms>,
+    diffuse_programs: OnceCell<BlendPrograms>,
+}
+
+impl Importer<'_> {
+    fn import_node(
+        &self,
+        node: gltf::Node,
+        graph: &mut SceneGraph,
+        parent: Option<NodeId>,
+    ) -> Result<NodeId, GltfError> {
+        let (t, r, s) = node.transform().decomposed();
+        let local = Transform::from_translation(Vec3::new(t[0], t[1], t[2]))
+            .with_quaternion(r)
+            .with_scale(Vec3::new(s[0], s[1], s[2]));
+        let name = node
+            .name()
+            .map(str::to_owned)
+            .unwrap_or_else(|| format!("node{}", node.index()));
+        let id = graph.add_node(parent, name.clone(), local);
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                // points and lines have no surface to shade
+                if primitive.mode() != Mode::Triangles {
+                    continue;
+                }
+                self.import_primitive(
+                    &primitive,
+                    &format!("{}/{}", name, primitive.index()),
+                    graph,
+                    id,
+                )?;
+            }
+        }
+
+        for child in node.children() {
+            self.import_node(child, graph, Some(id))?;
+        }
+        Ok(id)
+    }
+
+    fn import_primitive(
+        &self,
+        primitive: &gltf::Primitive,
+        name: &str,
+        graph: &mut SceneGraph,
+        parent: NodeId,
+    ) -> Result<(), GltfError> {
+        let mesh = self.read_mesh(primitive, name)?;
+        let material = primitive.material();
+        let pbr = material.pbr_metallic_roughness();
+        let base_color = pbr.base_color_factor();
+        let blend = blend_mode(&material);
+        let max_lights = self.options.max_lights;
+        let base_texture = pbr
+            .base_color_texture()
+            .map(|info| self.texture(info.texture().index()))
+            .transpose()?;
+
+        match HairParams::from_material(&material)? {
+            Some(params) => {
+                // the hair shader has no colour factor, so untextured hair gets a 1x1 texture
+                let texture = match base_texture {
+                    Some(texture) => texture,
+                    None => solid_texture(self.display, base_color),
+                };
+                let shift_map = match params.shift_map {
+                    Some(index) => self.linear_texture(index)?,
+                    None => create_shift_map(self.display),
+                };
+                let programs = shared_programs(&self.hair_programs, || {
+                    HairCube::programs(self.display, max_lights)
+                })?;
+                let mut hair = HairCube::from_shared(
+                    self.display,
+                    &mesh,
+                    texture,
+                    shift_map,
+                    programs,
+                    max_lights,
+                )
+                .with_name(name)
+                .with_material(self.options.hair_material);
+                let material = hair.material_mut();
+                if let Some(strength) = params.specular_strength {
+                    material.primary.strength = strength;
+                }
+                if let Some(exponent) = params.specular_exponent {
+                    material.primary.exponent = exponent;
+                }
+                if let Some(scale) = params.shift_scale {
+                    material.shift_scale = scale;
+                }
+                material.blend = self.options.hair_blend.unwrap_or(blend);
+                graph.add_drawable(Some(parent), Transform::new(), hair);
+            }
+            None => {
+                let programs = shared_programs(&self.diffuse_programs, || {
+                    DiffuseMesh::programs(self.display, max_lights)
+                })?;
+                let diffuse = DiffuseMesh::from_shared(
+                    self.display,
+                    &mesh,
+                    base_texture,
+                    base_color,
+                    programs,
+                    max_lights,
+                )
+                .with_name(name)
+                .with_blend_mode(blend);
+                graph.add_drawable(Some(parent), Transform::new(), diffuse);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_mesh(&self, primitive: &gltf::Primitive, name: &str) -> Result<MeshData, GltfError> {
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let positions: Vec<[f32; 3]> = reader
+            .read_positions()
+            .ok_or_else(|| GltfError::Invalid {
+                what: format!("primitive {}", name),
+                message: String::from("missing POSITION attribute"),
+            })?
+            .collect();
+        let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(Iterator::collect);
+        let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(Iterator::collect);
+        let tex_coords: Option<Vec<[f32; 2]>> =
+            reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+
+        let vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| Vertex {
+                position,
+                normal: normals.as_ref().map(|n| n[i]).unwrap_or([0.0, 0.0, 0.0]),
+                // glTF puts the uv origin at the top left, our textures are uploaded bottom up
+                tex_coord: tex_coords
+                    .as_ref()
+                    .map(|t| [t[i][0], 1.0 - t[i][1]])
+                    .unwrap_or([0.0, 0.0]),
+                binormal: [0.0, 0.0, 0.0],
+            })
+            .collect();
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        let mut mesh = MeshData { vertices, indices };
+        if normals.is_none() {
+            mesh.generate_normals();
+        }
+        match tangents {
+            Some(tangents) => {
+                for (vertex, t) in mesh.vertices.iter_mut().zip(tangents) {
+                    let n = Vec3::new(vertex.normal[0], vertex.normal[1], vertex.normal[2]);
+                    // glTF bitangents point up the image, which is +v after the flip above
+                    let b = glm::cross(n, Vec3::new(t[0], t[1], t[2])) * t[3];
+                    vertex.binormal = *b.as_array();
+                }
+            }
+            None => mesh.generate_binormals(),
+        }
+        Ok(mesh)
+    }
+
+    fn texture(&self, index: usize) -> Result<SrgbTexture2d, GltfError> {
+        Ok(SrgbTexture2d::new(self.display, self.raw_image(index)?).unwrap())
+    }
+
+    /// for data textures like the shift map, which must not be sRGB decoded
+    fn linear_texture(&self, index: usize) -> Result<Texture2d, GltfError> {
+        Ok(Texture2d::new(self.display, self.raw_image(index)?).unwrap())
+    }
+
+    fn raw_image(&self, index: usize) -> Result<RawImage2d<'static, u8>, GltfError> {
+        let texture = self
+            .document
+            .textures()
+            .nth(index)
+            .ok_or_else(|| GltfError::Invalid {
+                what: format!("texture {}", index),
+                message: String::from("no such texture"),
+            })?;
+        let image = &self.images[texture.source().index()];
+        let pixels = rgba8_pixels(image);
+        Ok(RawImage2d::from_raw_rgba_reversed(
+            &pixels,
+            (image.width, image.height),
+        ))
+    }
+}
+
+/// the programs in `cell`, built by `build` on first use
+fn shared_programs(
+    cell: &OnceCell<BlendPrograms>,
+    build: impl FnOnce() -> Result<BlendPrograms, ShaderError>,
+) -> Result<BlendPrograms, GltfError> {
+    if let Some(programs) = cell.get() {
+        return Ok(programs.clone());
+    }
+    let programs = build()?;
+    Ok(cell.get_or_init(|| programs).clone())
+}
+
+fn blend_mode(material: &gltf::Material) -> BlendMode {
+    match material.alpha_mode() {
+        AlphaMode::Opaque => BlendMode::Opaque,
+        // the spec's default cutoff
+        AlphaMode::Mask => BlendMode::Cutout {
+            cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        },
+        AlphaMode::Blend => BlendMode::Transparent,
+    }
+}
+
+fn solid_texture(display: &Display, color: [f32; 4]) -> SrgbTexture2d {
+    // the factor is linear, the texture is sampled as sRGB
+    let encode = |c: f32| (c.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0).round() as u8;
+    let pixel = vec![
+        encode(color[0]),
+        encode(color[1]),
+        encode(color[2]),
+        (color[3].clamp(0.0, 1.0) * 255.0).round() as u8,
+    ];
+    SrgbTexture2d::new(display, RawImage2d::from_raw_rgba(pixel, (1, 1))).unwrap()
+}
+
+/// expand any image format produced by the glTF loader to 8 bit RGBA
+fn rgba8_pixels(image: &gltf::image::Data) -> Vec<u8> {
+    use gltf::image::Format;
+
+    let (channels, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let channel = |bytes: &[u8]| -> u8 {
+        match bytes_per_channel {
+            1 => bytes[0],
+            2 => (u16::from_ne_bytes([bytes[0], bytes[1]]) >> 8) as u8,
+            _ => {
+                let v = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (v.clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+        }
+    };
+
+    let pixel_size = channels * bytes_per_channel;
+    let mut out = Vec::with_capacity((image.width * image.height * 4) as usize);
+    for pixel in image.pixels.chunks_exact(pixel_size) {
+        let c: Vec<u8> = pixel.chunks_exact(bytes_per_channel).map(channel).collect();
+        let rgba = match channels {
+            // grey, grey + alpha
+            1 => [c[0], c[0], c[0], 255],
+            2 => [c[0], c[0], c[0], c[1]],
+            3 => [c[0], c[1], c[2], 255],
+            _ => [c[0], c[1], c[2], c[3]],
+        };
+        out.extend_from_slice(&rgba);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the first material of a document holding only `materials`
+    fn with_material<T>(material: &str, check: impl FnOnce(&gltf::Material) -> T) -> T {
+        let json = format!(
+            r#"{{ "asset": {{ "version": "2.0" }}, "materials": [{}] }}"#,
+            material
+        );
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+        let material = gltf.document.materials().next().unwrap();
+        check(&material)
+    }
+
+    fn hair_params(material: &str) -> Result<Option<HairParams>, GltfError> {
+        with_material(material, HairParams::from_material)
+    }
+
+    #[test]
+    fn reads_the_hair_extension() {
+        let params = hair_params(
+            r#"{ "extensions": { "KKD_hair_material": {
+                "shiftMapTexture": { "index": 1 },
+                "specularStrength": 2.0,
+                "specularExponent": 512,
+                "shiftScale": 0.25
+            } } }"#,
+        );
+        assert_eq!(
+            params.unwrap(),
+            Some(HairParams {
+                shift_map: Some(1),
+                specular_strength: Some(2.0),
+                specular_exponent: Some(512.0),
+                shift_scale: Some(0.25),
+            })
+        );
+    }
+
+    #[test]
+    fn reads_the_extras_with_missing_fields() {
+        let params = hair_params(r#"{ "extras": { "kajiya_kay": { "shiftScale": 0.1 } } }"#);
+        assert_eq!(
+            params.unwrap(),
+            Some(HairParams {
+                shift_scale: Some(0.1),
+                ..HairParams::default()
+            })
+        );
+    }
+
+    #[test]
+    fn extension_wins_over_extras() {
+        let params = hair_params(
+            r#"{ "extensions": { "KKD_hair_material": { "shiftScale": 0.5 } },
+                 "extras": { "kajiya_kay": { "shiftScale": 0.1 } } }"#,
+        );
+        assert_eq!(params.unwrap().unwrap().shift_scale, Some(0.5));
+    }
+
+    #[test]
+    fn plain_materials_have_no_hair_params() {
+        assert_eq!(hair_params("{}").unwrap(), None);
+        assert_eq!(
+            hair_params(r#"{ "extras": { "author": "someone" } }"#).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_hair_params() {
+        assert!(hair_params(r#"{ "extras": { "kajiya_kay": 1.0 } }"#).is_err());
+        assert!(
+            hair_params(r#"{ "extras": { "kajiya_kay": { "shiftScale": "wide" } } }"#).is_err()
+        );
+        assert!(hair_params(
+            r#"{ "extras": { "kajiya_kay": { "shiftMapTexture": { "texCoord": 0 } } } }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn maps_alpha_modes_to_blend_modes() {
+        assert_eq!(with_material("{}", blend_mode), BlendMode::Opaque);
+        assert_eq!(
+            with_material(r#"{ "alphaMode": "MASK" }"#, blend_mode),
+            BlendMode::Cutout { cutoff: 0.5 }
+        );
+        assert_eq!(
+            with_material(r#"{ "alphaMode": "MASK", "alphaCutoff": 0.3 }"#, blend_mode),
+            BlendMode::Cutout { cutoff: 0.3 }
+        );
+        assert_eq!(
+            with_material(r#"{ "alphaMode": "BLEND" }"#, blend_mode),
+            BlendMode::Transparent
+        );
+    }
+}
@@ -0,0 +1,122 @@
+// the hair material shared by the hair cube and the strands, filled in by HairMaterial in
+// src/hair_cube/material.rs
+
+// how far the shift map moves the highlights, for surfaces that have one
+uniform float shift_scale;
+uniform float ambient_strength;
+uniform float diffuse_strength;
+// scales the specular term of both models
+uniform float specular_strength;
+uniform vec3 specular_tint;
+
+// Scheuermann's two lobes: a primary highlight in the specular tint and a secondary one in its
+// own tint, each moved along the normal by its own shift
+uniform float primary_shift;
+uniform float primary_exponent;
+uniform float primary_strength;
+uniform float secondary_shift;
+uniform float secondary_exponent;
+uniform float secondary_strength;
+uniform vec3 secondary_tint;
+
+// the model shading the fibres, see ShadingModel in src/marschner.rs
+#define KAJIYA_KAY 0
+#define MARSCHNER 1
+uniform int shading_model;
+// (R, TT, TRT) longitudinal shifts and widths in radians
+uniform vec3 marschner_shifts;
+uniform vec3 marschner_widths;
+uniform vec3 marschner_strengths;
+uniform float marschner_eta;
+
+const float PI = 3.14159265;
+
+vec3 shift_tangent(vec3 tangent, vec3 normal, float shift) {
+    return normalize(tangent + shift * normal);
+}
+
+float strand_specular(vec3 tangent, vec3 half_vec, float exponent) {
+    float dot_th = dot(tangent, half_vec);
+    float sin_th = sqrt(max(1.0 - dot_th * dot_th, 0.0));
+    float dir_atten = smoothstep(-1.0, 0.0, dot_th);
+    return dir_atten * pow(sin_th, exponent);
+}
+
+vec3 kajiya_kay(vec3 normal, vec3 binormal, float shift, vec3 light_dir, vec3 view_dir) {
+    // fades the highlights out as the surface turns away from the light
+    float ctrl = clamp(dot(light_dir, normal), 0.0, 1.0);
+    vec3 half_vec = normalize(view_dir + light_dir);
+    vec3 primary_tangent = shift_tangent(binormal, normal, primary_shift + shift);
+    vec3 secondary_tangent = shift_tangent(binormal, normal, secondary_shift + shift);
+    vec3 spec = primary_strength * strand_specular(primary_tangent, half_vec, primary_exponent) * specular_tint
+        + secondary_strength * strand_specular(secondary_tangent, half_vec, secondary_exponent) * secondary_tint;
+    return specular_strength * ctrl * spec;
+}
+
+float longitudinal(float width, float theta) {
+    return exp(-0.5 * theta * theta / (width * width)) / (sqrt(2.0 * PI) * width);
+}
+
+float fresnel(float eta, float cos_theta) {
+    float r0 = (1.0 - eta) / (1.0 + eta);
+    float f0 = r0 * r0;
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// R, TT and TRT lobes, kept in sync with the CPU reference in src/marschner.rs
+vec3 marschner(vec3 tangent, vec3 light_dir, vec3 view_dir, vec3 base_color) {
+    float sin_l = clamp(dot(light_dir, tangent), -1.0, 1.0);
+    float sin_v = clamp(dot(view_dir, tangent), -1.0, 1.0);
+    float theta_l = asin(sin_l);
+    float theta_v = asin(sin_v);
+    float theta_h = 0.5 * (theta_l + theta_v);
+    float cos_theta_d = max(cos(0.5 * (theta_l - theta_v)), 1e-4);
+
+    vec3 lp = light_dir - tangent * sin_l;
+    vec3 vp = view_dir - tangent * sin_v;
+    float cos_phi = dot(lp, vp) / sqrt(dot(lp, lp) * dot(vp, vp) + 1e-4);
+    float cos_half_phi = sqrt(max(0.5 + 0.5 * cos_phi, 0.0));
+
+    // Bravais' index for the inclined ray, see bravais_index
+    float sin2_theta_d = 1.0 - cos_theta_d * cos_theta_d;
+    float a = cos_theta_d / sqrt(max(marschner_eta * marschner_eta - sin2_theta_d, 1e-4));
+
+    float m_r = longitudinal(marschner_widths.x, theta_h - marschner_shifts.x);
+    float n_r = 0.25 * cos_half_phi;
+    float f_r = fresnel(marschner_eta, sqrt(max(0.5 + 0.5 * dot(light_dir, view_dir), 0.0)));
+    vec3 r = vec3(marschner_strengths.x * m_r * n_r * f_r);
+
+    float m_tt = longitudinal(marschner_widths.y, theta_h - marschner_shifts.y);
+    float h = cos_half_phi * (1.0 + a * (0.6 - 0.8 * cos_phi));
+    float f = fresnel(marschner_eta, cos_theta_d * sqrt(max(1.0 - h * h, 0.0)));
+    float f_tt = (1.0 - f) * (1.0 - f);
+    float t_exp = 0.5 * sqrt(max(1.0 - (h * a) * (h * a), 0.0)) / cos_theta_d;
+    float n_tt = exp(-3.65 * cos_phi - 3.98);
+    vec3 tt = marschner_strengths.y * m_tt * n_tt * f_tt * pow(base_color, vec3(t_exp));
+
+    float m_trt = longitudinal(marschner_widths.z, theta_h - marschner_shifts.z);
+    f = fresnel(marschner_eta, cos_theta_d * 0.5);
+    float f_trt = (1.0 - f) * (1.0 - f) * f;
+    float n_trt = exp(17.0 * cos_phi - 16.78);
+    vec3 trt = marschner_strengths.z * m_trt * n_trt * f_trt * pow(base_color, vec3(0.8 / cos_theta_d));
+
+    return specular_strength * (r + tt + trt);
+}
+
+// the specular term of the material's shading model for a fibre along tangent. normal is the
+// direction the Kajiya-Kay lobes shift towards, shift is added to the shift of each lobe
+vec3 hair_specular(vec3 tangent, vec3 normal, float shift, vec3 light_dir, vec3 view_dir, vec3 base_color) {
+    if (shading_model == MARSCHNER) {
+        return marschner(shift_tangent(tangent, normal, shift), light_dir, view_dir, base_color);
+    }
+    return kajiya_kay(normal, tangent, shift, light_dir, view_dir);
+}
+
+// the lit colour of a fibre. Kajiya-Kay highlights take the albedo, Marschner's lobes carry
+// their own colour
+vec3 hair_color(vec3 albedo, vec3 ambient_and_diffuse, vec3 specular) {
+    if (shading_model == MARSCHNER) {
+        return albedo * ambient_and_diffuse + specular;
+    }
+    return albedo * (ambient_and_diffuse + specular);
+}
\ No newline at end of file
@@ -0,0 +1,215 @@
+//! shading parameters of the hair material, kept out of the shader so looks can be tuned at
+//! runtime and saved as JSON. the hair cube and the strands shade with the same material through
+//! `hair_shading.glsl`
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use glium::uniforms::{Uniforms, UniformValue};
+use serde::{Deserialize, Serialize};
+
+use crate::marschner::{MarschnerParams, ShadingModel};
+use crate::shader::ProgramBuilder;
+use crate::shader_source;
+use crate::transparency::BlendMode;
+
+/// make `#include "hair_shading.glsl"` available, the material's uniforms and lobes
+pub fn include_hair_shading(builder: ProgramBuilder) -> ProgramBuilder {
+    builder.include("hair_shading.glsl", shader_source!("hair_shading.glsl"))
+}
+
+/// one anisotropic highlight of the two-lobe hair model
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpecularLobe {
+    /// offset along the normal, added to the shift map value
+    pub shift: f32,
+    pub exponent: f32,
+    pub strength: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HairMaterial {
+    pub ambient_strength: f32,
+    pub diffuse_strength: f32,
+    /// multiplier for the whole specular term of either shading model
+    pub specular_strength: f32,
+    /// colour of the primary lobe
+    pub specular_tint: [f32; 3],
+    /// how far the shift map moves the highlights along the normal
+    pub shift_scale: f32,
+    pub primary: SpecularLobe,
+    pub secondary: SpecularLobe,
+    pub secondary_tint: [f32; 3],
+    pub shading_model: ShadingModel,
+    pub marschner: MarschnerParams,
+    /// what the alpha of the colour texture does
+    pub blend: BlendMode,
+}
+
+impl Default for HairMaterial {
+    fn default() -> Self {
+        Self {
+            ambient_strength: 0.2,
+            diffuse_strength: 0.5,
+            specular_strength: 1.0,
+            specular_tint: [1.0, 1.0, 1.0],
+            shift_scale: 0.3,
+            primary: SpecularLobe {
+                shift: 0.0,
+                exponent: 1024.0,
+                strength: 1.5,
+            },
+            secondary: SpecularLobe {
+                shift: 0.15,
+                exponent: 64.0,
+                strength: 0.5,
+            },
+            secondary_tint: [1.0, 0.8, 0.6],
+            shading_model: ShadingModel::KajiyaKay,
+            marschner: MarschnerParams::default(),
+            blend: BlendMode::Opaque,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MaterialError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl Display for MaterialError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialError::Io(err) => write!(f, "failed to access material file: {}", err),
+            MaterialError::Json(err) => write!(f, "invalid material file: {}", err),
+        }
+    }
+}
+
+impl Error for MaterialError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MaterialError::Io(err) => Some(err),
+            MaterialError::Json(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for MaterialError {
+    fn from(err: io::Error) -> Self {
+        MaterialError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for MaterialError {
+    fn from(err: serde_json::Error) -> Self {
+        MaterialError::Json(err)
+    }
+}
+
+impl HairMaterial {
+    /// read a material from JSON, missing fields keep their defaults
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MaterialError> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MaterialError> {
+        Ok(serde_json::to_writer_pretty(
+            BufWriter::new(File::create(path)?),
+            self,
+        )?)
+    }
+
+    /// the material's uniforms followed by `others`
+    pub fn with_uniforms<U: Uniforms>(&self, others: U) -> MaterialUniforms<'_, U> {
+        MaterialUniforms {
+            material: self,
+            others,
+        }
+    }
+}
+
+impl Uniforms for HairMaterial {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+        f(
+            "ambient_strength",
+            UniformValue::Float(self.ambient_strength),
+        );
+        f(
+            "diffuse_strength",
+            UniformValue::Float(self.diffuse_strength),
+        );
+        f(
+            "specular_strength",
+            UniformValue::Float(self.specular_strength),
+        );
+        f("specular_tint", UniformValue::Vec3(self.specular_tint));
+        f("shift_scale", UniformValue::Float(self.shift_scale));
+        f("primary_shift", UniformValue::Float(self.primary.shift));
+        f(
+            "primary_exponent",
+            UniformValue::Float(self.primary.exponent),
+        );
+        f(
+            "primary_strength",
+            UniformValue::Float(self.primary.strength),
+        );
+        f("secondary_shift", UniformValue::Float(self.secondary.shift));
+        f(
+            "secondary_exponent",
+            UniformValue::Float(self.secondary.exponent),
+        );
+        f(
+            "secondary_strength",
+            UniformValue::Float(self.secondary.strength),
+        );
+        f("secondary_tint", UniformValue::Vec3(self.secondary_tint));
+        f(
+            "shading_model",
+            UniformValue::SignedInt(self.shading_model.uniform()),
+        );
+        f(
+            "marschner_shifts",
+            UniformValue::Vec3(self.marschner.shifts()),
+        );
+        f(
+            "marschner_widths",
+            UniformValue::Vec3(self.marschner.widths()),
+        );
+        f(
+            "marschner_strengths",
+            UniformValue::Vec3([
+                self.marschner.r_strength,
+                self.marschner.tt_strength,
+                self.marschner.trt_strength,
+            ]),
+        );
+        f("marschner_eta", UniformValue::Float(self.marschner.eta));
+        f(
+            "alpha_cutoff",
+            UniformValue::Float(self.blend.alpha_cutoff()),
+        );
+        f(
+            "alpha_coverage",
+            UniformValue::Bool(self.blend.alpha_coverage()),
+        );
+    }
+}
+
+/// uniforms of a [`HairMaterial`] chained with the per-draw ones
+pub struct MaterialUniforms<'m, U> {
+    material: &'m HairMaterial,
+    others: U,
+}
+
+impl<U: Uniforms> Uniforms for MaterialUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+        self.material.visit_values(&mut f);
+        self.others.visit_values(f);
+    }
+}
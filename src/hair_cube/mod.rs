@@ -1,223 +1,345 @@
+use std::error::Error;
+use std::fmt::{self, Display as FmtDisplay, Formatter};
 use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use glium::{
-    Display, DrawParameters, Frame, implement_vertex, IndexBuffer, Program, Surface, texture,
-    uniform, VertexBuffer,
-};
-use glium::texture::SrgbTexture2d;
-use glium::uniforms::Sampler;
+use glium::{Display, DrawParameters, implement_vertex, Surface, texture, uniform, VertexBuffer};
+use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
+use glium::index::IndexBufferAny;
+use glium::texture::{SrgbTexture2d, Texture2d};
+use glium::uniforms::{Sampler, Uniforms};
 use glm::{Mat4, Vec3};
 use image::ImageFormat;
 use num_traits::One;
 
-use crate::Drawable;
+use crate::{Drawable, FrameState};
+use crate::hair_cube::material::{HairMaterial, include_hair_shading};
+use crate::hot_reload::{ProgramReloader, ShaderFiles, TextureReloader};
+use crate::lights::{DEFAULT_MAX_LIGHTS, include_lights, LightRig};
+use crate::mesh::{MeshData, obj};
+use crate::mesh::obj::ObjError;
+use crate::shader::{ProgramBuilder, ShaderError};
+use crate::shader_source;
+use crate::shadows::{ShadowMaps, ShadowPass};
+use crate::texture::{load_linear_texture, load_srgb_texture, TextureError};
+use crate::transform::{mat3_uniform, mat4_uniform, normal_matrix, Transform};
+use crate::transparency::{BlendPrograms, include_blending};
+
+pub mod material;
+
+#[derive(Debug)]
+pub enum HairCubeError {
+    Obj(ObjError),
+    Texture(TextureError),
+    Shader(ShaderError),
+}
+
+impl FmtDisplay for HairCubeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HairCubeError::Obj(err) => write!(f, "{}", err),
+            HairCubeError::Texture(err) => write!(f, "{}", err),
+            HairCubeError::Shader(err) => write!(f, "hair material: {}", err),
+        }
+    }
+}
+
+impl Error for HairCubeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HairCubeError::Obj(err) => Some(err),
+            HairCubeError::Texture(err) => Some(err),
+            HairCubeError::Shader(err) => Some(err),
+        }
+    }
+}
+
+impl From<ObjError> for HairCubeError {
+    fn from(err: ObjError) -> Self {
+        HairCubeError::Obj(err)
+    }
+}
+
+impl From<TextureError> for HairCubeError {
+    fn from(err: TextureError) -> Self {
+        HairCubeError::Texture(err)
+    }
+}
+
+impl From<ShaderError> for HairCubeError {
+    fn from(err: ShaderError) -> Self {
+        HairCubeError::Shader(err)
+    }
+}
+
+/// sources watched in development mode
+struct HotReload {
+    display: Display,
+    program: ProgramReloader,
+    /// the same sources built for the transparency buffers
+    transparent_program: ProgramReloader,
+    texture: Option<TextureReloader>,
+    shift_map: Option<TextureReloader>,
+}
 
 pub struct HairCube {
-    program: Program,
+    name: String,
+    transform: Transform,
+    /// one program per pass, the material's blend mode picks
+    programs: BlendPrograms,
     vertex_buffer: VertexBuffer<Vertex>,
-    indices: IndexBuffer<u8>,
+    indices: IndexBufferAny,
+    /// bounding sphere of the vertices
+    bounds: Option<(Vec3, f32)>,
     texture: SrgbTexture2d,
-    shift_map: SrgbTexture2d,
-    light_color: (f32, f32, f32),
-    light_pos: Vec3,
+    /// sampled as linear data
+    shift_map: Texture2d,
+    material: HairMaterial,
+    hot_reload: Option<HotReload>,
+    lights: LightRig,
+    /// size of the light array the program was built with
+    max_lights: usize,
+    shadows: Rc<ShadowMaps>,
     camera_pos: Vec3,
 }
 
-impl Drawable for HairCube {
-    fn init(display: &Display) -> Self {
-        let program = Program::from_source(
-            display,
-            include_str!("shader.vert"),
-            include_str!("shader.frag"),
-            None,
-        )
-        .unwrap();
-
-        let shape = vec![
-            // 前面
-            Vertex::new(
-                (-0.5, 0.5, 0.5),
-                (0.0, 0.0, 1.0),
-                (0.0, 2.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (0.5, 0.5, 0.5),
-                (0.0, 0.0, 1.0),
-                (1.0, 2.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (0.5, -0.5, 0.5),
-                (0.0, 0.0, 1.0),
-                (1.0, 1.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (-0.5, -0.5, 0.5),
-                (0.0, 0.0, 1.0),
-                (0.0, 1.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            // 右面
-            Vertex::new(
-                (0.5, 0.5, 0.5),
-                (1.0, 0.0, 0.0),
-                (0.0, 2.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (0.5, 0.5, -0.5),
-                (1.0, 0.0, 0.0),
-                (1.0, 2.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (0.5, -0.5, -0.5),
-                (1.0, 0.0, 0.0),
-                (1.0, 1.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (0.5, -0.5, 0.5),
-                (1.0, 0.0, 0.0),
-                (0.0, 1.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            // 后面
-            Vertex::new(
-                (-0.5, 0.5, -0.5),
-                (0.0, 0.0, -1.0),
-                (1.0, 2.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (0.5, 0.5, -0.5),
-                (0.0, 0.0, -1.0),
-                (0.0, 2.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (0.5, -0.5, -0.5),
-                (0.0, 0.0, -1.0),
-                (0.0, 1.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (-0.5, -0.5, -0.5),
-                (0.0, 0.0, -1.0),
-                (1.0, 1.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            // 左面
-            Vertex::new(
-                (-0.5, 0.5, -0.5),
-                (-1.0, 0.0, 0.0),
-                (0.0, 2.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (-0.5, -0.5, -0.5),
-                (-1.0, 0.0, 0.0),
-                (0.0, 1.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (-0.5, -0.5, 0.5),
-                (-1.0, 0.0, 0.0),
-                (1.0, 1.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            Vertex::new(
-                (-0.5, 0.5, 0.5),
-                (-1.0, 0.0, 0.0),
-                (1.0, 2.0 / 3.0),
-                (0.0, 1.0, 0.0),
-            ),
-            // 上面
-            Vertex::new(
-                (-0.5, 0.5, -0.5),
-                (0.0, 1.0, 0.0),
-                (0.0, 1.0),
-                (0.0, 0.0, -1.0),
-            ),
-            Vertex::new(
-                (0.5, 0.5, -0.5),
-                (0.0, 1.0, 0.0),
-                (1.0, 1.0),
-                (0.0, 0.0, -1.0),
-            ),
-            Vertex::new(
-                (0.5, 0.5, 0.5),
-                (0.0, 1.0, 0.0),
-                (1.0, 2.0 / 3.0),
-                (0.0, 0.0, -1.0),
-            ),
-            Vertex::new(
-                (-0.5, 0.5, 0.5),
-                (0.0, 1.0, 0.0),
-                (0.0, 2.0 / 3.0),
-                (0.0, 0.0, -1.0),
-            ),
-            // 下面
-            Vertex::new(
-                (-0.5, -0.5, -0.5),
-                (0.0, -1.0, 0.0),
-                (0.0, 0.0),
-                (0.0, 0.0, -1.0),
-            ),
-            Vertex::new(
-                (0.5, -0.5, -0.5),
-                (0.0, -1.0, 0.0),
-                (1.0, 0.0),
-                (0.0, 0.0, -1.0),
-            ),
-            Vertex::new(
-                (0.5, -0.5, 0.5),
-                (0.0, -1.0, 0.0),
-                (1.0, 1.0 / 3.0),
-                (0.0, 0.0, -1.0),
-            ),
-            Vertex::new(
-                (-0.5, -0.5, 0.5),
-                (0.0, -1.0, 0.0),
-                (0.0, 1.0 / 3.0),
-                (0.0, 0.0, -1.0),
-            ),
-        ];
+impl HairCube {
+    pub fn new(display: &Display) -> Result<Self, ShaderError> {
+        Self::from_mesh(display, &cube_mesh())
+    }
 
-        let vertex_buffer = VertexBuffer::new(display, &shape).unwrap();
+    /// a hair-shaded object with arbitrary geometry and the built-in textures
+    pub fn from_mesh(display: &Display, mesh: &MeshData) -> Result<Self, ShaderError> {
+        let texture = create_texture(display);
+        let shift_map = create_shift_map(display);
+        Self::from_parts(display, mesh, texture, shift_map)
+    }
 
-        let indices = IndexBuffer::new(
+    /// a hair-shaded object with textures read from disk, `None` keeps the built-in one
+    pub fn from_files(
+        display: &Display,
+        mesh: &MeshData,
+        texture: Option<&Path>,
+        shift_map: Option<&Path>,
+    ) -> Result<Self, HairCubeError> {
+        let texture = match texture {
+            Some(path) => load_srgb_texture(display, path)?,
+            None => create_texture(display),
+        };
+        let shift_map = match shift_map {
+            Some(path) => load_linear_texture(display, path)?,
+            None => create_shift_map(display),
+        };
+        Ok(Self::from_parts(display, mesh, texture, shift_map)?)
+    }
+
+    /// a hair-shaded object with arbitrary geometry and textures
+    pub fn from_parts(
+        display: &Display,
+        mesh: &MeshData,
+        texture: SrgbTexture2d,
+        shift_map: Texture2d,
+    ) -> Result<Self, ShaderError> {
+        let programs = Self::programs(display, DEFAULT_MAX_LIGHTS)?;
+        let cube = Self::from_shared(
             display,
-            glium::index::PrimitiveType::TrianglesList,
-            &[
-                0, 3, 2, 0, 2, 1, // 前面
-                4, 7, 6, 4, 6, 5, // 右面
-                9, 10, 11, 9, 11, 8, // 后面
-                12, 13, 14, 12, 14, 15, // 左面
-                16, 19, 18, 16, 18, 17, // 上面
-                23, 20, 21, 23, 21, 22, // 下面
-            ],
-        )
-        .unwrap();
+            mesh,
+            texture,
+            shift_map,
+            programs,
+            DEFAULT_MAX_LIGHTS,
+        );
+        let builder = include_blending(program_builder(DEFAULT_MAX_LIGHTS), false);
+        if let Err(err) = builder.check_uniforms(&cube.uniforms(Mat4::one(), Mat4::one())) {
+            eprintln!("warning: {}: {}", cube.name, err);
+        }
+        Ok(cube)
+    }
 
-        let texture = create_texture(display);
-        let shift_map = create_shift_map(display);
+    /// like [`from_parts`](Self::from_parts), drawing with `programs` from
+    /// [`programs`](Self::programs) built for `max_lights` lights
+    pub fn from_shared(
+        display: &Display,
+        mesh: &MeshData,
+        texture: SrgbTexture2d,
+        shift_map: Texture2d,
+        programs: BlendPrograms,
+        max_lights: usize,
+    ) -> Self {
+        let vertex_buffer = mesh.vertex_buffer(display).unwrap();
+        let indices = mesh.index_buffer(display).unwrap();
 
         Self {
-            program,
+            name: String::from("hair_cube"),
+            transform: Transform::default(),
+            programs,
             vertex_buffer,
             indices,
+            bounds: mesh.bounding_sphere(),
             texture,
             shift_map,
-            light_color: (1.0, 1.0, 1.0),
-            light_pos: Vec3::one(),
+            material: HairMaterial::default(),
+            hot_reload: None,
+            lights: LightRig::default(),
+            max_lights,
+            shadows: Rc::new(ShadowMaps::disabled(display)),
             camera_pos: Vec3::one(),
         }
     }
 
-    fn draw_with_frame(&self, frame: &mut Frame, camera_mat: Mat4, dramparams: &DrawParameters) {
+    /// the programs of hair surfaces lit by up to `max_lights` lights, objects built with
+    /// [`from_shared`](Self::from_shared) can draw with the same ones
+    pub fn programs(display: &Display, max_lights: usize) -> Result<BlendPrograms, ShaderError> {
+        BlendPrograms::build(display, program_builder(max_lights))
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// replace the colour texture, keeping the shift map
+    pub fn with_texture(mut self, texture: SrgbTexture2d) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    /// replace the shift map, keeping the colour texture
+    pub fn with_shift_map(mut self, shift_map: Texture2d) -> Self {
+        self.shift_map = shift_map;
+        self
+    }
+
+    pub fn with_material(mut self, material: HairMaterial) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// rebuild the program for up to `max_lights` lights, the rest of a rig is ignored
+    pub fn with_max_lights(
+        mut self,
+        display: &Display,
+        max_lights: usize,
+    ) -> Result<Self, ShaderError> {
+        self.programs = Self::programs(display, max_lights)?;
+        self.max_lights = max_lights;
+        Ok(self)
+    }
+
+    pub fn material(&self) -> &HairMaterial {
+        &self.material
+    }
+
+    pub fn material_mut(&mut self) -> &mut HairMaterial {
+        &mut self.material
+    }
+
+    pub fn set_material(&mut self, material: HairMaterial) {
+        self.material = material;
+    }
+
+    /// shader sources in the source tree, read by the development mode
+    pub fn shader_files() -> ShaderFiles {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/hair_cube");
+        ShaderFiles {
+            vertex: dir.join("shader.vert"),
+            fragment: dir.join("shader.frag"),
+            geometry: None,
+        }
+    }
+
+    /// development mode: read the shaders from [`shader_files`](Self::shader_files) and the
+    /// given textures from disk, and rebuild them on the next frame whenever they change
+    pub fn enable_hot_reload(
+        &mut self,
+        display: &Display,
+        texture: Option<PathBuf>,
+        shift_map: Option<PathBuf>,
+    ) {
+        let max_lights = self.max_lights;
+        let reloader = |transparent| {
+            ProgramReloader::new(display, Self::shader_files()).with_config(move |builder| {
+                include_blending(
+                    include_hair_shading(include_lights(builder, max_lights)),
+                    transparent,
+                )
+            })
+        };
+        let program = reloader(false);
+        let transparent_program = reloader(true);
+        match program.build().and_then(|opaque| {
+            transparent_program
+                .build()
+                .map(|transparent| BlendPrograms::from_programs(opaque, transparent))
+        }) {
+            Ok(built) => self.programs = built,
+            Err(err) => eprintln!(
+                "{}: shaders on disk do not build, keeping the built-in ones\n{}",
+                self.name, err
+            ),
+        }
+        self.hot_reload = Some(HotReload {
+            display: display.clone(),
+            program,
+            transparent_program,
+            texture: texture.map(TextureReloader::new),
+            shift_map: shift_map.map(TextureReloader::new),
+        });
+    }
+
+    /// error of the last failed shader reload, while the previous program is still in use
+    pub fn reload_error(&self) -> Option<&ShaderError> {
+        self.hot_reload.as_ref()?.program.last_error()
+    }
+
+    fn poll_hot_reload(&mut self) {
+        let Some(hot) = &mut self.hot_reload else {
+            return;
+        };
+        if let Some(program) = hot.program.poll(&self.name) {
+            self.programs.set_opaque(program);
+        }
+        if let Some(program) = hot.transparent_program.poll(&self.name) {
+            self.programs.set_transparent(program);
+        }
+        if let Some(texture) = &mut hot.texture {
+            if let Some(texture) = texture.poll_srgb(&hot.display, &self.name) {
+                self.texture = texture;
+            }
+        }
+        if let Some(shift_map) = &mut hot.shift_map {
+            if let Some(shift_map) = shift_map.poll_linear(&hot.display, &self.name) {
+                self.shift_map = shift_map;
+            }
+        }
+    }
+
+    pub fn set_lights(&mut self, lights: &LightRig) {
+        self.lights.clone_from(lights);
+    }
+
+    pub fn set_camera_pos(&mut self, pos: Vec3) {
+        self.camera_pos = pos;
+    }
+
+    /// load geometry from a Wavefront OBJ file, the mesh is named after the file
+    pub fn from_obj(display: &Display, path: impl AsRef<Path>) -> Result<Self, HairCubeError> {
+        let path = path.as_ref();
+        let mesh = obj::load_obj(path)?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("hair_mesh"));
+        Ok(Self::from_mesh(display, &mesh)?.with_name(name))
+    }
+
+    fn uniforms(&self, camera_mat: Mat4, model_mat: Mat4) -> impl Uniforms + '_ {
         let uniforms = uniform! {
             camera: [
                 *camera_mat.as_array()[0].as_array(),
@@ -225,52 +347,108 @@ impl Drawable for HairCube {
                 *camera_mat.as_array()[2].as_array(),
                 *camera_mat.as_array()[3].as_array(),
             ],
+            model: mat4_uniform(&model_mat),
+            normal_mat: mat3_uniform(&normal_matrix(&model_mat)),
             tnt_texture:
                 Sampler::new(&self.texture)
                         .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear),
             shift_map:
                 Sampler::new(&self.shift_map)
                         .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear),
-            light_color: [self.light_color.0, self.light_color.1, self.light_color.2],
-            light_pos: *self.light_pos.as_array(),
             camera_pos: *self.camera_pos.as_array(),
         };
+        let lights = self.lights.with_uniforms(uniforms);
+        self.material
+            .with_uniforms(self.shadows.with_uniforms(lights))
+    }
+
+    fn draw_into<S: Surface>(
+        &self,
+        target: &mut S,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        let uniforms = self.uniforms(camera_mat, model_mat);
+        let blend = self.material.blend;
 
-        frame
+        target
             .draw(
                 &self.vertex_buffer,
                 &self.indices,
-                &self.program,
+                self.programs.get(blend),
                 &uniforms,
-                dramparams,
+                &blend.draw_parameters(dramparams),
             )
             .unwrap();
     }
 }
 
-impl HairCube {
-    pub fn set_light_color(&mut self, color: (f32, f32, f32)) {
-        self.light_color = color;
+impl Drawable for HairCube {
+    fn name(&self) -> &str {
+        &self.name
     }
-    pub fn set_light_pos(&mut self, pos: Vec3) {
-        self.light_pos = pos;
+
+    fn update(&mut self, state: &FrameState) {
+        self.poll_hot_reload();
+        self.set_lights(state.lights);
+        self.shadows = Rc::clone(state.shadows);
+        self.set_camera_pos(state.camera_pos);
     }
-    pub fn set_camera_pos(&mut self, pos: Vec3) {
-        self.camera_pos = pos;
+
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    fn draw_with_model(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        if !self.material.blend.is_transparent() {
+            self.draw_into(target, camera_mat, model_mat, dramparams);
+        }
+    }
+
+    fn draw_transparent(
+        &self,
+        target: &mut MultiOutputFrameBuffer,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        if self.material.blend.is_transparent() {
+            self.draw_into(target, camera_mat, model_mat, dramparams);
+        }
+    }
+
+    fn draw_shadow(&self, target: &mut SimpleFrameBuffer, pass: &ShadowPass, model_mat: Mat4) {
+        pass.draw(target, &self.vertex_buffer, &self.indices, model_mat);
+    }
+
+    fn bounds(&self) -> Option<(Vec3, f32)> {
+        self.bounds
     }
 }
 
-#[derive(Clone, Copy)]
-struct Vertex {
-    position: [f32; 3],
-    normal: [f32; 3],
-    tex_coord: [f32; 2],
-    binormal: [f32; 3],
+/// vertex layout of hair surfaces, the binormal gives the direction the hair runs in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub binormal: [f32; 3],
 }
 implement_vertex!(Vertex, position location(0), normal location(1), tex_coord location(2), binormal location(3));
 
 impl Vertex {
-    fn new(
+    pub fn new(
         (x, y, z): (f32, f32, f32),
         (nx, ny, nz): (f32, f32, f32),
         (tx, ty): (f32, f32),
@@ -285,6 +463,181 @@ impl Vertex {
     }
 }
 
+/// the built-in cube, with the hair running along v on the sides and along -z on top and bottom
+pub fn cube_mesh() -> MeshData {
+    let shape = vec![
+        // 前面
+        Vertex::new(
+            (-0.5, 0.5, 0.5),
+            (0.0, 0.0, 1.0),
+            (0.0, 2.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (0.5, 0.5, 0.5),
+            (0.0, 0.0, 1.0),
+            (1.0, 2.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (0.5, -0.5, 0.5),
+            (0.0, 0.0, 1.0),
+            (1.0, 1.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (-0.5, -0.5, 0.5),
+            (0.0, 0.0, 1.0),
+            (0.0, 1.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        // 右面
+        Vertex::new(
+            (0.5, 0.5, 0.5),
+            (1.0, 0.0, 0.0),
+            (0.0, 2.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (0.5, 0.5, -0.5),
+            (1.0, 0.0, 0.0),
+            (1.0, 2.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (0.5, -0.5, -0.5),
+            (1.0, 0.0, 0.0),
+            (1.0, 1.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (0.5, -0.5, 0.5),
+            (1.0, 0.0, 0.0),
+            (0.0, 1.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        // 后面
+        Vertex::new(
+            (-0.5, 0.5, -0.5),
+            (0.0, 0.0, -1.0),
+            (1.0, 2.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (0.5, 0.5, -0.5),
+            (0.0, 0.0, -1.0),
+            (0.0, 2.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (0.5, -0.5, -0.5),
+            (0.0, 0.0, -1.0),
+            (0.0, 1.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (-0.5, -0.5, -0.5),
+            (0.0, 0.0, -1.0),
+            (1.0, 1.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        // 左面
+        Vertex::new(
+            (-0.5, 0.5, -0.5),
+            (-1.0, 0.0, 0.0),
+            (0.0, 2.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (-0.5, -0.5, -0.5),
+            (-1.0, 0.0, 0.0),
+            (0.0, 1.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (-0.5, -0.5, 0.5),
+            (-1.0, 0.0, 0.0),
+            (1.0, 1.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        Vertex::new(
+            (-0.5, 0.5, 0.5),
+            (-1.0, 0.0, 0.0),
+            (1.0, 2.0 / 3.0),
+            (0.0, 1.0, 0.0),
+        ),
+        // 上面
+        Vertex::new(
+            (-0.5, 0.5, -0.5),
+            (0.0, 1.0, 0.0),
+            (0.0, 1.0),
+            (0.0, 0.0, -1.0),
+        ),
+        Vertex::new(
+            (0.5, 0.5, -0.5),
+            (0.0, 1.0, 0.0),
+            (1.0, 1.0),
+            (0.0, 0.0, -1.0),
+        ),
+        Vertex::new(
+            (0.5, 0.5, 0.5),
+            (0.0, 1.0, 0.0),
+            (1.0, 2.0 / 3.0),
+            (0.0, 0.0, -1.0),
+        ),
+        Vertex::new(
+            (-0.5, 0.5, 0.5),
+            (0.0, 1.0, 0.0),
+            (0.0, 2.0 / 3.0),
+            (0.0, 0.0, -1.0),
+        ),
+        // 下面
+        Vertex::new(
+            (-0.5, -0.5, -0.5),
+            (0.0, -1.0, 0.0),
+            (0.0, 0.0),
+            (0.0, 0.0, -1.0),
+        ),
+        Vertex::new(
+            (0.5, -0.5, -0.5),
+            (0.0, -1.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 0.0, -1.0),
+        ),
+        Vertex::new(
+            (0.5, -0.5, 0.5),
+            (0.0, -1.0, 0.0),
+            (1.0, 1.0 / 3.0),
+            (0.0, 0.0, -1.0),
+        ),
+        Vertex::new(
+            (-0.5, -0.5, 0.5),
+            (0.0, -1.0, 0.0),
+            (0.0, 1.0 / 3.0),
+            (0.0, 0.0, -1.0),
+        ),
+    ];
+
+    MeshData {
+        vertices: shape,
+        indices: vec![
+            0, 3, 2, 0, 2, 1, // 前面
+            4, 7, 6, 4, 6, 5, // 右面
+            9, 10, 11, 9, 11, 8, // 后面
+            12, 13, 14, 12, 14, 15, // 左面
+            16, 19, 18, 16, 18, 17, // 上面
+            23, 20, 21, 23, 21, 22, // 下面
+        ],
+    }
+}
+
+fn program_builder(max_lights: usize) -> ProgramBuilder {
+    include_hair_shading(include_lights(
+        ProgramBuilder::new(shader_source!("shader.vert"), shader_source!("shader.frag")),
+        max_lights,
+    ))
+}
+
 fn create_texture(display: &Display) -> SrgbTexture2d {
     let image = image::load(
         Cursor::new(include_bytes!("hair1024.png")),
@@ -297,8 +650,9 @@ fn create_texture(display: &Display) -> SrgbTexture2d {
     SrgbTexture2d::new(display, image).unwrap()
 }
 
-fn create_shift_map(display: &Display) -> SrgbTexture2d {
-    //TODO: 改为合适的材质类型
+/// the built-in shift map. it holds offsets rather than colours, so it is uploaded without sRGB
+/// decoding
+pub fn create_shift_map(display: &Display) -> Texture2d {
     let image = image::load(
         Cursor::new(include_bytes!("shift_map1024.png")),
         ImageFormat::Png,
@@ -307,5 +661,5 @@ fn create_shift_map(display: &Display) -> SrgbTexture2d {
     .to_rgba8();
     let id = image.dimensions();
     let image = texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), id);
-    SrgbTexture2d::new(display, image).unwrap()
+    Texture2d::new(display, image).unwrap()
 }
@@ -1,6 +1,8 @@
 #version 330 core
 
-out vec4 FragColor;
+#include "lights.glsl"
+#include "blend.glsl"
+#include "hair_shading.glsl"
 
 in vec2 TexCoord;
 in vec3 Normal;
@@ -8,33 +10,30 @@ in vec3 FragPos;
 in vec3 Binormal;
 
 uniform sampler2D tnt_texture;
-uniform sampler2D shifp_map;
-uniform vec3 light_color;
-uniform vec3 light_pos;
+// linear data, 0.5 leaves the tangent in place
+uniform sampler2D shift_map;
 uniform vec3 camera_pos;
 
-
 void main() {
-    float ambient_strength = 0.2;
-    vec3 ambient = ambient_strength * light_color;
-
-    float diffuse_strength = 0.5;
-    vec3 light_dir = normalize(light_pos - FragPos);
-    float diff = max(dot(light_dir, Normal), 0.0);
-    vec3 diffuse = diffuse_strength * diff * light_color;
+    // interpolation denormalizes the vectors coming from the vertex shader
+    vec3 normal = normalize(Normal);
+    vec3 surface_binormal = normalize(Binormal);
+    vec4 albedo = texture(tnt_texture, TexCoord);
 
-    float specular_strength = 1.5;
-    float ctrl = max(normalize(dot(light_dir, Normal)), 0.0);
-    vec3 binormal = 0.3 * (length(texture(shifp_map, TexCoord)) - 0.5) * Normal + Binormal;
+    vec3 ambient = ambient_strength * ambient_light;
 
+    float shift = shift_scale * (texture(shift_map, TexCoord).r - 0.5);
     vec3 view_dir = normalize(camera_pos - FragPos);
-    vec3 half_vec = normalize(view_dir + light_dir);
-    float result = sqrt(1.0 - pow(dot(half_vec, binormal), 2));
-    float dir_atten = smoothstep(-1.0, 0.0, dot(half_vec, binormal));
-    float spec = dir_atten * pow(max(result, 0.0), 1024);
-    vec3 specular = specular_strength * ctrl * spec * light_color;
-
-    vec3 light_result = ambient + diffuse + specular;
 
-    FragColor = texture(tnt_texture, TexCoord) * vec4(light_result, 0.0);
+    vec3 diffuse = vec3(0.0);
+    vec3 specular = vec3(0.0);
+    for (int i = 0; i < active_lights(); ++i) {
+        vec3 light_dir = light_direction(lights[i], FragPos);
+        vec3 radiance = light_radiance(lights[i], FragPos);
+        radiance *= light_shadow(lights[i], FragPos, normal);
+        diffuse += diffuse_strength * max(dot(light_dir, normal), 0.0) * radiance;
+        specular += hair_specular(surface_binormal, normal, shift, light_dir, view_dir, albedo.rgb) * radiance;
+    }
+
+    write_color(vec4(hair_color(albedo.rgb, ambient + diffuse, specular), albedo.a));
 }
\ No newline at end of file
@@ -11,11 +11,16 @@ out vec3 FragPos;
 out vec3 Binormal;
 
 uniform mat4 camera;
+uniform mat4 model;
+uniform mat3 normal_mat;
 
 void main() {
-    gl_Position = camera * vec4(position, 1.0);
+    vec4 world_pos = model * vec4(position, 1.0);
+    gl_Position = camera * world_pos;
     TexCoord = tex_coord;
-    Normal = normal;
-    FragPos = position;
-    Binormal = binormal;
+    // normals need the inverse transpose, while the binormal lies in the surface
+    // and follows the model matrix like any other tangent vector
+    Normal = normalize(normal_mat * normal);
+    FragPos = vec3(world_pos);
+    Binormal = normalize(mat3(model) * binormal);
 }
\ No newline at end of file
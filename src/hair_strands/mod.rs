@@ -0,0 +1,431 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use glium::{Display, DrawParameters, implement_vertex, IndexBuffer, Surface, uniform, VertexBuffer};
+use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
+use glium::index::PrimitiveType;
+use glium::uniforms::Uniforms;
+use glm::{Mat4, Vec3, Vec4};
+use num_traits::One;
+
+use crate::{Drawable, FrameState};
+use crate::hair_cube::material::{HairMaterial, include_hair_shading, MaterialUniforms};
+use crate::lights::{DEFAULT_MAX_LIGHTS, include_lights, LightRig, LightUniforms};
+use crate::shader::{ProgramBuilder, ShaderError};
+use crate::shader_source;
+use crate::shadows::{ShadowMaps, ShadowPass, ShadowUniforms};
+use crate::shadows::deep_opacity::{
+    DeepOpacityError, DeepOpacityMaps, DeepOpacitySettings, DeepOpacityUniforms, DeepOpacityView,
+    include_deep_opacity, transform_sphere,
+};
+use crate::strands::{bounding_sphere, line_geometry, Strand};
+use crate::strands::ribbon::{expand_ribbons, ribbon_indices, RibbonParams};
+use crate::transform::{mat4_uniform, Transform};
+use crate::transparency::{BlendMode, BlendPrograms};
+
+/// how strands are turned into primitives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrandMode {
+    /// one pixel wide lines, cheap but aliased
+    Lines,
+    /// camera-facing ribbons rebuilt on the CPU whenever the camera moves
+    CpuRibbons,
+    /// camera-facing ribbons expanded by a geometry shader
+    GpuRibbons,
+}
+
+/// hair fibres shaded with a [`HairMaterial`] around the strand tangent
+pub struct HairStrands {
+    name: String,
+    transform: Transform,
+    mode: StrandMode,
+    ribbon_params: RibbonParams,
+    /// kept for the CPU ribbon expansion
+    strands: Vec<Strand>,
+    /// local centre and radius of the strands, what the deep opacity maps and directional
+    /// shadow maps are fitted to
+    bounds: (Vec3, f32),
+    program: BlendPrograms,
+    vertex_buffer: VertexBuffer<StrandVertex>,
+    indices: IndexBuffer<u32>,
+    ribbon_program: BlendPrograms,
+    ribbon_buffer: VertexBuffer<RibbonVertex>,
+    /// camera position and model matrix `ribbon_buffer` was expanded for
+    ribbon_key: Cell<Option<(Vec3, Mat4)>>,
+    ribbon_indices: IndexBuffer<u32>,
+    ribbon_gs_program: BlendPrograms,
+    root_color: [f32; 3],
+    tip_color: [f32; 3],
+    /// alpha of every fibre, only transparent strands blend it
+    opacity: f32,
+    /// lobes and blend mode, the strands have no shift map
+    material: HairMaterial,
+    lights: LightRig,
+    shadows: Rc<ShadowMaps>,
+    deep_opacity: DeepOpacityMaps,
+    /// the views the deep opacity maps were last rendered from
+    opacity_views: Vec<DeepOpacityView>,
+    camera_pos: Vec3,
+}
+
+impl HairStrands {
+    pub fn new(display: &Display, strands: &[Strand]) -> Result<Self, ShaderError> {
+        let [program, ribbon_program, ribbon_gs_program] =
+            build_programs(display, DEFAULT_MAX_LIGHTS)?;
+
+        let (vertices, indices) = line_geometry(strands);
+        let vertex_buffer = VertexBuffer::new(display, &vertices).unwrap();
+        let indices = IndexBuffer::new(display, PrimitiveType::LinesList, &indices).unwrap();
+
+        // rewritten whenever the camera moves, the camera position only matters for the contents
+        let ribbon_vertices = expand_ribbons(strands, Vec3::one(), &RibbonParams::default());
+        let ribbon_buffer = VertexBuffer::dynamic(display, &ribbon_vertices).unwrap();
+        let ribbon_indices = IndexBuffer::new(
+            display,
+            PrimitiveType::TrianglesList,
+            &ribbon_indices(strands),
+        )
+        .unwrap();
+
+        Ok(Self {
+            name: String::from("hair_strands"),
+            transform: Transform::default(),
+            mode: StrandMode::CpuRibbons,
+            ribbon_params: RibbonParams::default(),
+            strands: strands.to_vec(),
+            bounds: bounding_sphere(strands).unwrap_or((Vec3::new(0.0, 0.0, 0.0), 0.0)),
+            program,
+            vertex_buffer,
+            indices,
+            ribbon_program,
+            ribbon_buffer,
+            ribbon_key: Cell::new(None),
+            ribbon_indices,
+            ribbon_gs_program,
+            root_color: [0.25, 0.15, 0.08],
+            tip_color: [0.55, 0.4, 0.25],
+            opacity: 1.0,
+            material: HairMaterial::default(),
+            lights: LightRig::default(),
+            shadows: Rc::new(ShadowMaps::disabled(display)),
+            deep_opacity: DeepOpacityMaps::disabled(display),
+            opacity_views: Vec::new(),
+            camera_pos: Vec3::one(),
+        })
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_material(mut self, material: HairMaterial) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// rebuild the programs for up to `max_lights` lights, the rest of a rig is ignored
+    pub fn with_max_lights(
+        mut self,
+        display: &Display,
+        max_lights: usize,
+    ) -> Result<Self, ShaderError> {
+        [self.program, self.ribbon_program, self.ribbon_gs_program] =
+            build_programs(display, max_lights)?;
+        Ok(self)
+    }
+
+    /// shadow the strands by each other through deep opacity maps, rendered once per frame by
+    /// [`render_maps`](Drawable::render_maps)
+    pub fn with_deep_opacity(
+        mut self,
+        display: &Display,
+        settings: DeepOpacitySettings,
+    ) -> Result<Self, DeepOpacityError> {
+        self.deep_opacity = DeepOpacityMaps::new(display, settings)?;
+        Ok(self)
+    }
+
+    pub fn set_mode(&mut self, mode: StrandMode) {
+        self.mode = mode;
+    }
+
+    pub fn mode(&self) -> StrandMode {
+        self.mode
+    }
+
+    pub fn set_ribbon_params(&mut self, params: RibbonParams) {
+        self.ribbon_params = params;
+        self.ribbon_key.set(None);
+    }
+
+    /// linear colours at the root and at the tip, interpolated along the strand
+    pub fn set_colors(&mut self, root_color: [f32; 3], tip_color: [f32; 3]) {
+        self.root_color = root_color;
+        self.tip_color = tip_color;
+    }
+
+    pub fn material(&self) -> &HairMaterial {
+        &self.material
+    }
+
+    pub fn material_mut(&mut self) -> &mut HairMaterial {
+        &mut self.material
+    }
+
+    pub fn set_material(&mut self, material: HairMaterial) {
+        self.material = material;
+    }
+
+    /// the material's blend mode
+    pub fn set_blend_mode(&mut self, blend: BlendMode) {
+        self.material.blend = blend;
+    }
+
+    /// fibres thinner than a pixel look best slightly transparent and blended, see
+    /// [`set_blend_mode`](Self::set_blend_mode)
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
+    /// `uniforms` with those of the material, the lights, their shadow maps and the deep
+    /// opacity maps
+    fn lit_uniforms<'a, U: Uniforms>(
+        &'a self,
+        uniforms: U,
+    ) -> DeepOpacityUniforms<'a, ShadowUniforms<'a, LightUniforms<'a, MaterialUniforms<'a, U>>>>
+    {
+        self.deep_opacity.with_uniforms(
+            &self.opacity_views,
+            self.shadows.with_uniforms(
+                self.lights
+                    .with_uniforms(self.material.with_uniforms(uniforms)),
+            ),
+        )
+    }
+
+    /// expand the CPU ribbons again if the camera or the strands moved since the last time
+    fn update_ribbons(&self, model_mat: Mat4) {
+        let key = Some((self.camera_pos, model_mat));
+        if self.ribbon_key.get() == key {
+            return;
+        }
+        // expanded in world space, so widths are world units like on the GPU path
+        let world_strands: Vec<Strand> = self
+            .strands
+            .iter()
+            .map(|strand| Strand {
+                points: strand
+                    .points
+                    .iter()
+                    .map(|p| {
+                        let w = model_mat * Vec4::new(p[0], p[1], p[2], 1.0);
+                        [w.x, w.y, w.z]
+                    })
+                    .collect(),
+                widths: strand.widths.clone(),
+            })
+            .collect();
+        self.ribbon_buffer.write(&expand_ribbons(
+            &world_strands,
+            self.camera_pos,
+            &self.ribbon_params,
+        ));
+        self.ribbon_key.set(key);
+    }
+
+    fn draw_into<S: Surface>(
+        &self,
+        target: &mut S,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        let blend = self.material.blend;
+        let params = blend.draw_parameters(dramparams);
+
+        match self.mode {
+            StrandMode::Lines => {
+                let uniforms = uniform! {
+                    camera: mat4_uniform(&camera_mat),
+                    model: mat4_uniform(&model_mat),
+                    root_color: self.root_color,
+                    tip_color: self.tip_color,
+                    camera_pos: *self.camera_pos.as_array(),
+                    opacity: self.opacity,
+                };
+                target
+                    .draw(
+                        &self.vertex_buffer,
+                        &self.indices,
+                        self.program.get(blend),
+                        &self.lit_uniforms(uniforms),
+                        &params,
+                    )
+                    .unwrap();
+            }
+            StrandMode::CpuRibbons => {
+                self.update_ribbons(model_mat);
+
+                let uniforms = uniform! {
+                    camera: mat4_uniform(&camera_mat),
+                    model: mat4_uniform(&Mat4::one()),
+                    root_color: self.root_color,
+                    tip_color: self.tip_color,
+                    camera_pos: *self.camera_pos.as_array(),
+                    opacity: self.opacity,
+                };
+                target
+                    .draw(
+                        &self.ribbon_buffer,
+                        &self.ribbon_indices,
+                        self.ribbon_program.get(blend),
+                        &self.lit_uniforms(uniforms),
+                        &params,
+                    )
+                    .unwrap();
+            }
+            StrandMode::GpuRibbons => {
+                let uniforms = uniform! {
+                    camera: mat4_uniform(&camera_mat),
+                    model: mat4_uniform(&model_mat),
+                    width_scale: self.ribbon_params.width_scale,
+                    root_color: self.root_color,
+                    tip_color: self.tip_color,
+                    camera_pos: *self.camera_pos.as_array(),
+                    opacity: self.opacity,
+                };
+                target
+                    .draw(
+                        &self.vertex_buffer,
+                        &self.indices,
+                        self.ribbon_gs_program.get(blend),
+                        &self.lit_uniforms(uniforms),
+                        &params,
+                    )
+                    .unwrap();
+            }
+        }
+    }
+}
+
+impl Drawable for HairStrands {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn update(&mut self, state: &FrameState) {
+        self.lights.clone_from(state.lights);
+        self.shadows = Rc::clone(state.shadows);
+        self.camera_pos = state.camera_pos;
+    }
+
+    fn render_maps(&mut self, model_mat: Mat4) {
+        let (centre, radius) = transform_sphere(&model_mat, self.bounds.0, self.bounds.1);
+        self.opacity_views = DeepOpacityView::fit(&self.lights, centre, radius);
+        self.deep_opacity.render(
+            &self.opacity_views,
+            &self.vertex_buffer,
+            &self.indices,
+            model_mat,
+        );
+    }
+
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    fn draw_with_model(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        if !self.material.blend.is_transparent() {
+            self.draw_into(target, camera_mat, model_mat, dramparams);
+        }
+    }
+
+    fn draw_transparent(
+        &self,
+        target: &mut MultiOutputFrameBuffer,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        if self.material.blend.is_transparent() {
+            self.draw_into(target, camera_mat, model_mat, dramparams);
+        }
+    }
+
+    /// strands cast their shadow as lines in every mode, ribbons face the camera rather than
+    /// the light
+    fn draw_shadow(&self, target: &mut SimpleFrameBuffer, pass: &ShadowPass, model_mat: Mat4) {
+        pass.draw(target, &self.vertex_buffer, &self.indices, model_mat);
+    }
+
+    fn bounds(&self) -> Option<(Vec3, f32)> {
+        Some(self.bounds)
+    }
+}
+
+/// one point of a strand. the width is not used by line rendering, which is always one pixel
+/// wide, but the geometry shader expands the same vertices into ribbons
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrandVertex {
+    pub position: [f32; 3],
+    /// unit direction from root towards tip
+    pub tangent: [f32; 3],
+    /// 0 at the root, 1 at the tip
+    pub param: f32,
+    pub width: f32,
+}
+implement_vertex!(StrandVertex, position location(0), tangent location(1), param location(2), width location(3));
+
+/// one edge vertex of a camera-facing ribbon
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RibbonVertex {
+    pub position: [f32; 3],
+    /// strand tangent, shading ignores the ribbon orientation
+    pub tangent: [f32; 3],
+    /// (across, along): 0..1 from the left to the right edge, 0..1 from root to tip
+    pub tex_coord: [f32; 2],
+}
+implement_vertex!(RibbonVertex, position location(0), tangent location(1), tex_coord location(2));
+
+/// line, CPU ribbon and GPU ribbon programs, all shaded by the same fragment shader
+fn build_programs(display: &Display, max_lights: usize) -> Result<[BlendPrograms; 3], ShaderError> {
+    let build = |builder: ProgramBuilder| {
+        BlendPrograms::build(
+            display,
+            include_hair_shading(include_deep_opacity(include_lights(builder, max_lights))),
+        )
+    };
+    Ok([
+        build(ProgramBuilder::new(
+            shader_source!("shader.vert"),
+            shader_source!("shader.frag"),
+        ))?,
+        build(ProgramBuilder::new(
+            shader_source!("ribbon.vert"),
+            shader_source!("shader.frag"),
+        ))?,
+        build(
+            ProgramBuilder::new(
+                shader_source!("ribbon_gs.vert"),
+                shader_source!("shader.frag"),
+            )
+            .geometry(shader_source!("ribbon.geom")),
+        )?,
+    ])
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 tangent;
+layout (location = 2) in vec2 tex_coord;
+
+out vec3 Tangent;
+out vec3 FragPos;
+out float Param;
+out vec2 TexCoord;
+
+uniform mat4 camera;
+uniform mat4 model;
+
+void main() {
+    vec4 world_pos = model * vec4(position, 1.0);
+    gl_Position = camera * world_pos;
+    Tangent = normalize(mat3(model) * tangent);
+    FragPos = vec3(world_pos);
+    Param = tex_coord.y;
+    TexCoord = tex_coord;
+}
\ No newline at end of file
@@ -0,0 +1,20 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 tangent;
+layout (location = 2) in float param;
+layout (location = 3) in float width;
+
+out vec3 GsTangent;
+out float GsParam;
+out float GsWidth;
+
+uniform mat4 model;
+
+void main() {
+    // stays in world space, the geometry shader needs it to face the camera
+    gl_Position = model * vec4(position, 1.0);
+    GsTangent = normalize(mat3(model) * tangent);
+    GsParam = param;
+    GsWidth = width;
+}
\ No newline at end of file
@@ -0,0 +1,55 @@
+#version 330 core
+
+#include "lights.glsl"
+#include "deep_opacity.glsl"
+#include "blend.glsl"
+#include "hair_shading.glsl"
+
+in vec3 Tangent;
+in vec3 FragPos;
+in float Param;
+// (across, along) the ribbon, lines sit in its middle
+in vec2 TexCoord;
+
+uniform vec3 root_color;
+uniform vec3 tip_color;
+uniform vec3 camera_pos;
+uniform float opacity;
+
+
+void main() {
+    vec3 tangent = normalize(Tangent);
+    vec3 albedo = mix(root_color, tip_color, Param);
+
+    vec3 ambient = ambient_strength * ambient_light;
+
+    vec3 view_dir = normalize(camera_pos - FragPos);
+    // a fibre has no normal of its own, the lobes shift towards the viewer as on a ribbon
+    vec3 normal = view_dir - tangent * dot(view_dir, tangent);
+    normal /= max(length(normal), 1e-4);
+    vec3 diffuse = vec3(0.0);
+    vec3 specular = vec3(0.0);
+    for (int i = 0; i < active_lights(); ++i) {
+        vec3 light_dir = light_direction(lights[i], FragPos);
+        // the shadow lookup moves towards the light
+        vec3 radiance = light_radiance(lights[i], FragPos);
+        radiance *= light_shadow(lights[i], FragPos, light_dir);
+        // the strands in front of this one, seen from the light
+        radiance *= hair_transmittance(i, FragPos);
+
+        // Kajiya-Kay diffuse uses the sine between tangent and light
+        float diff = sqrt(max(1.0 - pow(dot(tangent, light_dir), 2), 0.0));
+        diffuse += diffuse_strength * diff * radiance;
+        specular += hair_specular(tangent, normal, 0.0, light_dir, view_dir, albedo) * radiance;
+    }
+
+    // a fibre is round, so it covers less of the ribbon towards the edges. the falloff spans
+    // about a pixel, smoothing the edges of wide ribbons
+    float centre = 1.0 - abs(2.0 * TexCoord.x - 1.0);
+    float coverage = clamp(centre / max(fwidth(centre), 1e-4), 0.0, 1.0);
+    // and the tip thins out instead of ending in a cut
+    coverage *= 1.0 - smoothstep(0.9, 1.0, TexCoord.y);
+
+    // the coverage only shows where alpha does, in the cutout, coverage and transparent modes
+    write_color(vec4(hair_color(albedo, ambient + diffuse, specular), opacity * coverage));
+}
\ No newline at end of file
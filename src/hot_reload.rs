@@ -0,0 +1,187 @@
+//! development mode: shaders and textures read from disk and rebuilt when their files change.
+//! changes are detected by polling modification times, which is cheap for the handful of files
+//! a drawable uses and needs no platform specific watcher
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use glium::{Display, Program};
+use glium::texture::{SrgbTexture2d, Texture2d};
+
+use crate::shader::{ProgramBuilder, ShaderError, ShaderSource};
+use crate::texture::{load_linear_texture, load_srgb_texture, TextureError};
+
+/// files whose modification times are compared on every poll
+pub struct WatchedFiles {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl WatchedFiles {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = modified(&path);
+                    (path, modified)
+                })
+                .collect(),
+        }
+    }
+
+    /// whether any file changed since the last call. a file that is missing for a moment, as
+    /// happens while an editor saves, counts as changed once it is back
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last) in &mut self.files {
+            let now = modified(path);
+            if now.is_some() && now != *last {
+                changed = true;
+            }
+            *last = now;
+        }
+        changed
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderFiles {
+    pub vertex: PathBuf,
+    pub fragment: PathBuf,
+    pub geometry: Option<PathBuf>,
+}
+
+impl ShaderFiles {
+    fn paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        [&self.vertex, &self.fragment]
+            .into_iter()
+            .chain(self.geometry.as_ref())
+            .cloned()
+    }
+}
+
+/// rebuilds a program whenever one of its source files changes
+pub struct ProgramReloader {
+    display: Display,
+    files: ShaderFiles,
+    watch: WatchedFiles,
+    /// includes and defines the embedded program was built with
+    configure: Box<dyn Fn(ProgramBuilder) -> ProgramBuilder>,
+    last_error: Option<ShaderError>,
+}
+
+impl ProgramReloader {
+    pub fn new(display: &Display, files: ShaderFiles) -> Self {
+        Self {
+            display: display.clone(),
+            watch: WatchedFiles::new(files.paths()),
+            files,
+            configure: Box::new(|builder| builder),
+            last_error: None,
+        }
+    }
+
+    /// applied to the builder before every build, e.g. to register includes
+    pub fn with_config(
+        mut self,
+        configure: impl Fn(ProgramBuilder) -> ProgramBuilder + 'static,
+    ) -> Self {
+        self.configure = Box::new(configure);
+        self
+    }
+
+    /// compile the program from the files as they are now
+    pub fn build(&self) -> Result<Program, ShaderError> {
+        self.builder()?.build(&self.display)
+    }
+
+    /// builder over the files as they are now, includes are looked up next to them
+    pub fn builder(&self) -> Result<ProgramBuilder, ShaderError> {
+        let mut builder = ProgramBuilder::new(
+            ShaderSource::from_file(&self.files.vertex)?,
+            ShaderSource::from_file(&self.files.fragment)?,
+        );
+        if let Some(geometry) = &self.files.geometry {
+            builder = builder.geometry(ShaderSource::from_file(geometry)?);
+        }
+        Ok((self.configure)(builder))
+    }
+
+    /// the rebuilt program when a source changed and compiled. a failed build is printed and
+    /// kept in [`last_error`](Self::last_error), the caller keeps drawing with its old program
+    pub fn poll(&mut self, owner: &str) -> Option<Program> {
+        if !self.watch.changed() {
+            return None;
+        }
+        match self.build() {
+            Ok(program) => {
+                eprintln!("{}: shaders reloaded", owner);
+                self.last_error = None;
+                Some(program)
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}: shader reload failed, keeping the last good program\n{}",
+                    owner, err
+                );
+                self.last_error = Some(err);
+                None
+            }
+        }
+    }
+
+    pub fn last_error(&self) -> Option<&ShaderError> {
+        self.last_error.as_ref()
+    }
+}
+
+/// reloads a texture whenever its file changes
+pub struct TextureReloader {
+    path: PathBuf,
+    watch: WatchedFiles,
+}
+
+impl TextureReloader {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            watch: WatchedFiles::new([path.clone()]),
+            path,
+        }
+    }
+
+    pub fn poll_srgb(&mut self, display: &Display, owner: &str) -> Option<SrgbTexture2d> {
+        self.poll(owner, |path| load_srgb_texture(display, path))
+    }
+
+    pub fn poll_linear(&mut self, display: &Display, owner: &str) -> Option<Texture2d> {
+        self.poll(owner, |path| load_linear_texture(display, path))
+    }
+
+    fn poll<T>(
+        &mut self,
+        owner: &str,
+        load: impl FnOnce(&Path) -> Result<T, TextureError>,
+    ) -> Option<T> {
+        if !self.watch.changed() {
+            return None;
+        }
+        match load(&self.path) {
+            Ok(texture) => {
+                eprintln!("{}: reloaded {}", owner, self.path.display());
+                Some(texture)
+            }
+            Err(err) => {
+                eprintln!(
+                    "{}: texture reload failed, keeping the old texture\n{}",
+                    owner, err
+                );
+                None
+            }
+        }
+    }
+}
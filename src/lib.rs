@@ -1,14 +1,103 @@
-use glium::{Display, DrawParameters, Frame};
-use glm::Mat4;
+use std::rc::Rc;
+use std::time::Duration;
+
+use glium::DrawParameters;
+use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
+use glm::{Mat4, Vec3};
+
+use crate::lights::LightRig;
+use crate::shadows::{ShadowMaps, ShadowPass};
+use crate::transform::Transform;
 
 pub mod camera;
 pub mod camera_events;
+pub mod hot_reload;
+pub mod light_events;
+pub mod lights;
+pub mod marschner;
+pub mod options;
+pub mod post;
 pub mod refresh_rate;
+pub mod render_target;
+pub mod scene_graph;
+pub mod shader;
+pub mod shadows;
+pub mod strands;
+pub mod texture;
+pub mod tone_mapping;
+pub mod transform;
+pub mod transparency;
 
+pub mod diffuse_mesh;
+pub mod gltf_import;
 pub mod hair_cube;
+pub mod hair_strands;
 pub mod light_source;
+pub mod mesh;
+
+/// per-frame state handed to every drawable before it is drawn
+#[derive(Clone, Copy, Debug)]
+pub struct FrameState<'a> {
+    pub delta_time: Duration,
+    pub camera_pos: Vec3,
+    pub lights: &'a LightRig,
+    /// the lights' shadow maps, rendered before the frame is drawn
+    pub shadows: &'a Rc<ShadowMaps>,
+}
 
 pub trait Drawable {
-    fn init(display: &Display) -> Self;
-    fn draw_with_frame(&self, frame: &mut Frame, camera_mat: Mat4, dramparams: &DrawParameters);
+    /// name used to look the object up in a scene
+    fn name(&self) -> &str;
+
+    /// called once per frame before drawing
+    fn update(&mut self, _state: &FrameState) {}
+
+    /// render maps of the object's own, once per frame after the shadow maps and before
+    /// drawing, with the model matrix the object is drawn with
+    fn render_maps(&mut self, _model_mat: Mat4) {}
+
+    /// placement of the object, relative to its parent when it lives in a scene graph
+    fn transform(&self) -> &Transform;
+
+    fn transform_mut(&mut self) -> &mut Transform;
+
+    /// draw with an explicit model matrix. transparent objects draw nothing here, they draw
+    /// in [`draw_transparent`](Self::draw_transparent) once everything opaque is drawn
+    fn draw_with_model(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    );
+
+    /// add the object to the order-independent transparency buffers, opaque objects keep the
+    /// default
+    fn draw_transparent(
+        &self,
+        _target: &mut MultiOutputFrameBuffer,
+        _camera_mat: Mat4,
+        _model_mat: Mat4,
+        _dramparams: &DrawParameters,
+    ) {
+    }
+
+    /// draw the object's depth into a shadow map, objects that cast no shadow keep the default
+    fn draw_shadow(&self, _target: &mut SimpleFrameBuffer, _pass: &ShadowPass, _model_mat: Mat4) {}
+
+    /// centre and radius of a sphere around what [`draw_shadow`](Self::draw_shadow) draws, in
+    /// model space. directional shadow maps are fitted to the casters' spheres
+    fn bounds(&self) -> Option<(Vec3, f32)> {
+        None
+    }
+
+    /// draw with the object's own transform as the model matrix
+    fn draw_with_frame(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        self.draw_with_model(target, camera_mat, self.transform().matrix(), dramparams);
+    }
 }
@@ -0,0 +1,443 @@
+//! placing lights with the mouse. the right button picks a light gizmo and drags it in the
+//! plane facing the camera, holding shift, ctrl or alt constrains the drag to the x, y or z
+//! axis, and the wheel pushes the picked or hovered light away from or toward the camera.
+//! the left button stays with [`CameraHandler`](crate::camera_events::CameraHandler), and
+//! lights stay put while it holds the cursor, where shift moves the camera down
+
+use glium::glutin::event::{
+    ElementState, Event, ModifiersState, MouseButton, MouseScrollDelta, WindowEvent,
+};
+use glium::glutin::window::Window;
+use glm::{GenSquareMat, Vec3, Vec4};
+
+use crate::camera::Camera;
+use crate::lights::LightRig;
+
+/// bounding sphere of the unit cube a [`Light`](crate::light_source::Light) draws
+const PICK_RADIUS: f32 = 0.87;
+/// fraction of the camera distance one wheel step moves a light
+const WHEEL_STEP: f32 = 0.1;
+/// lights are not pushed closer to the camera than this
+const MIN_DISTANCE: f32 = 0.5;
+
+/// a half line from `origin` along the normalized `direction`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// ray through the pixel at `cursor` in a window of `size`, by unprojecting the near and
+    /// far plane through the camera matrix. `None` if the matrix cannot be inverted
+    pub fn from_cursor(
+        camera_mat: &glm::Mat4,
+        cursor: (f32, f32),
+        size: (f32, f32),
+    ) -> Option<Self> {
+        let x = 2.0 * cursor.0 / size.0 - 1.0;
+        let y = 1.0 - 2.0 * cursor.1 / size.1;
+        let inverse = camera_mat.inverse()?;
+        let unproject = |z: f32| {
+            let p = inverse * Vec4::new(x, y, z, 1.0);
+            Vec3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+        };
+        let near = unproject(-1.0);
+        let direction = glm::normalize(unproject(1.0) - near);
+        // a nearly singular matrix can still unproject to infinity
+        let finite = [near, direction]
+            .iter()
+            .all(|v| v.as_array().iter().all(|c| c.is_finite()));
+        finite.then_some(Self {
+            origin: near,
+            direction,
+        })
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// distance along the ray to the first hit of the sphere
+    pub fn hit_sphere(&self, center: Vec3, radius: f32) -> Option<f32> {
+        let to_center = center - self.origin;
+        let along = glm::dot(to_center, self.direction);
+        let miss2 = glm::dot(to_center, to_center) - along * along;
+        if miss2 > radius * radius {
+            return None;
+        }
+        let half_chord = (radius * radius - miss2).sqrt();
+        // zero when the ray starts inside the sphere
+        (along + half_chord >= 0.0).then(|| (along - half_chord).max(0.0))
+    }
+
+    /// distance along the ray to the plane through `point` with `normal`
+    pub fn hit_plane(&self, point: Vec3, normal: Vec3) -> Option<f32> {
+        let denom = glm::dot(self.direction, normal);
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+        Some(glm::dot(point - self.origin, normal) / denom).filter(|&t| t >= 0.0)
+    }
+
+    /// parameter of the point on the line `point + s * axis` closest to the ray
+    pub fn closest_on_line(&self, point: Vec3, axis: Vec3) -> Option<f32> {
+        let w = point - self.origin;
+        let b = glm::dot(axis, self.direction);
+        let denom = glm::dot(axis, axis) - b * b;
+        if denom.abs() < 1e-6 {
+            // the axis points along the ray
+            return None;
+        }
+        Some((b * glm::dot(self.direction, w) - glm::dot(axis, w)) / denom)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Constraint {
+    /// in the plane through the light facing the camera
+    ScreenPlane {
+        normal: Vec3,
+    },
+    Axis(Vec3),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Drag {
+    light: usize,
+    /// gizmo position when the drag started
+    start: Vec3,
+    /// from the point under the cursor to the gizmo, keeps the gizmo from jumping
+    offset: Vec3,
+    /// where along the axis the cursor was when an axis drag started
+    axis_start: f32,
+    constraint: Constraint,
+}
+
+pub struct LightDragHandler {
+    cursor: Option<(f32, f32)>,
+    modifiers: ModifiersState,
+    drag: Option<Drag>,
+    /// off while the camera holds the cursor
+    active: bool,
+    /// light let go of since the last [`take_released`](LightDragHandler::take_released)
+    released: Option<usize>,
+}
+
+impl Default for LightDragHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LightDragHandler {
+    pub fn new() -> Self {
+        Self {
+            cursor: None,
+            modifiers: ModifiersState::empty(),
+            drag: None,
+            active: true,
+            released: None,
+        }
+    }
+
+    /// whether a light is being dragged, animations should leave the rig alone meanwhile
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// index of the dragged light
+    pub fn dragged_light(&self) -> Option<usize> {
+        self.drag.map(|drag| drag.light)
+    }
+
+    /// index of the light the mouse last put down, after a drag or a wheel push, once. its
+    /// animation should carry on from there, see
+    /// [`RigAnimation::rebase`](crate::lights::animation::RigAnimation::rebase)
+    pub fn take_released(&mut self) -> Option<usize> {
+        self.released.take()
+    }
+
+    /// stop picking and dragging lights, e.g. while the camera holds the cursor. a drag in
+    /// progress ends where it is
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+        if !active {
+            if let Some(drag) = self.drag.take() {
+                self.released = Some(drag.light);
+            }
+        }
+    }
+
+    pub fn handle_event<T: 'static>(
+        &mut self,
+        event: &Event<T>,
+        window: &Window,
+        camera: &Camera,
+        lights: &mut LightRig,
+    ) {
+        let Event::WindowEvent { event, .. } = event else {
+            return;
+        };
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((position.x as f32, position.y as f32));
+                if !self.active {
+                    return;
+                }
+                if let Some(ray) = self.cursor_ray(window, camera) {
+                    self.drag_to(&ray, lights);
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Right,
+                ..
+            } if self.active => match state {
+                ElementState::Pressed => {
+                    if let Some(ray) = self.cursor_ray(window, camera) {
+                        self.drag = self.start_drag(&ray, camera, lights);
+                    }
+                }
+                ElementState::Released => {
+                    if let Some(drag) = self.drag.take() {
+                        self.released = Some(drag.light);
+                    }
+                }
+            },
+            WindowEvent::MouseWheel { delta, .. } if self.active => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0,
+                };
+                if let Some(ray) = self.cursor_ray(window, camera) {
+                    self.push(&ray, camera, lights, steps);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cursor_ray(&self, window: &Window, camera: &Camera) -> Option<Ray> {
+        let cursor = self.cursor?;
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return None;
+        }
+        Ray::from_cursor(
+            &camera.get_mat(),
+            cursor,
+            (size.width as f32, size.height as f32),
+        )
+    }
+
+    /// the closest gizmo under the cursor
+    fn pick(ray: &Ray, lights: &LightRig) -> Option<(usize, f32)> {
+        lights
+            .lights()
+            .iter()
+            .enumerate()
+            .filter(|(_, light)| light.gizmo)
+            .filter_map(|(i, light)| {
+                Some((i, ray.hit_sphere(light.gizmo_position(), PICK_RADIUS)?))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    fn start_drag(&self, ray: &Ray, camera: &Camera, lights: &LightRig) -> Option<Drag> {
+        let (light, _) = Self::pick(ray, lights)?;
+        let start = lights.get(light)?.gizmo_position();
+        let constraint = if self.modifiers.shift() {
+            Constraint::Axis(Vec3::new(1.0, 0.0, 0.0))
+        } else if self.modifiers.ctrl() {
+            Constraint::Axis(Vec3::new(0.0, 1.0, 0.0))
+        } else if self.modifiers.alt() {
+            Constraint::Axis(Vec3::new(0.0, 0.0, 1.0))
+        } else {
+            Constraint::ScreenPlane {
+                normal: camera.get_front(),
+            }
+        };
+        let (offset, axis_start) = match constraint {
+            Constraint::ScreenPlane { normal } => {
+                let hit = ray.at(ray.hit_plane(start, normal)?);
+                (start - hit, 0.0)
+            }
+            Constraint::Axis(axis) => (Vec3::new(0.0, 0.0, 0.0), ray.closest_on_line(start, axis)?),
+        };
+        Some(Drag {
+            light,
+            start,
+            offset,
+            axis_start,
+            constraint,
+        })
+    }
+
+    fn drag_to(&self, ray: &Ray, lights: &mut LightRig) {
+        let Some(drag) = self.drag else {
+            return;
+        };
+        let position = match drag.constraint {
+            Constraint::ScreenPlane { normal } => ray
+                .hit_plane(drag.start, normal)
+                .map(|t| ray.at(t) + drag.offset),
+            Constraint::Axis(axis) => ray
+                .closest_on_line(drag.start, axis)
+                .map(|s| drag.start + axis * (s - drag.axis_start)),
+        };
+        if let (Some(position), Some(light)) = (position, lights.get_mut(drag.light)) {
+            light.set_position(position);
+        }
+    }
+
+    /// move the dragged or hovered light along the line from the camera, positive `steps`
+    /// push it away
+    fn push(&mut self, ray: &Ray, camera: &Camera, lights: &mut LightRig, steps: f32) {
+        let index = match self.drag {
+            Some(drag) => drag.light,
+            None => match Self::pick(ray, lights) {
+                Some((index, _)) => index,
+                None => return,
+            },
+        };
+        let Some(light) = lights.get_mut(index) else {
+            return;
+        };
+        let eye = camera.get_camera_pos();
+        let to_light = light.gizmo_position() - eye;
+        let distance = glm::length(to_light);
+        if distance < 1e-4 {
+            return;
+        }
+        let new_distance = (distance * (1.0 + WHEEL_STEP * steps)).max(MIN_DISTANCE);
+        let position = eye + to_light * (new_distance / distance);
+        light.set_position(position);
+        if self.drag.is_none() {
+            self.released = Some(index);
+        }
+        // the drag continues from the pushed position
+        if let Some(drag) = &mut self.drag {
+            drag.start = position;
+            match drag.constraint {
+                Constraint::ScreenPlane { normal } => {
+                    if let Some(t) = ray.hit_plane(position, normal) {
+                        drag.offset = position - ray.at(t);
+                    }
+                }
+                Constraint::Axis(axis) => {
+                    drag.axis_start = ray.closest_on_line(position, axis).unwrap_or(0.0);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::Mat4;
+    use num_traits::{One, Zero};
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray {
+            origin: Vec3::new(origin[0], origin[1], origin[2]),
+            direction: glm::normalize(Vec3::new(direction[0], direction[1], direction[2])),
+        }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn rays_hit_spheres_in_front() {
+        let ray = ray([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        assert_close(ray.hit_sphere(Vec3::new(0.0, 0.0, -5.0), 1.0).unwrap(), 4.0);
+        // grazing the side
+        assert_close(ray.hit_sphere(Vec3::new(1.0, 0.0, -5.0), 1.0).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn rays_miss_spheres_beside_or_behind() {
+        let ray = ray([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        assert_eq!(ray.hit_sphere(Vec3::new(2.0, 0.0, -5.0), 1.0), None);
+        assert_eq!(ray.hit_sphere(Vec3::new(0.0, 0.0, 5.0), 1.0), None);
+    }
+
+    #[test]
+    fn rays_inside_spheres_hit_at_once() {
+        let ray = ray([0.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        assert_eq!(ray.hit_sphere(Vec3::new(0.0, 0.5, 0.0), 1.0), Some(0.0));
+        assert_eq!(ray.hit_sphere(Vec3::new(0.0, -0.5, 0.0), 1.0), Some(0.0));
+    }
+
+    #[test]
+    fn rays_hit_planes_in_front() {
+        let ray = ray([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        assert_close(
+            ray.hit_plane(Vec3::new(3.0, 2.0, -4.0), normal).unwrap(),
+            4.0,
+        );
+        // the side of the plane does not matter
+        assert_close(
+            ray.hit_plane(Vec3::new(0.0, 0.0, -4.0), -normal).unwrap(),
+            4.0,
+        );
+        assert_eq!(ray.hit_plane(Vec3::new(0.0, 0.0, 4.0), normal), None);
+    }
+
+    #[test]
+    fn rays_parallel_to_planes_miss() {
+        let ray = ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        assert_eq!(ray.hit_plane(Vec3::new(0.0, 1.0, 0.0), up), None);
+        assert_eq!(ray.hit_plane(Vec3::new(0.0, 0.0, 0.0), up), None);
+    }
+
+    #[test]
+    fn closest_points_on_axis_parallel_lines() {
+        // looking down -z at the x axis through (0, 0, -5), the ray passes x = 2
+        let straight = ray([2.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        let point = Vec3::new(0.0, 0.0, -5.0);
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        assert_close(straight.closest_on_line(point, x).unwrap(), 2.0);
+        // the parameter is in units of the axis
+        assert_close(straight.closest_on_line(point, x * 2.0).unwrap(), 1.0);
+        // going down as it goes in, the ray crosses the y line 2 below the point
+        let tilted = ray([0.0, 3.0, 0.0], [0.0, -1.0, -1.0]);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        assert_close(tilted.closest_on_line(point, y).unwrap(), -2.0);
+    }
+
+    #[test]
+    fn lines_along_the_ray_have_no_closest_point() {
+        let ray = ray([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        let axis = Vec3::new(0.0, 0.0, 1.0);
+        assert_eq!(ray.closest_on_line(Vec3::new(1.0, 0.0, 0.0), axis), None);
+    }
+
+    #[test]
+    fn cursor_rays_unproject_through_the_camera() {
+        let ray = Ray::from_cursor(&Mat4::one(), (50.0, 50.0), (100.0, 100.0)).unwrap();
+        assert_eq!(ray.origin, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(ray.direction, Vec3::new(0.0, 0.0, 1.0));
+        let corner = Ray::from_cursor(&Mat4::one(), (0.0, 0.0), (100.0, 100.0)).unwrap();
+        assert_eq!(corner.origin, Vec3::new(-1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn singular_cameras_give_no_ray() {
+        assert_eq!(
+            Ray::from_cursor(&Mat4::zero(), (50.0, 50.0), (100.0, 100.0)),
+            None
+        );
+    }
+}
@@ -1,28 +1,35 @@
 use glium::{
-    Display, DrawParameters, Frame, implement_vertex, IndexBuffer, Program, Surface, uniform,
-    VertexBuffer,
+    Display, DrawParameters, implement_vertex, IndexBuffer, Program, Surface, uniform, VertexBuffer,
 };
+use glium::framebuffer::SimpleFrameBuffer;
 use glm::{Mat4, Vec3};
 
-use crate::Drawable;
+use crate::{Drawable, FrameState};
+use crate::shader::ProgramBuilder;
+use crate::shader_source;
+use crate::transform::{mat4_uniform, Transform};
 
+/// gizmo showing one light of the frame's [`LightRig`](crate::lights::LightRig)
 pub struct Light {
+    name: String,
+    /// index of the shown light in the rig
+    index: usize,
+    /// off when the rig has no such light or the light wants no gizmo
+    visible: bool,
     program: Program,
     vertex_buffer: VertexBuffer<Vertex>,
     indices: IndexBuffer<u8>,
     light_color: (f32, f32, f32),
-    light_pos: Vec3,
+    /// translation is the light position
+    transform: Transform,
 }
 
 impl Light {
-    fn new(display: &Display) -> Self {
-        let program = Program::from_source(
-            display,
-            include_str!("shader.vert"),
-            include_str!("shader.frag"),
-            None,
-        )
-        .unwrap();
+    pub fn new(display: &Display) -> Self {
+        let program =
+            ProgramBuilder::new(shader_source!("shader.vert"), shader_source!("shader.frag"))
+                .build(display)
+                .unwrap_or_else(|err| panic!("light: {}", err));
 
         let shape = vec![
             Vertex::new(0.5, 0.5, 0.5),
@@ -48,29 +55,74 @@ impl Light {
         .unwrap();
 
         Self {
+            name: String::from("light"),
+            index: 0,
+            visible: true,
             program,
             vertex_buffer,
             indices,
             light_color: (1.0, 1.0, 1.0),
-            light_pos: Vec3::new(2.0, 2.0, 2.0),
+            transform: Transform::from_translation(Vec3::new(2.0, 2.0, 2.0)),
         }
     }
 
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// show the light at `index` of the rig instead of the first one
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = index;
+        self
+    }
+
     pub fn set_light_color(&mut self, color: (f32, f32, f32)) {
         self.light_color = color;
     }
 
     pub fn set_light_pos(&mut self, pos: Vec3) {
-        self.light_pos = pos;
+        self.transform.translation = pos;
+    }
+
+    pub fn light_pos(&self) -> Vec3 {
+        self.transform.translation
     }
 }
 
 impl Drawable for Light {
-    fn init(display: &Display) -> Self {
-        Self::new(display)
+    fn name(&self) -> &str {
+        &self.name
     }
 
-    fn draw_with_frame(&self, frame: &mut Frame, camera_mat: Mat4, dramparams: &DrawParameters) {
+    fn update(&mut self, state: &FrameState) {
+        self.visible = false;
+        if let Some(light) = state.lights.get(self.index) {
+            self.visible = light.gizmo;
+            let [r, g, b] = light.display_color();
+            self.set_light_color((r, g, b));
+            self.set_light_pos(light.gizmo_position());
+        }
+    }
+
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    fn draw_with_model(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera_mat: Mat4,
+        model_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        if !self.visible {
+            return;
+        }
         let uniforms = uniform! {
             camera: [
                 *camera_mat.as_array()[0].as_array(),
@@ -78,11 +130,11 @@ impl Drawable for Light {
                 *camera_mat.as_array()[2].as_array(),
                 *camera_mat.as_array()[3].as_array(),
             ],
-            shift: [self.light_pos.x, self.light_pos.y, self.light_pos.z],
+            model: mat4_uniform(&model_mat),
             light_color: [self.light_color.0, self.light_color.1, self.light_color.2],
         };
 
-        frame
+        target
             .draw(
                 &self.vertex_buffer,
                 &self.indices,
@@ -2,11 +2,10 @@
 
 layout (location = 0) in vec3 position;
 
-uniform vec3 shift;
+uniform mat4 model;
 uniform mat4 camera;
 
 void main()
 {
-    vec3 final_pos = position + shift;
-    gl_Position = camera * vec4(final_pos, 1.0);
+    gl_Position = camera * model * vec4(position, 1.0);
 }
\ No newline at end of file
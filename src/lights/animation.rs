@@ -0,0 +1,334 @@
+//! animated lights. a [`RigAnimation`] moves and recolours the lights of a [`LightRig`] before
+//! the frame's update, every drawable then sees the same lights through
+//! [`FrameState`](crate::FrameState)
+
+use std::time::Duration;
+
+use glm::Vec3;
+
+use crate::lights::{LightRig, SceneLight};
+use crate::lights::color::LightColor;
+
+/// values keyframes can blend between
+pub trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vec3 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for LightColor {
+    /// temperatures blend in Kelvin, anything else in linear RGB
+    fn lerp(self, other: Self, t: f32) -> Self {
+        match (self, other) {
+            (LightColor::Temperature(a), LightColor::Temperature(b)) => {
+                LightColor::Temperature(a.lerp(b, t))
+            }
+            (a, b) => {
+                let (a, b) = (a.rgb(), b.rgb());
+                LightColor::Rgb([0, 1, 2].map(|i| a[i].lerp(b[i], t)))
+            }
+        }
+    }
+}
+
+/// values at points in time, linearly interpolated in between
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframes<T> {
+    /// (seconds, value), sorted by time
+    keys: Vec<(f32, T)>,
+    /// start over after the last key instead of holding it
+    pub looping: bool,
+}
+
+impl<T: Lerp> Keyframes<T> {
+    /// `keys` are sorted by time, so they may be given in any order
+    pub fn new(mut keys: Vec<(f32, T)>) -> Self {
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            keys,
+            looping: true,
+        }
+    }
+
+    pub fn once(mut self) -> Self {
+        self.looping = false;
+        self
+    }
+
+    /// time of the last key
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |&(time, _)| time)
+    }
+
+    /// `None` without keys
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let (first, last) = (self.keys.first()?, self.keys.last()?);
+        let time = if self.looping && last.0 > first.0 {
+            first.0 + (time - first.0).rem_euclid(last.0 - first.0)
+        } else {
+            time
+        };
+        let next = self.keys.iter().position(|&(t, _)| t > time);
+        Some(match next {
+            Some(0) => first.1,
+            None => last.1,
+            Some(i) => {
+                let (t0, v0) = self.keys[i - 1];
+                let (t1, v1) = self.keys[i];
+                v0.lerp(v1, (time - t0) / (t1 - t0))
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LightAnimation {
+    /// circle around `center` in the horizontal plane
+    Orbit {
+        center: Vec3,
+        radius: f32,
+        /// above the centre
+        height: f32,
+        /// degrees per second, positive turns from +x towards +z
+        speed: f32,
+        /// angle in degrees at time zero, zero is on the +x side
+        phase: f32,
+    },
+    /// positions in world space
+    Path(Keyframes<Vec3>),
+    Color(Keyframes<LightColor>),
+    Intensity(Keyframes<f32>),
+}
+
+impl LightAnimation {
+    /// orbit passing through `start`, so a light begins where the rig put it
+    pub fn orbit_through(center: Vec3, start: Vec3, speed: f32) -> Self {
+        let offset = start - center;
+        LightAnimation::Orbit {
+            center,
+            radius: (offset.x * offset.x + offset.z * offset.z).sqrt(),
+            height: offset.y,
+            speed,
+            phase: offset.z.atan2(offset.x).to_degrees(),
+        }
+    }
+
+    /// where an orbit or a path puts its light at `time`, `None` for the other animations
+    pub fn position(&self, time: f32) -> Option<Vec3> {
+        match self {
+            LightAnimation::Orbit {
+                center,
+                radius,
+                height,
+                speed,
+                phase,
+            } => {
+                let angle = (phase + speed * time).to_radians();
+                let offset = Vec3::new(radius * angle.cos(), *height, radius * angle.sin());
+                Some(*center + offset)
+            }
+            LightAnimation::Path(keys) => keys.sample(time),
+            LightAnimation::Color(_) | LightAnimation::Intensity(_) => None,
+        }
+    }
+
+    /// pose `light` at `time`, positions moved by `offset`
+    fn apply(&self, light: &mut SceneLight, time: f32, offset: Vec3) {
+        match self {
+            LightAnimation::Orbit { .. } | LightAnimation::Path(_) => {
+                if let Some(position) = self.position(time) {
+                    light.set_position(position + offset);
+                }
+            }
+            LightAnimation::Color(keys) => {
+                if let Some(color) = keys.sample(time) {
+                    light.color = color;
+                }
+            }
+            LightAnimation::Intensity(keys) => {
+                if let Some(intensity) = keys.sample(time) {
+                    light.intensity = intensity;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Track {
+    light: usize,
+    animation: LightAnimation,
+    /// added to the animated position, moves the orbit or path to where a light was dropped
+    offset: Vec3,
+}
+
+/// animations of the lights of one rig, several may drive the same light
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RigAnimation {
+    tracks: Vec<Track>,
+    /// seconds since the start
+    time: f32,
+    pub paused: bool,
+}
+
+impl RigAnimation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// animate the light at `index` of the rig
+    pub fn with(mut self, index: usize, animation: LightAnimation) -> Self {
+        self.add(index, animation);
+        self
+    }
+
+    pub fn add(&mut self, index: usize, animation: LightAnimation) {
+        self.tracks.push(Track {
+            light: index,
+            animation,
+            offset: Vec3::new(0.0, 0.0, 0.0),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// move the clock on and pose the rig's lights for the new time, tracks of missing lights
+    /// are skipped
+    pub fn advance(&mut self, rig: &mut LightRig, delta_time: Duration) {
+        if !self.paused {
+            self.time += delta_time.as_secs_f32();
+        }
+        self.apply(rig);
+    }
+
+    /// pose the rig's lights for the current time
+    pub fn apply(&self, rig: &mut LightRig) {
+        for track in &self.tracks {
+            if let Some(light) = rig.get_mut(track.light) {
+                track.animation.apply(light, self.time, track.offset);
+            }
+        }
+    }
+
+    /// carry on moving the light at `index` from `position` instead of snapping it back, e.g.
+    /// after it was dragged. its orbits and paths are shifted to pass through `position` now
+    pub fn rebase(&mut self, index: usize, position: Vec3) {
+        for track in self.tracks.iter_mut().filter(|track| track.light == index) {
+            if let Some(animated) = track.animation.position(self.time) {
+                track.offset = position - animated;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(glm::length(a - b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn keyframes_loop() {
+        let keys = Keyframes::new(vec![(1.0, 0.0), (2.0, 10.0), (3.0, 0.0)]);
+        assert_eq!(keys.sample(1.5), Some(5.0));
+        assert_eq!(keys.sample(3.5), Some(5.0));
+        assert_eq!(keys.sample(4.0), Some(10.0));
+    }
+
+    #[test]
+    fn keyframes_once_hold_the_last_key() {
+        let keys = Keyframes::new(vec![(0.0, 0.0), (2.0, 10.0)]).once();
+        assert_eq!(keys.sample(1.0), Some(5.0));
+        assert_eq!(keys.sample(5.0), Some(10.0));
+    }
+
+    #[test]
+    fn keyframes_hold_the_first_key_before_it() {
+        let keys = Keyframes::new(vec![(1.0, 3.0), (2.0, 10.0)]).once();
+        assert_eq!(keys.sample(0.0), Some(3.0));
+        assert_eq!(keys.sample(-5.0), Some(3.0));
+        assert_eq!(Keyframes::<f32>::new(Vec::new()).sample(0.0), None);
+    }
+
+    #[test]
+    fn keyframes_are_sorted() {
+        let keys = Keyframes::new(vec![(2.0, 10.0), (0.0, 0.0), (1.0, 4.0)]).once();
+        assert_eq!(keys.duration(), 2.0);
+        assert_eq!(keys.sample(0.5), Some(2.0));
+        assert_eq!(keys.sample(1.5), Some(7.0));
+    }
+
+    #[test]
+    fn orbit_starts_at_its_start() {
+        let center = Vec3::new(1.0, 0.5, -2.0);
+        for start in [
+            Vec3::new(3.0, 2.0, 1.0),
+            Vec3::new(-2.0, 0.0, -4.0),
+            Vec3::new(1.0, 4.0, -5.0),
+        ] {
+            let orbit = LightAnimation::orbit_through(center, start, 45.0);
+            assert_close(orbit.position(0.0).unwrap(), start);
+            let quarter = orbit.position(2.0).unwrap();
+            assert!(glm::length(quarter - start) > 0.1);
+            assert!((quarter.y - start.y).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn missing_lights_are_skipped() {
+        let mut rig = LightRig::new(vec![SceneLight::point(
+            Vec3::new(0.0, 1.0, 0.0),
+            [1.0, 1.0, 1.0],
+        )]);
+        let path = Keyframes::new(vec![
+            (0.0, Vec3::new(0.0, 0.0, 0.0)),
+            (1.0, Vec3::new(1.0, 0.0, 0.0)),
+        ]);
+        let mut animation = RigAnimation::new()
+            .with(0, LightAnimation::Path(path.clone()))
+            .with(3, LightAnimation::Path(path));
+        animation.advance(&mut rig, Duration::from_millis(500));
+        assert_eq!(rig.len(), 1);
+        assert_close(
+            rig.lights()[0].position().unwrap(),
+            Vec3::new(0.5, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn rebased_lights_carry_on_from_where_they_were_dropped() {
+        let start = Vec3::new(2.0, 1.0, 0.0);
+        let mut rig = LightRig::new(vec![SceneLight::point(start, [1.0, 1.0, 1.0])]);
+        let orbit = LightAnimation::orbit_through(Vec3::new(0.0, 0.0, 0.0), start, 90.0);
+        let mut animation = RigAnimation::new().with(0, orbit.clone());
+        animation.advance(&mut rig, Duration::from_secs(1));
+
+        let dropped = Vec3::new(0.0, 3.0, 5.0);
+        rig.lights_mut()[0].set_position(dropped);
+        animation.rebase(0, dropped);
+        animation.apply(&mut rig);
+        assert_close(rig.lights()[0].position().unwrap(), dropped);
+
+        animation.advance(&mut rig, Duration::from_secs(1));
+        let moved = orbit.position(2.0).unwrap() - orbit.position(1.0).unwrap();
+        assert_close(rig.lights()[0].position().unwrap(), dropped + moved);
+    }
+}
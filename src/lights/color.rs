@@ -0,0 +1,114 @@
+//! light colours given as RGB or as a blackbody temperature
+
+/// coolest and hottest temperature the blackbody fit covers
+const KELVIN_RANGE: (f32, f32) = (1000.0, 40000.0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightColor {
+    /// linear RGB
+    Rgb([f32; 3]),
+    /// blackbody temperature in Kelvin, e.g. 2700 for a light bulb or 6500 for daylight
+    Temperature(f32),
+}
+
+impl LightColor {
+    /// linear RGB, temperatures are converted so the brightest channel is 1
+    pub fn rgb(&self) -> [f32; 3] {
+        match *self {
+            LightColor::Rgb(rgb) => rgb,
+            LightColor::Temperature(kelvin) => kelvin_to_rgb(kelvin),
+        }
+    }
+}
+
+impl From<[f32; 3]> for LightColor {
+    fn from(rgb: [f32; 3]) -> Self {
+        LightColor::Rgb(rgb)
+    }
+}
+
+impl Default for LightColor {
+    fn default() -> Self {
+        LightColor::Rgb([1.0, 1.0, 1.0])
+    }
+}
+
+/// linear RGB of a blackbody, after Tanner Helland's fit of the CIE 1964 colour matching
+/// functions. the fit gives sRGB in 0..=255, which is decoded and normalized here
+pub fn kelvin_to_rgb(kelvin: f32) -> [f32; 3] {
+    let t = kelvin.clamp(KELVIN_RANGE.0, KELVIN_RANGE.1) / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let green = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_17 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+    let rgb = [red, green, blue].map(|c| srgb_to_linear((c / 255.0).clamp(0.0, 1.0)));
+    let max = rgb.iter().cloned().fold(0.0, f32::max);
+    rgb.map(|c| c / max)
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daylight_is_about_white() {
+        for c in kelvin_to_rgb(6500.0) {
+            assert!(c > 0.95, "{:?}", kelvin_to_rgb(6500.0));
+        }
+    }
+
+    #[test]
+    fn hotter_is_bluer() {
+        let temperatures = [1500.0, 2700.0, 4000.0, 6500.0, 9000.0, 20000.0];
+        let ratios: Vec<f32> = temperatures
+            .iter()
+            .map(|&kelvin| {
+                let [red, _, blue] = kelvin_to_rgb(kelvin);
+                blue / red
+            })
+            .collect();
+        for pair in ratios.windows(2) {
+            assert!(pair[0] < pair[1], "{:?}", ratios);
+        }
+        let [red, green, blue] = kelvin_to_rgb(2700.0);
+        assert!(red > green && green > blue);
+        let [red, green, blue] = kelvin_to_rgb(20000.0);
+        assert!(blue > green && green > red);
+    }
+
+    #[test]
+    fn brightest_channel_is_one() {
+        for kelvin in [0.0, 1000.0, 3000.0, 6600.0, 12000.0, 1e6] {
+            let rgb = kelvin_to_rgb(kelvin);
+            assert_eq!(rgb.iter().cloned().fold(0.0, f32::max), 1.0, "{}", kelvin);
+            assert!(rgb.iter().all(|c| (0.0..=1.0).contains(c)), "{}", kelvin);
+        }
+    }
+
+    #[test]
+    fn rgb_colors_pass_through() {
+        assert_eq!(LightColor::Rgb([0.2, 0.4, 0.6]).rgb(), [0.2, 0.4, 0.6]);
+        assert_eq!(LightColor::Temperature(6500.0).rgb(), kelvin_to_rgb(6500.0));
+    }
+}
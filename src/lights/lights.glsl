@@ -0,0 +1,66 @@
+// the scene's lights, filled in by LightRig in src/lights/mod.rs
+#ifndef MAX_LIGHTS
+#define MAX_LIGHTS 8
+#endif
+
+#define POINT_LIGHT 0
+#define DIRECTIONAL_LIGHT 1
+#define SPOT_LIGHT 2
+
+struct Light {
+    int kind;
+    vec3 position;
+    // the direction the light travels in, unused by point lights
+    vec3 direction;
+    // colour times intensity
+    vec3 color;
+    // distance where point and spot lights fade out, 0 for no cutoff
+    float range;
+    // cosines of the inner and outer half angle of a spot light
+    vec2 cone;
+    // shadow map slot, -1 for none, filled in by ShadowMaps in src/shadows/mod.rs
+    int shadow;
+};
+
+uniform Light lights[MAX_LIGHTS];
+uniform int light_count;
+// mean light colour regardless of intensity
+uniform vec3 ambient_light;
+
+#include "shadows.glsl"
+
+int active_lights() {
+    return min(light_count, MAX_LIGHTS);
+}
+
+// unit vector from pos towards the light
+vec3 light_direction(Light light, vec3 pos) {
+    if (light.kind == DIRECTIONAL_LIGHT) {
+        return normalize(-light.direction);
+    }
+    return normalize(light.position - pos);
+}
+
+// colour arriving at pos after distance attenuation and the spot cone
+vec3 light_radiance(Light light, vec3 pos) {
+    if (light.kind == DIRECTIONAL_LIGHT) {
+        return light.color;
+    }
+    // inverse square falloff, windowed to reach zero at the range, kept in sync with
+    // SceneLight::falloff
+    vec3 to_light = light.position - pos;
+    float d2 = dot(to_light, to_light);
+    float atten = 1.0 / max(d2, 1e-4);
+    if (light.range > 0.0) {
+        float ratio = d2 / (light.range * light.range);
+        float window = clamp(1.0 - ratio * ratio, 0.0, 1.0);
+        atten *= window * window;
+    }
+    if (light.kind == SPOT_LIGHT) {
+        float cos_angle = dot(normalize(pos - light.position), light.direction);
+        // smoothstep is undefined for equal edges, a hard-edged spot fades over a sliver
+        float cos_inner = max(light.cone.x, light.cone.y + 1e-4);
+        atten *= smoothstep(light.cone.y, cos_inner, cos_angle);
+    }
+    return atten * light.color;
+}
\ No newline at end of file
@@ -0,0 +1,474 @@
+//! the lights of a scene, shared by every lit shader through `lights.glsl`.
+//!
+//! lights are passed as a uniform array of structs. its size is fixed when a program is built,
+//! [`include_lights`] sets it, lights past that size are ignored by the shader.
+//!
+//! intensities are physical-ish: point and spot lights give an irradiance of
+//! `intensity / distance²`, like candela give lux, and directional lights give `intensity`
+//! everywhere. one unit is the full-strength white light the shaders were tuned for.
+
+use glium::uniforms::{Uniforms, UniformValue};
+use glm::Vec3;
+
+use crate::lights::color::LightColor;
+use crate::shader::ProgramBuilder;
+use crate::shader_source;
+use crate::shadows::include_shadows;
+
+pub mod animation;
+pub mod color;
+
+/// array size used by programs that do not ask for another one
+pub const DEFAULT_MAX_LIGHTS: usize = 8;
+
+/// largest light array the lit programs are built with. GL 3.3 only promises 1024 uniform
+/// components to a fragment shader, a light takes up to 28 of them and the material, shadow and
+/// deep opacity uniforms about 250 more
+pub const LIGHT_ARRAY_LIMIT: usize = 16;
+
+/// least gap between the cosines of a spot's inner and outer cone, so that the fade between
+/// them never divides by zero
+const MIN_CONE_GAP: f32 = 1e-4;
+
+/// how far from the origin the gizmo of a directional light is drawn
+const DIRECTIONAL_GIZMO_DISTANCE: f32 = 5.0;
+
+/// make `#include "lights.glsl"` available and size its light array, up to
+/// [`LIGHT_ARRAY_LIMIT`]
+pub fn include_lights(builder: ProgramBuilder, max_lights: usize) -> ProgramBuilder {
+    include_shadows(builder)
+        .include("lights.glsl", shader_source!("lights.glsl"))
+        .define(
+            "MAX_LIGHTS",
+            max_lights.clamp(1, LIGHT_ARRAY_LIMIT).to_string(),
+        )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    Point {
+        position: Vec3,
+    },
+    /// infinitely far away, e.g. the sun
+    Directional {
+        /// the direction the light travels in
+        direction: Vec3,
+    },
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        /// half angle in degrees inside which the spot is at full strength
+        inner_angle: f32,
+        /// half angle in degrees where the spot has faded out
+        outer_angle: f32,
+    },
+}
+
+impl LightKind {
+    /// value of the `kind` member in `lights.glsl`
+    pub fn uniform(&self) -> i32 {
+        match self {
+            LightKind::Point { .. } => 0,
+            LightKind::Directional { .. } => 1,
+            LightKind::Spot { .. } => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneLight {
+    pub name: String,
+    pub kind: LightKind,
+    pub color: LightColor,
+    /// candela-like for point and spot lights, lux-like for directional ones
+    pub intensity: f32,
+    /// distance at which point and spot lights have faded out completely, `None` lets the
+    /// inverse-square falloff run on forever
+    pub range: Option<f32>,
+    /// whether a [`Light`](crate::light_source::Light) draws a gizmo for this light
+    pub gizmo: bool,
+    /// whether the light gets a shadow map while one is free, see [`crate::shadows`]
+    pub cast_shadows: bool,
+}
+
+impl SceneLight {
+    fn new(name: &str, kind: LightKind, color: impl Into<LightColor>) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            color: color.into(),
+            intensity: 1.0,
+            range: None,
+            gizmo: true,
+            cast_shadows: true,
+        }
+    }
+
+    pub fn point(position: Vec3, color: impl Into<LightColor>) -> Self {
+        Self::new("point", LightKind::Point { position }, color)
+    }
+
+    pub fn directional(direction: Vec3, color: impl Into<LightColor>) -> Self {
+        Self::new("directional", LightKind::Directional { direction }, color)
+    }
+
+    /// cone half angles in degrees
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        inner_angle: f32,
+        outer_angle: f32,
+        color: impl Into<LightColor>,
+    ) -> Self {
+        let kind = LightKind::Spot {
+            position,
+            direction,
+            inner_angle,
+            outer_angle,
+        };
+        Self::new("spot", kind, color)
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn without_gizmo(mut self) -> Self {
+        self.gizmo = false;
+        self
+    }
+
+    pub fn without_shadows(mut self) -> Self {
+        self.cast_shadows = false;
+        self
+    }
+
+    /// `None` for directional lights
+    pub fn position(&self) -> Option<Vec3> {
+        match self.kind {
+            LightKind::Point { position } | LightKind::Spot { position, .. } => Some(position),
+            LightKind::Directional { .. } => None,
+        }
+    }
+
+    /// move a point or spot light. a directional light is turned to shine from `position`
+    /// towards the origin
+    pub fn set_position(&mut self, new_position: Vec3) {
+        match &mut self.kind {
+            LightKind::Point { position } | LightKind::Spot { position, .. } => {
+                *position = new_position
+            }
+            LightKind::Directional { direction } => *direction = -new_position,
+        }
+    }
+
+    /// where the gizmo goes, directional lights are shown up their direction from the origin
+    pub fn gizmo_position(&self) -> Vec3 {
+        match self.kind {
+            LightKind::Point { position } | LightKind::Spot { position, .. } => position,
+            LightKind::Directional { direction } => {
+                glm::normalize(-direction) * DIRECTIONAL_GIZMO_DISTANCE
+            }
+        }
+    }
+
+    /// direction the light travels in, `None` for point lights
+    pub fn direction(&self) -> Option<Vec3> {
+        match self.kind {
+            LightKind::Directional { direction } | LightKind::Spot { direction, .. } => {
+                Some(glm::normalize(direction))
+            }
+            LightKind::Point { .. } => None,
+        }
+    }
+
+    /// colour times intensity, what the shaders receive
+    pub fn emitted(&self) -> [f32; 3] {
+        self.color.rgb().map(|c| c * self.intensity)
+    }
+
+    /// falloff at `distance` from a point or spot light: inverse square, windowed to reach
+    /// zero at the range like in Unreal's lights. directional lights do not fall off
+    pub fn falloff(&self, distance: f32) -> f32 {
+        if let LightKind::Directional { .. } = self.kind {
+            return 1.0;
+        }
+        let inverse_square = 1.0 / (distance * distance).max(1e-4);
+        match self.range {
+            Some(range) => {
+                let window = (1.0 - (distance / range).powi(4)).clamp(0.0, 1.0);
+                inverse_square * window * window
+            }
+            None => inverse_square,
+        }
+    }
+
+    /// how much of a spot light reaches `point`, fading from 1 inside the inner cone to 0
+    /// outside the outer one like `lights.glsl`. other lights are 1 everywhere
+    pub fn cone_falloff(&self, point: Vec3) -> f32 {
+        let (LightKind::Spot { position, .. }, Some(direction)) = (self.kind, self.direction())
+        else {
+            return 1.0;
+        };
+        let [cos_inner, cos_outer] = self.cone();
+        let cos_angle = glm::dot(glm::normalize(point - position), direction);
+        let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    /// the emitted colour scaled down until no channel exceeds 1, keeping its hue for the gizmo
+    pub fn display_color(&self) -> [f32; 3] {
+        let emitted = self.emitted();
+        let max = emitted.iter().cloned().fold(1.0, f32::max);
+        emitted.map(|c| c.max(0.0) / max)
+    }
+
+    /// cosines of the inner and outer cone half angles, what the shader compares against
+    fn cone(&self) -> [f32; 2] {
+        match self.kind {
+            LightKind::Spot {
+                inner_angle,
+                outer_angle,
+                ..
+            } => {
+                let outer = outer_angle.max(inner_angle).to_radians().cos();
+                let inner = inner_angle.to_radians().cos().max(outer + MIN_CONE_GAP);
+                [inner, outer]
+            }
+            _ => [-1.0, -1.0],
+        }
+    }
+}
+
+/// every light of a scene, handed to the drawables each frame through
+/// [`FrameState`](crate::FrameState)
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightRig {
+    lights: Vec<SceneLight>,
+}
+
+impl Default for LightRig {
+    /// the single white light the demo always had
+    fn default() -> Self {
+        Self::new(vec![
+            // about 4.5 units from the cube, where 20 gives the light's old full strength
+            SceneLight::point(Vec3::new(2.0, 0.9, -4.0), [1.0, 1.0, 1.0])
+                .with_name("light")
+                .with_intensity(20.0),
+        ])
+    }
+}
+
+impl LightRig {
+    pub fn new(lights: Vec<SceneLight>) -> Self {
+        Self { lights }
+    }
+
+    /// classic portrait lighting: a warm key from the front side, a dim cool fill from the
+    /// other side and a rim light behind the subject to separate the hair from the background
+    pub fn three_point() -> Self {
+        Self::new(vec![
+            SceneLight::spot(
+                Vec3::new(2.5, 2.0, 3.0),
+                Vec3::new(-2.5, -2.0, -3.0),
+                20.0,
+                30.0,
+                LightColor::Temperature(4000.0),
+            )
+            .with_name("key")
+            .with_intensity(20.0),
+            SceneLight::point(Vec3::new(-3.0, 0.5, 2.0), LightColor::Temperature(9000.0))
+                .with_name("fill")
+                .with_intensity(3.0)
+                .with_range(10.0)
+                // a fill light is there to lift the shadows of the key
+                .without_shadows(),
+            SceneLight::directional(Vec3::new(0.3, -0.5, 1.0), LightColor::Temperature(6500.0))
+                .with_name("rim")
+                .with_intensity(0.8),
+        ])
+    }
+
+    pub fn push(&mut self, light: SceneLight) {
+        self.lights.push(light);
+    }
+
+    pub fn lights(&self) -> &[SceneLight] {
+        &self.lights
+    }
+
+    pub fn lights_mut(&mut self) -> &mut [SceneLight] {
+        &mut self.lights
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SceneLight> {
+        self.lights.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut SceneLight> {
+        self.lights.get_mut(index)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&SceneLight> {
+        self.lights.iter().find(|light| light.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// mean light colour regardless of intensity, a stand-in for light bounced around the scene
+    pub fn ambient_color(&self) -> [f32; 3] {
+        if self.lights.is_empty() {
+            return [0.0; 3];
+        }
+        let mut sum = [0.0; 3];
+        for light in &self.lights {
+            for (s, c) in sum.iter_mut().zip(light.color.rgb()) {
+                *s += c;
+            }
+        }
+        sum.map(|s| s / self.lights.len() as f32)
+    }
+
+    /// the rig's uniforms followed by `others`
+    pub fn with_uniforms<U: Uniforms>(&self, others: U) -> LightUniforms<'_, U> {
+        LightUniforms { rig: self, others }
+    }
+}
+
+impl Uniforms for LightRig {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+        f(
+            "light_count",
+            UniformValue::SignedInt(self.lights.len() as i32),
+        );
+        f("ambient_light", UniformValue::Vec3(self.ambient_color()));
+        for (i, light) in self.lights.iter().enumerate() {
+            let position = light.position().unwrap_or_else(|| Vec3::new(0.0, 0.0, 0.0));
+            let direction = light
+                .direction()
+                .unwrap_or_else(|| Vec3::new(0.0, 0.0, 0.0));
+            let member = |name: &str| format!("lights[{}].{}", i, name);
+            f(
+                &member("kind"),
+                UniformValue::SignedInt(light.kind.uniform()),
+            );
+            f(
+                &member("position"),
+                UniformValue::Vec3(*position.as_array()),
+            );
+            f(
+                &member("direction"),
+                UniformValue::Vec3(*direction.as_array()),
+            );
+            f(&member("color"), UniformValue::Vec3(light.emitted()));
+            // zero for no cutoff
+            f(
+                &member("range"),
+                UniformValue::Float(light.range.unwrap_or(0.0)),
+            );
+            f(&member("cone"), UniformValue::Vec2(light.cone()));
+        }
+    }
+}
+
+/// uniforms of a [`LightRig`] chained with the per-draw ones
+pub struct LightUniforms<'l, U> {
+    rig: &'l LightRig,
+    others: U,
+}
+
+impl<U: Uniforms> Uniforms for LightUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+        self.rig.visit_values(&mut f);
+        self.others.visit_values(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spot(inner_angle: f32, outer_angle: f32) -> SceneLight {
+        SceneLight::spot(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -2.0),
+            inner_angle,
+            outer_angle,
+            [1.0, 1.0, 1.0],
+        )
+    }
+
+    /// a point 1 unit down the spot's axis and `degrees` off it
+    fn off_axis(degrees: f32) -> Vec3 {
+        let angle = degrees.to_radians();
+        Vec3::new(angle.sin(), 0.0, -angle.cos())
+    }
+
+    #[test]
+    fn falloff_is_inverse_square_without_a_range() {
+        let light = SceneLight::point(Vec3::new(0.0, 0.0, 0.0), [1.0, 1.0, 1.0]);
+        assert_eq!(light.falloff(1.0), 1.0);
+        assert_eq!(light.falloff(2.0), 0.25);
+        assert!(light.falloff(0.0).is_finite());
+    }
+
+    #[test]
+    fn falloff_reaches_zero_at_the_range() {
+        let light = SceneLight::point(Vec3::new(0.0, 0.0, 0.0), [1.0, 1.0, 1.0]).with_range(4.0);
+        assert!((light.falloff(1.0) - (1.0 - 1.0 / 256.0f32).powi(2)).abs() < 1e-6);
+        assert!(light.falloff(3.9) > 0.0);
+        assert_eq!(light.falloff(4.0), 0.0);
+        assert_eq!(light.falloff(10.0), 0.0);
+    }
+
+    #[test]
+    fn directional_lights_do_not_fall_off() {
+        let light = SceneLight::directional(Vec3::new(0.0, -1.0, 0.0), [1.0, 1.0, 1.0]);
+        assert_eq!(light.falloff(100.0), 1.0);
+        assert_eq!(light.cone_falloff(Vec3::new(5.0, 5.0, 5.0)), 1.0);
+    }
+
+    #[test]
+    fn spots_fade_between_their_cones() {
+        let light = spot(20.0, 30.0);
+        assert_eq!(light.cone_falloff(off_axis(0.0)), 1.0);
+        assert_eq!(light.cone_falloff(off_axis(19.0)), 1.0);
+        let halfway = light.cone_falloff(off_axis(25.0));
+        assert!(halfway > 0.3 && halfway < 0.7, "{}", halfway);
+        assert!(light.cone_falloff(off_axis(22.0)) > light.cone_falloff(off_axis(28.0)));
+        assert_eq!(light.cone_falloff(off_axis(31.0)), 0.0);
+        assert_eq!(light.cone_falloff(Vec3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn hard_edged_spots_stay_finite() {
+        for (inner, outer) in [(25.0, 25.0), (30.0, 20.0), (0.0, 0.0)] {
+            let light = spot(inner, outer);
+            let [cos_inner, cos_outer] = light.cone();
+            assert!(cos_inner > cos_outer, "{} {}", inner, outer);
+            for degrees in [0.0, 10.0, 24.0, 26.0, 40.0] {
+                let falloff = light.cone_falloff(off_axis(degrees));
+                assert!((0.0..=1.0).contains(&falloff), "{} at {}", falloff, degrees);
+            }
+        }
+        assert_eq!(spot(25.0, 25.0).cone_falloff(off_axis(24.0)), 1.0);
+        assert_eq!(spot(25.0, 25.0).cone_falloff(off_axis(26.0)), 0.0);
+    }
+}
@@ -1,4 +1,5 @@
 use std::error::Error;
+use std::rc::Rc;
 
 use glium::{Display, Surface};
 use glium::glutin::ContextBuilder;
@@ -10,19 +11,45 @@ use glm::Vec3;
 
 use kajiya_kay_demo::camera::Camera;
 use kajiya_kay_demo::camera_events::CameraHandler;
-use kajiya_kay_demo::Drawable;
-use kajiya_kay_demo::hair_cube::HairCube;
+use kajiya_kay_demo::diffuse_mesh::DiffuseMesh;
+use kajiya_kay_demo::FrameState;
+use kajiya_kay_demo::gltf_import::{load_gltf, ImportOptions};
+use kajiya_kay_demo::hair_cube::{cube_mesh, HairCube};
+use kajiya_kay_demo::hair_cube::material::HairMaterial;
+use kajiya_kay_demo::hair_strands::HairStrands;
+use kajiya_kay_demo::light_events::LightDragHandler;
 use kajiya_kay_demo::light_source::Light;
+use kajiya_kay_demo::mesh;
+use kajiya_kay_demo::mesh::obj::load_obj;
+use kajiya_kay_demo::options::{GroomBase, Options, USAGE};
+use kajiya_kay_demo::post::{PostSettings, PostStack};
 use kajiya_kay_demo::refresh_rate::RefreshRate;
+use kajiya_kay_demo::render_target::SceneTarget;
+use kajiya_kay_demo::scene_graph::SceneGraph;
+use kajiya_kay_demo::shadows::debug::ShadowDebugView;
+use kajiya_kay_demo::shadows::ShadowMaps;
+use kajiya_kay_demo::strands::fit_strands;
+use kajiya_kay_demo::strands::groom::{groom, GroomParams};
+use kajiya_kay_demo::strands::hair_file::load_hair;
+use kajiya_kay_demo::tone_mapping::ToneMapper;
+use kajiya_kay_demo::transform::Transform;
+use kajiya_kay_demo::transparency::{BlendMode, OitBuffers};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let options = Options::from_env()?;
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
     let event_loop = EventLoop::new();
 
     let wb = WindowBuilder::new()
         .with_title("kajiya-kay demo")
         .with_inner_size(PhysicalSize::new(800, 600));
 
+    // only the scene target is multisampled, the window just receives the tone mapped frame
     let display = Display::new(wb, ContextBuilder::new().with_depth_buffer(24), &event_loop)?;
+    let mut scene_target = SceneTarget::new(&display, u32::from(options.msaa));
 
     let drawparams = glium::DrawParameters {
         depth: glium::Depth {
@@ -37,19 +64,145 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut camera = Camera::new();
     let mut rate = RefreshRate::new(61.0);
     let mut camera_handler = CameraHandler::new();
+    let mut light_handler = LightDragHandler::new();
 
-    let light_color = (1.0, 1.0, 1.0);
-    let light_pos = Vec3::new(2.0, 0.9, -4.0);
+    let mut lights = options.lights.rig();
+    let mut light_animation = options.light_animation.animation(&lights);
+    let shadows = Rc::new(ShadowMaps::new(&display, options.shadows));
+    let shadow_view = options
+        .show_shadow_maps
+        .then(|| ShadowDebugView::new(&display));
 
-    let mut light = Light::init(&display);
-    let mut hair_cube = HairCube::init(&display);
-    light.set_light_color(light_color);
-    light.set_light_pos(light_pos);
-    hair_cube.set_light_color(light_color);
-    hair_cube.set_light_pos(light_pos);
+    let mut material = match &options.material {
+        Some(path) => HairMaterial::load(path)?,
+        None => HairMaterial::default(),
+    };
+    if let Some(shading) = options.shading {
+        material.shading_model = shading;
+    }
+    if let Some(blend) = options.blend {
+        material.blend = blend;
+    }
+    let strand_blend = if options.strand_opacity < 1.0 {
+        BlendMode::Transparent
+    } else {
+        BlendMode::Opaque
+    };
+
+    let mut scene = SceneGraph::new();
+    for (index, light) in lights.lights().iter().enumerate() {
+        let gizmo = Light::new(&display)
+            .with_name(light.name.clone())
+            .with_index(index);
+        scene.add_drawable(None, Transform::new(), gizmo);
+    }
+    if let Some(path) = &options.mesh {
+        let name = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+        let mut cube = HairCube::from_files(
+            &display,
+            &load_obj(path)?,
+            options.texture.as_deref(),
+            options.shift_map.as_deref(),
+        )?
+        .with_name(name.unwrap_or_else(|| String::from("hair_mesh")))
+        .with_material(material)
+        .with_max_lights(&display, options.max_lights)?;
+        if options.dev {
+            cube.enable_hot_reload(&display, options.texture.clone(), options.shift_map.clone());
+        }
+        scene.add_drawable(None, Transform::new(), cube);
+    }
+    if let Some(path) = &options.gltf {
+        let import = ImportOptions {
+            hair_material: material,
+            hair_blend: options.blend,
+            max_lights: options.max_lights,
+        };
+        load_gltf(&display, path, &mut scene, None, &import)?;
+    }
+    if let Some(base) = &options.groom {
+        let base = match base {
+            GroomBase::Sphere => mesh::uv_sphere(0.5, 24, 48),
+            GroomBase::Plane => mesh::plane(1.0, 8),
+            GroomBase::Obj(path) => load_obj(path)?,
+        };
+        let params = GroomParams {
+            seed: options.seed,
+            ..GroomParams::default()
+        };
+        let mut strands = HairStrands::new(&display, &groom(&base, &params).hairs)?
+            .with_material(material)
+            .with_max_lights(&display, options.max_lights)?
+            .with_deep_opacity(&display, options.deep_opacity)?;
+        strands.set_mode(options.strand_mode);
+        strands.set_opacity(options.strand_opacity);
+        strands.set_blend_mode(strand_blend);
+        let scalp = DiffuseMesh::new(&display, &base, None, [0.1, 0.07, 0.05, 1.0])?
+            .with_name("scalp")
+            .with_max_lights(&display, options.max_lights)?;
+        scene.add_drawable(None, Transform::new(), scalp);
+        scene.add_drawable(None, Transform::new(), strands);
+    }
+    if let Some(path) = &options.hair {
+        let file = load_hair(path)?;
+        let mut hairs = file.strands();
+        fit_strands(&mut hairs, 2.0);
+        let mut strands = HairStrands::new(&display, &hairs)?
+            .with_name("hair_file")
+            .with_material(material)
+            .with_max_lights(&display, options.max_lights)?
+            .with_deep_opacity(&display, options.deep_opacity)?
+            // the hair collection models are z-up
+            .with_transform(Transform::new().with_rotation(Vec3::new(-90.0, 0.0, 0.0)));
+        strands.set_mode(options.strand_mode);
+        strands.set_opacity(options.strand_opacity);
+        strands.set_blend_mode(strand_blend);
+        if let Some((root, tip)) = file.root_and_tip_colors() {
+            strands.set_colors(root, tip);
+        }
+        scene.add_drawable(None, Transform::new(), strands);
+    }
+    if options.mesh.is_none()
+        && options.gltf.is_none()
+        && options.groom.is_none()
+        && options.hair.is_none()
+    {
+        let mut cube = HairCube::from_files(
+            &display,
+            &cube_mesh(),
+            options.texture.as_deref(),
+            options.shift_map.as_deref(),
+        )?
+        .with_material(material)
+        .with_max_lights(&display, options.max_lights)?;
+        if options.dev {
+            cube.enable_hot_reload(&display, options.texture.clone(), options.shift_map.clone());
+        }
+        scene.add_drawable(None, Transform::new(), cube);
+    }
+    if options.ground {
+        let ground = DiffuseMesh::new(&display, &mesh::plane(8.0, 1), None, [0.5, 0.5, 0.5, 1.0])?
+            .with_name("ground")
+            .with_max_lights(&display, options.max_lights)?;
+        let below = Transform::from_translation(Vec3::new(0.0, -1.0, 0.0));
+        scene.add_drawable(None, below, ground);
+    }
+
+    let mut oit = OitBuffers::new(&display, scene_target.dimensions());
+    let mut tone_mapper = ToneMapper::new(&display, options.tone_mapping);
+    let post_settings = if options.no_post {
+        PostSettings::none()
+    } else if let Some(path) = &options.post {
+        PostSettings::load(path)?
+    } else {
+        PostSettings::default()
+    };
+    let mut post = PostStack::new(&display, &post_settings, scene_target.dimensions())?;
 
     event_loop.run(move |event, _, controlflow| {
         camera_handler.handle_event(&event, display.gl_window().window());
+        light_handler.set_active(!camera_handler.is_cursor_grabbed());
+        light_handler.handle_event(&event, display.gl_window().window(), &camera, &mut lights);
 
         match event {
             Event::WindowEvent {
@@ -73,22 +226,52 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         *controlflow = ControlFlow::WaitUntil(rate.refresh_now());
         camera_handler.update_camera(&mut camera, rate.interval());
-        hair_cube.set_camera_pos(camera.get_camera_pos());
+        // a dragged light stays where the mouse puts it, and its animation carries on from there
+        if let Some(index) = light_handler.take_released() {
+            if let Some(light) = lights.get(index) {
+                light_animation.rebase(index, light.gizmo_position());
+            }
+        }
+        if !light_handler.is_dragging() {
+            light_animation.advance(&mut lights, rate.interval());
+        }
+        scene.update(&FrameState {
+            delta_time: rate.interval(),
+            camera_pos: camera.get_camera_pos(),
+            lights: &lights,
+            shadows: &shadows,
+        });
+        shadows.render(&display, &lights, scene.bounds(), |target, pass| {
+            scene.draw_shadows(target, pass)
+        });
+        scene.render_maps();
 
         let camera_mat = camera.get_mat();
 
-        let mut target = display.draw();
-        target.clear_color_and_depth(
-            (
-                0.2 * light_color.0,
-                0.2 * light_color.1,
-                0.2 * light_color.2,
-                1.0,
-            ),
-            1.0,
-        );
-        light.draw_with_frame(&mut target, camera_mat, &drawparams);
-        hair_cube.draw_with_frame(&mut target, camera_mat, &drawparams);
-        target.finish().unwrap();
+        scene_target.resize(&display);
+        oit.resize(&display, scene_target.dimensions());
+        post.resize(&display, scene_target.dimensions());
+        {
+            let mut target = scene_target.framebuffer(&display);
+            let [r, g, b] = lights.ambient_color();
+            target.clear_color_and_depth((0.2 * r, 0.2 * g, 0.2 * b, 1.0), 1.0);
+            scene.draw_with_frame(&mut target, camera_mat, &drawparams);
+        }
+        scene_target.resolve(&display);
+        {
+            // transparent surfaces last, tested against the depth of the opaque ones
+            let mut transparent = oit.framebuffer(&display, scene_target.depth());
+            scene.draw_transparent(&mut transparent, camera_mat, &drawparams);
+            oit.composite(&mut scene_target.resolved_framebuffer(&display));
+        }
+
+        tone_mapper.update_exposure(&display, scene_target.color(), rate.interval());
+
+        let mut frame = display.draw();
+        post.draw(&display, &mut frame, scene_target.color(), &tone_mapper);
+        if let Some(view) = &shadow_view {
+            view.draw(&mut frame, &shadows);
+        }
+        frame.finish().unwrap();
     })
 }
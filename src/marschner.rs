@@ -0,0 +1,300 @@
+//! CPU reference of the Marschner hair model as evaluated by `hair_cube/shader.frag`.
+//!
+//! light scattered by a fibre is split into three lobes: R reflects off the surface, TT is
+//! transmitted through the fibre and TRT is reflected once inside it. each lobe is a product of
+//! a longitudinal gaussian around the half angle, shifted by the cuticle tilt, and an azimuthal
+//! term. the azimuthal terms and the attenuation follow the real-time fit from Karis,
+//! "Physically Based Hair Shading in Unreal" (2016)
+
+use std::f32::consts::PI;
+
+use glm::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// which model the hair shaders evaluate
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadingModel {
+    #[default]
+    KajiyaKay,
+    Marschner,
+}
+
+impl ShadingModel {
+    /// value of the `shading_model` uniform
+    pub fn uniform(self) -> i32 {
+        match self {
+            ShadingModel::KajiyaKay => 0,
+            ShadingModel::Marschner => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarschnerParams {
+    /// tilt of the cuticle scales in degrees, moves the R highlight towards the tip when
+    /// negative. TT is shifted by `-shift / 2` and TRT by `-3 * shift / 2`
+    pub longitudinal_shift: f32,
+    /// standard deviation of the R lobe in degrees, TT is half as wide and TRT twice as wide
+    pub longitudinal_width: f32,
+    /// index of refraction of the fibre
+    pub eta: f32,
+    pub r_strength: f32,
+    pub tt_strength: f32,
+    pub trt_strength: f32,
+}
+
+impl Default for MarschnerParams {
+    fn default() -> Self {
+        Self {
+            longitudinal_shift: -5.0,
+            longitudinal_width: 7.5,
+            eta: 1.55,
+            r_strength: 1.0,
+            tt_strength: 1.0,
+            trt_strength: 1.0,
+        }
+    }
+}
+
+impl MarschnerParams {
+    /// `(R, TT, TRT)` shifts in radians
+    pub fn shifts(&self) -> [f32; 3] {
+        let alpha = self.longitudinal_shift.to_radians();
+        [alpha, -alpha / 2.0, -3.0 * alpha / 2.0]
+    }
+
+    /// `(R, TT, TRT)` widths in radians
+    pub fn widths(&self) -> [f32; 3] {
+        let beta = self.longitudinal_width.to_radians();
+        [beta, beta / 2.0, beta * 2.0]
+    }
+}
+
+/// radiance scattered towards the viewer by each lobe, per unit of incoming light
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Lobes {
+    /// surface reflection is not tinted by the fibre
+    pub r: f32,
+    pub tt: [f32; 3],
+    pub trt: [f32; 3],
+}
+
+impl Lobes {
+    pub fn total(&self) -> [f32; 3] {
+        [0, 1, 2].map(|k| self.r + self.tt[k] + self.trt[k])
+    }
+}
+
+/// normalized gaussian, the longitudinal scattering function M_p
+pub fn longitudinal(width: f32, theta: f32) -> f32 {
+    (-0.5 * theta * theta / (width * width)).exp() / ((2.0 * PI).sqrt() * width)
+}
+
+/// Schlick's approximation of the dielectric fresnel term
+pub fn fresnel(eta: f32, cos_theta: f32) -> f32 {
+    let f0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+    f0 + (1.0 - f0) * (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+/// index of refraction seen by a ray inclined by `theta_d` to the normal plane of the fibre,
+/// what the azimuthal terms refract with
+pub fn bravais_index(eta: f32, cos_theta_d: f32) -> f32 {
+    let sin2_theta_d = 1.0 - cos_theta_d * cos_theta_d;
+    (eta * eta - sin2_theta_d).max(0.0).sqrt() / cos_theta_d.max(1e-4)
+}
+
+/// evaluate the three lobes. `tangent` runs from root to tip, `light_dir` and `view_dir` point
+/// away from the fibre and all three are unit vectors. `base_color` is the colour the fibre
+/// takes after one pass, it drives the absorption of TT and TRT
+pub fn evaluate(
+    params: &MarschnerParams,
+    tangent: Vec3,
+    light_dir: Vec3,
+    view_dir: Vec3,
+    base_color: [f32; 3],
+) -> Lobes {
+    let sin_l = glm::dot(light_dir, tangent).clamp(-1.0, 1.0);
+    let sin_v = glm::dot(view_dir, tangent).clamp(-1.0, 1.0);
+    let (theta_l, theta_v) = (sin_l.asin(), sin_v.asin());
+    let theta_h = 0.5 * (theta_l + theta_v);
+    let cos_theta_d = (0.5 * (theta_l - theta_v)).cos().max(1e-4);
+
+    // azimuth between the light and the view projected into the normal plane
+    let lp = light_dir - tangent * sin_l;
+    let vp = view_dir - tangent * sin_v;
+    let cos_phi = glm::dot(lp, vp) / (glm::dot(lp, lp) * glm::dot(vp, vp) + 1e-4).sqrt();
+    let cos_half_phi = (0.5 + 0.5 * cos_phi).max(0.0).sqrt();
+
+    let [alpha_r, alpha_tt, alpha_trt] = params.shifts();
+    let [beta_r, beta_tt, beta_trt] = params.widths();
+    let eta = params.eta;
+    let a = 1.0 / bravais_index(eta, cos_theta_d);
+
+    let m_r = longitudinal(beta_r, theta_h - alpha_r);
+    let n_r = 0.25 * cos_half_phi;
+    let f_r = fresnel(
+        eta,
+        (0.5 + 0.5 * glm::dot(light_dir, view_dir)).max(0.0).sqrt(),
+    );
+    let r = params.r_strength * m_r * n_r * f_r;
+
+    let m_tt = longitudinal(beta_tt, theta_h - alpha_tt);
+    let h = cos_half_phi * (1.0 + a * (0.6 - 0.8 * cos_phi));
+    let f = fresnel(eta, cos_theta_d * (1.0 - h * h).max(0.0).sqrt());
+    let f_tt = (1.0 - f) * (1.0 - f);
+    let t_exp = 0.5 * (1.0 - (h * a) * (h * a)).max(0.0).sqrt() / cos_theta_d;
+    let n_tt = (-3.65 * cos_phi - 3.98).exp();
+    let tt = base_color.map(|c| params.tt_strength * m_tt * n_tt * f_tt * c.powf(t_exp));
+
+    let m_trt = longitudinal(beta_trt, theta_h - alpha_trt);
+    let f = fresnel(eta, cos_theta_d * 0.5);
+    let f_trt = (1.0 - f) * (1.0 - f) * f;
+    let n_trt = (17.0 * cos_phi - 16.78).exp();
+    let trt =
+        base_color.map(|c| params.trt_strength * m_trt * n_trt * f_trt * c.powf(0.8 / cos_theta_d));
+
+    Lobes { r, tt, trt }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TANGENT: Vec3 = Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    };
+
+    /// unit direction at longitudinal angle `theta` (towards the tip) and azimuth `phi`
+    /// around the fibre, both in radians
+    fn direction(theta: f32, phi: f32) -> Vec3 {
+        Vec3::new(
+            theta.cos() * phi.cos(),
+            theta.sin(),
+            theta.cos() * phi.sin(),
+        )
+    }
+
+    /// view angle that maximizes `lobe` for a light at `theta_l`
+    fn peak(theta_l: f32, phi: f32, lobe: impl Fn(&Lobes) -> f32) -> f32 {
+        let params = MarschnerParams::default();
+        let light = direction(theta_l, 0.0);
+        (-880..=880)
+            .map(|i| i as f32 * 0.001)
+            .max_by(|&a, &b| {
+                let la = evaluate(&params, TANGENT, light, direction(a, phi), [1.0; 3]);
+                let lb = evaluate(&params, TANGENT, light, direction(b, phi), [1.0; 3]);
+                lobe(&la).total_cmp(&lobe(&lb))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn longitudinal_term_is_normalized() {
+        let width = 0.1;
+        let step = 0.001;
+        let integral: f32 = (-2000..=2000)
+            .map(|i| longitudinal(width, i as f32 * step) * step)
+            .sum();
+        assert!((integral - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn lobes_peak_at_their_shifted_half_angle() {
+        let params = MarschnerParams::default();
+        let [alpha_r, alpha_tt, alpha_trt] = params.shifts();
+        let theta_l = 0.3;
+
+        // reflection seen from the light side, transmission from the opposite side
+        let theta_r = peak(theta_l, 0.0, |l| l.r);
+        assert!((0.5 * (theta_l + theta_r) - alpha_r).abs() < 0.01);
+        let theta_tt = peak(theta_l, PI, |l| l.tt[0]);
+        assert!((0.5 * (theta_l + theta_tt) - alpha_tt).abs() < 0.01);
+        // the fresnel term pulls the widest lobe a little off its centre
+        let theta_trt = peak(theta_l, 0.0, |l| l.trt[0]);
+        assert!((0.5 * (theta_l + theta_trt) - alpha_trt).abs() < 0.03);
+
+        // the shifts separate the highlights along the fibre
+        assert!(theta_r < theta_tt && theta_tt < theta_trt);
+    }
+
+    #[test]
+    fn bravais_index_follows_eta() {
+        assert!((bravais_index(1.55, 1.0) - 1.55).abs() < 1e-6);
+        assert!((bravais_index(1.3, 1.0) - 1.3).abs() < 1e-6);
+        // inclined rays see a denser fibre
+        assert!(bravais_index(1.55, 0.8) > bravais_index(1.55, 0.9));
+        assert!(bravais_index(1.55, 0.8) > bravais_index(1.3, 0.8));
+    }
+
+    #[test]
+    fn eta_changes_the_transmission() {
+        let (light, view) = (direction(0.2, 0.0), direction(-0.3, 2.8));
+        let lobes = |eta| {
+            let params = MarschnerParams {
+                eta,
+                ..MarschnerParams::default()
+            };
+            evaluate(&params, TANGENT, light, view, [0.5; 3])
+        };
+        assert_ne!(lobes(1.3).tt, lobes(1.8).tt);
+    }
+
+    #[test]
+    fn transmission_needs_backlighting() {
+        let params = MarschnerParams::default();
+        let light = direction(0.2, 0.0);
+        let front = evaluate(&params, TANGENT, light, direction(-0.2, 0.0), [1.0; 3]);
+        let back = evaluate(&params, TANGENT, light, direction(-0.2, PI), [1.0; 3]);
+        assert!(back.tt[0] > 100.0 * front.tt[0]);
+        assert!(front.r > back.r);
+        assert!(front.trt[0] > back.trt[0]);
+    }
+
+    #[test]
+    fn absorption_tints_only_the_inner_lobes() {
+        let params = MarschnerParams::default();
+        let (light, view) = (direction(0.2, 0.0), direction(-0.3, 0.5));
+        let lobes = evaluate(&params, TANGENT, light, view, [1.0, 0.5, 0.1]);
+        let white = evaluate(&params, TANGENT, light, view, [1.0; 3]);
+        assert_eq!(lobes.r, white.r);
+        assert!(lobes.tt[0] > lobes.tt[1] && lobes.tt[1] > lobes.tt[2]);
+        assert!(lobes.trt[0] > lobes.trt[1] && lobes.trt[1] > lobes.trt[2]);
+        assert_eq!(lobes.trt[0], white.trt[0]);
+    }
+
+    #[test]
+    fn strengths_scale_each_lobe() {
+        let params = MarschnerParams {
+            r_strength: 0.0,
+            tt_strength: 2.0,
+            trt_strength: 0.0,
+            ..MarschnerParams::default()
+        };
+        let (light, view) = (direction(0.1, 0.0), direction(0.0, 2.5));
+        let lobes = evaluate(&params, TANGENT, light, view, [0.8; 3]);
+        let reference = evaluate(&MarschnerParams::default(), TANGENT, light, view, [0.8; 3]);
+        assert_eq!(lobes.r, 0.0);
+        assert_eq!(lobes.trt, [0.0; 3]);
+        assert!((lobes.tt[0] - 2.0 * reference.tt[0]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn lobes_are_finite_and_non_negative() {
+        let params = MarschnerParams::default();
+        for i in 0..12 {
+            for j in 0..12 {
+                let light = direction(i as f32 * 0.26 - 1.5, j as f32 * 0.5);
+                let view = direction(1.5 - j as f32 * 0.26, i as f32 * 0.5);
+                let total = evaluate(&params, TANGENT, light, view, [0.6, 0.4, 0.2]).total();
+                assert!(total.iter().all(|c| c.is_finite() && *c >= 0.0));
+            }
+        }
+        // looking straight along the fibre
+        let total = evaluate(&params, TANGENT, TANGENT, TANGENT, [0.5; 3]).total();
+        assert!(total.iter().all(|c| c.is_finite()));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use glium::{Display, IndexBuffer, VertexBuffer};
+use glium::index::{IndexBufferAny, PrimitiveType};
+use glm::Vec3;
+
+use crate::hair_cube::Vertex;
+
+pub mod obj;
+
+/// indexed triangle list in the hair vertex layout
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn vertex_buffer(
+        &self,
+        display: &Display,
+    ) -> Result<VertexBuffer<Vertex>, glium::vertex::BufferCreationError> {
+        VertexBuffer::new(display, &self.vertices)
+    }
+
+    /// upload the indices as u16 when they fit, u32 otherwise
+    pub fn index_buffer(
+        &self,
+        display: &Display,
+    ) -> Result<IndexBufferAny, glium::index::BufferCreationError> {
+        if self.vertices.len() <= u16::MAX as usize + 1 {
+            let indices: Vec<u16> = self.indices.iter().map(|&i| i as u16).collect();
+            Ok(IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices)?.into())
+        } else {
+            Ok(IndexBuffer::new(display, PrimitiveType::TrianglesList, &self.indices)?.into())
+        }
+    }
+
+    /// centre and radius of a sphere around every vertex, `None` without vertices
+    pub fn bounding_sphere(&self) -> Option<(Vec3, f32)> {
+        sphere_around(self.vertices.iter().map(|vertex| &vertex.position))
+    }
+
+    fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+    }
+
+    /// replace all normals with smooth, area weighted vertex normals.
+    /// vertices sharing a position share a normal, even when their uvs differ
+    pub fn generate_normals(&mut self) {
+        self.generate_normals_where(|_| true);
+    }
+
+    /// like [`generate_normals`](Self::generate_normals), but only for the vertices whose
+    /// index `replace` accepts. the others keep their normals
+    pub fn generate_normals_where(&mut self, replace: impl Fn(usize) -> bool) {
+        let mut welded: HashMap<[u32; 3], Vec3> = HashMap::new();
+        for [a, b, c] in self.triangles() {
+            let (pa, pb, pc) = (
+                to_vec3(self.vertices[a].position),
+                to_vec3(self.vertices[b].position),
+                to_vec3(self.vertices[c].position),
+            );
+            // not normalized, so larger triangles weigh more
+            let face_normal = glm::cross(pb - pa, pc - pa);
+            for i in [a, b, c] {
+                let sum = welded
+                    .entry(position_key(self.vertices[i].position))
+                    .or_insert_with(|| Vec3::new(0.0, 0.0, 0.0));
+                *sum = *sum + face_normal;
+            }
+        }
+
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            if !replace(i) {
+                continue;
+            }
+            let normal = welded
+                .get(&position_key(vertex.position))
+                .copied()
+                .filter(|n| glm::length(*n) > f32::EPSILON)
+                .map(glm::normalize)
+                .unwrap_or_else(|| Vec3::new(0.0, 1.0, 0.0));
+            vertex.normal = *normal.as_array();
+        }
+    }
+
+    /// replace all binormals with the direction of increasing v, orthogonalized against the
+    /// normal. this is the direction the hair runs in for textures with vertical strands
+    pub fn generate_binormals(&mut self) {
+        let mut sums = vec![Vec3::new(0.0, 0.0, 0.0); self.vertices.len()];
+        for [a, b, c] in self.triangles() {
+            let (va, vb, vc) = (self.vertices[a], self.vertices[b], self.vertices[c]);
+            let e1 = to_vec3(vb.position) - to_vec3(va.position);
+            let e2 = to_vec3(vc.position) - to_vec3(va.position);
+            let (du1, dv1) = (
+                vb.tex_coord[0] - va.tex_coord[0],
+                vb.tex_coord[1] - va.tex_coord[1],
+            );
+            let (du2, dv2) = (
+                vc.tex_coord[0] - va.tex_coord[0],
+                vc.tex_coord[1] - va.tex_coord[1],
+            );
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+            let bitangent = (e2 * du1 - e1 * du2) / det;
+            for i in [a, b, c] {
+                sums[i] = sums[i] + bitangent;
+            }
+        }
+
+        for (vertex, sum) in self.vertices.iter_mut().zip(sums) {
+            let normal = to_vec3(vertex.normal);
+            let projected = sum - normal * glm::dot(normal, sum);
+            let binormal = if glm::length(projected) > 1e-6 {
+                glm::normalize(projected)
+            } else {
+                any_perpendicular(normal)
+            };
+            vertex.binormal = *binormal.as_array();
+        }
+    }
+}
+
+/// uv sphere around the origin, the binormals run along the meridians
+pub fn uv_sphere(radius: f32, rings: u32, sectors: u32) -> MeshData {
+    let rings = rings.max(2);
+    let sectors = sectors.max(3);
+    let mut mesh = MeshData::default();
+    for ring in 0..=rings {
+        let v = ring as f32 / rings as f32;
+        let theta = v * std::f32::consts::PI;
+        for sector in 0..=sectors {
+            let u = sector as f32 / sectors as f32;
+            let phi = u * 2.0 * std::f32::consts::PI;
+            let normal = [
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                -theta.sin() * phi.sin(),
+            ];
+            mesh.vertices.push(Vertex {
+                position: normal.map(|c| c * radius),
+                normal,
+                tex_coord: [u, 1.0 - v],
+                binormal: [0.0, 0.0, 0.0],
+            });
+        }
+    }
+    let stride = sectors + 1;
+    for ring in 0..rings {
+        for sector in 0..sectors {
+            let a = ring * stride + sector;
+            let b = a + stride;
+            mesh.indices
+                .extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+        }
+    }
+    mesh.generate_binormals();
+    mesh
+}
+
+/// square in the xz plane facing +y, the hair runs towards -z
+pub fn plane(size: f32, subdivisions: u32) -> MeshData {
+    let cells = subdivisions.max(1);
+    let mut mesh = MeshData::default();
+    for row in 0..=cells {
+        for col in 0..=cells {
+            let u = col as f32 / cells as f32;
+            let v = row as f32 / cells as f32;
+            mesh.vertices.push(Vertex {
+                position: [(u - 0.5) * size, 0.0, (0.5 - v) * size],
+                normal: [0.0, 1.0, 0.0],
+                tex_coord: [u, v],
+                binormal: [0.0, 0.0, -1.0],
+            });
+        }
+    }
+    let stride = cells + 1;
+    for row in 0..cells {
+        for col in 0..cells {
+            let a = row * stride + col;
+            let b = a + stride;
+            mesh.indices
+                .extend_from_slice(&[a, a + 1, b, a + 1, b + 1, b]);
+        }
+    }
+    mesh
+}
+
+/// sphere around `points` centred on their bounding box, `None` without points
+pub fn sphere_around<'a>(
+    points: impl Iterator<Item = &'a [f32; 3]> + Clone,
+) -> Option<(Vec3, f32)> {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for p in points.clone() {
+        for k in 0..3 {
+            min[k] = min[k].min(p[k]);
+            max[k] = max[k].max(p[k]);
+        }
+    }
+    if min[0] > max[0] {
+        return None;
+    }
+    let centre = to_vec3([0, 1, 2].map(|k| 0.5 * (min[k] + max[k])));
+    let radius = points
+        .map(|&p| glm::distance(centre, to_vec3(p)))
+        .fold(0.0, f32::max);
+    Some((centre, radius))
+}
+
+fn to_vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+fn position_key(p: [f32; 3]) -> [u32; 3] {
+    // +0.0 and -0.0 must weld together
+    p.map(|x| (x + 0.0).to_bits())
+}
+
+/// some unit vector perpendicular to `n`, used where uvs give no usable direction
+fn any_perpendicular(n: Vec3) -> Vec3 {
+    let helper = if n.y.abs() < 0.99 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(0.0, 0.0, -1.0)
+    };
+    let projected = helper - n * glm::dot(n, helper);
+    if glm::length(projected) > 1e-6 {
+        glm::normalize(projected)
+    } else {
+        helper
+    }
+}
@@ -0,0 +1,305 @@
+//! minimal Wavefront OBJ reader, supporting `v`, `vt`, `vn` and polygonal `f` records.
+//! everything else (groups, materials, smoothing groups, ...) is ignored
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::hair_cube::Vertex;
+use crate::mesh::MeshData;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// the file contains no faces
+    Empty,
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "failed to read obj file: {}", err),
+            ObjError::Parse { line, message } => write!(f, "obj line {}: {}", line, message),
+            ObjError::Empty => write!(f, "obj file contains no faces"),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> Self {
+        ObjError::Io(err)
+    }
+}
+
+pub fn load_obj(path: impl AsRef<Path>) -> Result<MeshData, ObjError> {
+    parse_obj(BufReader::new(File::open(path)?))
+}
+
+/// parse an OBJ stream into a triangle mesh. polygons are fan triangulated, normals are
+/// generated for face vertices without one and binormals are always generated from the uvs
+pub fn parse_obj<R: BufRead>(reader: R) -> Result<MeshData, ObjError> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut tex_coords: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut mesh = MeshData::default();
+    // (position, tex_coord, normal) -> index into mesh.vertices
+    let mut lookup: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    // per vertex in mesh.vertices, whether its face vertex had no normal
+    let mut missing_normals: Vec<bool> = Vec::new();
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_no = line_idx + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let err = |message: String| ObjError::Parse {
+            line: line_no,
+            message,
+        };
+
+        match keyword {
+            "v" => {
+                let v = parse_floats::<3>(&mut tokens, 3).map_err(err)?;
+                positions.push(v);
+            }
+            "vt" => {
+                // the optional w component is dropped
+                let v = parse_floats::<2>(&mut tokens, 1).map_err(err)?;
+                tex_coords.push(v);
+            }
+            "vn" => {
+                let v = parse_floats::<3>(&mut tokens, 3).map_err(err)?;
+                normals.push(v);
+            }
+            "f" => {
+                let mut polygon = Vec::new();
+                for token in tokens {
+                    let (p, t, n) =
+                        parse_face_vertex(token, positions.len(), tex_coords.len(), normals.len())
+                            .map_err(err)?;
+                    let index = *lookup.entry((p, t, n)).or_insert_with(|| {
+                        missing_normals.push(n.is_none());
+                        mesh.vertices.push(Vertex {
+                            position: positions[p],
+                            normal: n.map(|n| normals[n]).unwrap_or([0.0, 0.0, 0.0]),
+                            tex_coord: t.map(|t| tex_coords[t]).unwrap_or([0.0, 0.0]),
+                            binormal: [0.0, 0.0, 0.0],
+                        });
+                        (mesh.vertices.len() - 1) as u32
+                    });
+                    polygon.push(index);
+                }
+                if polygon.len() < 3 {
+                    return Err(err(format!(
+                        "face needs at least 3 vertices, got {}",
+                        polygon.len()
+                    )));
+                }
+                for i in 1..polygon.len() - 1 {
+                    mesh.indices
+                        .extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if mesh.indices.is_empty() {
+        return Err(ObjError::Empty);
+    }
+    if missing_normals.contains(&true) {
+        mesh.generate_normals_where(|i| missing_normals[i]);
+    }
+    mesh.generate_binormals();
+    Ok(mesh)
+}
+
+/// parse up to `N` floats, at least `required` of them, missing ones are zero
+fn parse_floats<'a, const N: usize>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    required: usize,
+) -> Result<[f32; N], String> {
+    let mut out = [0.0; N];
+    for (i, slot) in out.iter_mut().enumerate() {
+        match tokens.next() {
+            Some(token) => {
+                *slot = token
+                    .parse()
+                    .map_err(|_| format!("invalid number `{}`", token))?;
+            }
+            None if i < required => {
+                return Err(format!("expected {} numbers, got {}", required, i));
+            }
+            None => break,
+        }
+    }
+    Ok(out)
+}
+
+/// parse `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero based indices
+fn parse_face_vertex(
+    token: &str,
+    position_count: usize,
+    tex_coord_count: usize,
+    normal_count: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = token.split('/');
+    let position = match parts.next() {
+        Some(p) if !p.is_empty() => resolve_index(p, position_count)?,
+        _ => return Err(format!("missing position index in `{}`", token)),
+    };
+    let tex_coord = match parts.next() {
+        Some(t) if !t.is_empty() => Some(resolve_index(t, tex_coord_count)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(n) if !n.is_empty() => Some(resolve_index(n, normal_count)?),
+        _ => None,
+    };
+    Ok((position, tex_coord, normal))
+}
+
+/// OBJ indices are one based, negative ones count back from the latest element
+fn resolve_index(token: &str, count: usize) -> Result<usize, String> {
+    let raw: i64 = token
+        .parse()
+        .map_err(|_| format!("invalid index `{}`", token))?;
+    let index = match raw {
+        0 => None,
+        r if r > 0 => Some(r as usize - 1),
+        r => (count as i64 + r).try_into().ok(),
+    };
+    match index {
+        Some(i) if i < count => Ok(i),
+        _ => Err(format!("index {} out of range (have {})", raw, count)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> MeshData {
+        parse_obj(text.as_bytes()).unwrap()
+    }
+
+    fn positions(mesh: &MeshData) -> Vec<[f32; 3]> {
+        mesh.indices
+            .iter()
+            .map(|&i| mesh.vertices[i as usize].position)
+            .collect()
+    }
+
+    #[test]
+    fn resolves_negative_indices() {
+        let mesh = parse(
+            "v 0 0 0
+v 1 0 0
+v 0 1 0
+f -3 -2 -1
+",
+        );
+        assert_eq!(
+            positions(&mesh),
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn fan_triangulates_quads_and_ngons() {
+        let mesh = parse(
+            "v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v -1 1 0
+f 1 2 3 4
+f 1 3 4 5
+",
+        );
+        assert_eq!(mesh.indices.len(), 4 * 3);
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(&mesh.indices[..6], &[0, 1, 2, 0, 2, 3]);
+        assert_eq!(&mesh.indices[6..], &[0, 2, 3, 0, 3, 4]);
+    }
+
+    #[test]
+    fn reads_position_normal_and_position_uv_forms() {
+        let mesh = parse(
+            "v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0.25 0.5
+vn 0 0 1
+f 1//1 2//1 3//1
+f 1/1 2/1 3/1
+",
+        );
+        // the same positions with different attributes are separate vertices
+        assert_eq!(mesh.vertices.len(), 6);
+        assert_eq!(mesh.vertices[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(mesh.vertices[0].tex_coord, [0.0, 0.0]);
+        assert_eq!(mesh.vertices[3].tex_coord, [0.25, 0.5]);
+    }
+
+    #[test]
+    fn generates_only_missing_normals() {
+        // a triangle facing +z without normals and one with a tilted authored normal
+        let mesh = parse(
+            "v 0 0 0
+v 1 0 0
+v 0 1 0
+v 5 0 0
+v 6 0 0
+v 5 1 0
+vn 0 0.6 0.8
+f 1 2 3
+f 4//1 5//1 6//1
+",
+        );
+        for vertex in &mesh.vertices[..3] {
+            let [x, y, z] = vertex.normal;
+            assert!(x.abs() < 1e-6 && y.abs() < 1e-6 && (z - 1.0).abs() < 1e-6);
+        }
+        for vertex in &mesh.vertices[3..] {
+            assert_eq!(vertex.normal, [0.0, 0.6, 0.8]);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_faces() {
+        assert!(matches!(
+            parse_obj("v 0 0 0\nf 1 2 3\n".as_bytes()),
+            Err(ObjError::Parse { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n".as_bytes()),
+            Err(ObjError::Parse { line: 3, .. })
+        ));
+        assert!(matches!(
+            parse_obj("v 0 0 0\n".as_bytes()),
+            Err(ObjError::Empty)
+        ));
+    }
+}
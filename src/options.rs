@@ -0,0 +1,430 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+use crate::hair_strands::StrandMode;
+use glm::Vec3;
+
+use crate::lights::{DEFAULT_MAX_LIGHTS, LIGHT_ARRAY_LIMIT, LightRig};
+use crate::lights::animation::{Keyframes, LightAnimation, RigAnimation};
+use crate::lights::color::LightColor;
+use crate::marschner::ShadingModel;
+use crate::shadows::ShadowSettings;
+use crate::shadows::deep_opacity::{DeepOpacitySettings, OPACITY_LAYER_LIMIT};
+use crate::tone_mapping::{Exposure, ToneMapSettings, ToneOperator};
+use crate::transparency::BlendMode;
+
+/// command line options of the demo
+#[derive(Debug)]
+pub struct Options {
+    /// OBJ file rendered with the hair material instead of the built-in cube
+    pub mesh: Option<PathBuf>,
+    /// colour texture of the hair material, any format the `image` crate reads
+    pub texture: Option<PathBuf>,
+    /// shift map of the hair material
+    pub shift_map: Option<PathBuf>,
+    /// glTF scene imported next to the other objects
+    pub gltf: Option<PathBuf>,
+    /// surface to grow procedural hair on
+    pub groom: Option<GroomBase>,
+    /// Cem Yuksel `.hair` file rendered as strands
+    pub hair: Option<PathBuf>,
+    /// seed of the procedural groom
+    pub seed: u64,
+    pub strand_mode: StrandMode,
+    /// JSON file with the hair material parameters
+    pub material: Option<PathBuf>,
+    /// overrides the shading model of the hair material
+    pub shading: Option<ShadingModel>,
+    /// overrides the blend mode of the hair material
+    pub blend: Option<BlendMode>,
+    /// alpha of the strands, below 1 they are drawn transparent
+    pub strand_opacity: f32,
+    pub lights: LightSetup,
+    pub light_animation: LightAnimationPreset,
+    /// size of the light arrays in the shaders
+    pub max_lights: usize,
+    pub shadows: ShadowSettings,
+    /// draw the shadow maps over the scene
+    pub show_shadow_maps: bool,
+    /// add a plane below the objects to catch their shadows
+    pub ground: bool,
+    /// samples per pixel of the scene target, 0 turns multisampling off
+    pub msaa: u16,
+    pub tone_mapping: ToneMapSettings,
+    /// JSON file with the post-processing passes
+    pub post: Option<PathBuf>,
+    /// tone map the scene without any post-processing
+    pub no_post: bool,
+    /// self-shadowing of the strands
+    pub deep_opacity: DeepOpacitySettings,
+    /// read shaders and textures from disk and reload them when they change
+    pub dev: bool,
+    /// print the usage and exit
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            mesh: None,
+            texture: None,
+            shift_map: None,
+            gltf: None,
+            groom: None,
+            hair: None,
+            seed: 1,
+            strand_mode: StrandMode::CpuRibbons,
+            material: None,
+            shading: None,
+            blend: None,
+            strand_opacity: 1.0,
+            lights: LightSetup::Single,
+            light_animation: LightAnimationPreset::None,
+            max_lights: DEFAULT_MAX_LIGHTS,
+            shadows: ShadowSettings::default(),
+            show_shadow_maps: false,
+            ground: false,
+            msaa: 4,
+            tone_mapping: ToneMapSettings::default(),
+            post: None,
+            no_post: false,
+            deep_opacity: DeepOpacitySettings::default(),
+            dev: false,
+            help: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GroomBase {
+    Sphere,
+    Plane,
+    Obj(PathBuf),
+}
+
+/// light rig the demo starts with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightSetup {
+    /// one white point light
+    Single,
+    /// key, fill and rim light
+    ThreePoint,
+}
+
+impl LightSetup {
+    pub fn rig(self) -> LightRig {
+        match self {
+            LightSetup::Single => LightRig::default(),
+            LightSetup::ThreePoint => LightRig::three_point(),
+        }
+    }
+}
+
+/// how the first light of the rig moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightAnimationPreset {
+    None,
+    /// circles around the origin
+    Orbit,
+    /// flies a loop over and around the origin
+    Path,
+    /// cycles through colour temperatures while its intensity pulses
+    Cycle,
+}
+
+impl LightAnimationPreset {
+    pub fn animation(self, rig: &LightRig) -> RigAnimation {
+        let Some(light) = rig.get(0) else {
+            return RigAnimation::new();
+        };
+        let start = light.gizmo_position();
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        match self {
+            LightAnimationPreset::None => RigAnimation::new(),
+            LightAnimationPreset::Orbit => {
+                RigAnimation::new().with(0, LightAnimation::orbit_through(origin, start, 30.0))
+            }
+            LightAnimationPreset::Path => RigAnimation::new().with(
+                0,
+                LightAnimation::Path(Keyframes::new(vec![
+                    (0.0, start),
+                    (3.0, Vec3::new(3.0, 3.0, 0.0)),
+                    (6.0, Vec3::new(0.0, 2.0, 4.0)),
+                    (9.0, Vec3::new(-3.0, -1.0, 0.0)),
+                    (12.0, start),
+                ])),
+            ),
+            LightAnimationPreset::Cycle => RigAnimation::new()
+                .with(
+                    0,
+                    LightAnimation::Color(Keyframes::new(vec![
+                        (0.0, LightColor::Temperature(2700.0)),
+                        (4.0, LightColor::Temperature(6500.0)),
+                        (8.0, LightColor::Temperature(12000.0)),
+                        (12.0, LightColor::Temperature(2700.0)),
+                    ])),
+                )
+                .with(
+                    0,
+                    LightAnimation::Intensity(Keyframes::new(vec![
+                        (0.0, light.intensity),
+                        (1.5, 0.3 * light.intensity),
+                        (3.0, light.intensity),
+                    ])),
+                ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OptionsError(String);
+
+impl Display for OptionsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{}", self.0, USAGE)
+    }
+}
+
+impl Error for OptionsError {}
+
+pub const USAGE: &str = "usage: kajiya-kay-demo [options]
+    --mesh <file.obj>               render an OBJ mesh with the hair material
+    --texture <image>               colour texture of the hair material
+    --shift-map <image>             shift map of the hair material
+    --gltf <file.gltf|file.glb>     import a glTF scene
+    --groom <sphere|plane|file.obj> grow procedural hair on a surface
+    --hair <file.hair>              render a Cem Yuksel hair file as strands
+    --seed <n>                      seed of the procedural hair
+    --strands <lines|cpu|gpu>       draw strands as lines or as CPU/GPU expanded ribbons
+    --material <file.json>          hair material parameters
+    --shading <model>               hair shading, kajiya-kay or marschner
+    --blend <mode>                  hair alpha: opaque, cutout, coverage or transparent
+    --strand-opacity <a>            alpha of the strands, below 1 they blend
+    --lights <single|three-point>   one point light or a key, fill and rim setup
+    --animate <preset>              animate the first light: orbit, path, cycle or none
+    --max-lights <n>                most lights a shader takes into account, up to 16
+    --no-shadows                    draw without shadow maps
+    --shadow-bias <bias>            depth bias of the shadow lookups
+    --shadow-pcf <n>                shadow filter radius in texels, 0 for hard edges
+    --shadow-size <n>               resolution of the shadow maps
+    --show-shadow-maps              draw the shadow maps over the scene
+    --ground                        add a ground plane below the objects
+    --msaa <n>                      samples per pixel, 0 to turn multisampling off
+    --tonemap <curve>               tone curve: reinhard, aces or uncharted2
+    --exposure <ev>                 exposure in stops, on top of automatic exposure
+    --auto-exposure                 adapt the exposure to the brightness of the scene
+    --post <file.json>              post-processing passes and their order
+    --no-post                       draw without bloom and the other post-processing
+    --no-self-shadows               draw strands without deep opacity maps
+    --opacity-layers <n>            opacity layers behind the nearest strand, up to 256
+    --opacity-spacing <d>           depth of an opacity layer in world units
+    --dev                           reload shaders and textures when their files change
+    --help                          print this message";
+
+impl Options {
+    pub fn from_env() -> Result<Self, OptionsError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, OptionsError> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--help" | "-h" => options.help = true,
+                "--dev" => options.dev = true,
+                "--no-shadows" => options.shadows.enabled = false,
+                "--show-shadow-maps" => options.show_shadow_maps = true,
+                "--ground" => options.ground = true,
+                "--no-post" => options.no_post = true,
+                "--auto-exposure" => {
+                    let ev = match options.tone_mapping.exposure {
+                        Exposure::Manual { ev } | Exposure::Auto { ev, .. } => ev,
+                    };
+                    options.tone_mapping.exposure = Exposure::auto(ev);
+                }
+                "--no-self-shadows" => options.deep_opacity.enabled = false,
+                "--mesh" => options.mesh = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--texture" => options.texture = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--shift-map" => options.shift_map = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--gltf" => options.gltf = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--groom" => {
+                    options.groom = Some(match value(&arg, args.next())?.as_str() {
+                        "sphere" => GroomBase::Sphere,
+                        "plane" => GroomBase::Plane,
+                        path => GroomBase::Obj(PathBuf::from(path)),
+                    })
+                }
+                "--hair" => options.hair = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--seed" => {
+                    let seed = value(&arg, args.next())?;
+                    options.seed = seed
+                        .parse()
+                        .map_err(|_| OptionsError(format!("invalid seed `{}`", seed)))?;
+                }
+                "--strands" => {
+                    options.strand_mode = match value(&arg, args.next())?.as_str() {
+                        "lines" => StrandMode::Lines,
+                        "cpu" => StrandMode::CpuRibbons,
+                        "gpu" => StrandMode::GpuRibbons,
+                        other => {
+                            return Err(OptionsError(format!("unknown strand mode `{}`", other)))
+                        }
+                    }
+                }
+                "--material" => options.material = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--post" => options.post = Some(PathBuf::from(value(&arg, args.next())?)),
+                "--shading" => {
+                    options.shading = match value(&arg, args.next())?.as_str() {
+                        "kajiya-kay" => Some(ShadingModel::KajiyaKay),
+                        "marschner" => Some(ShadingModel::Marschner),
+                        other => {
+                            return Err(OptionsError(format!("unknown shading model `{}`", other)))
+                        }
+                    }
+                }
+                "--blend" => {
+                    options.blend = match value(&arg, args.next())?.as_str() {
+                        "opaque" => Some(BlendMode::Opaque),
+                        "cutout" => Some(BlendMode::Cutout { cutoff: 0.5 }),
+                        "coverage" => Some(BlendMode::AlphaToCoverage),
+                        "transparent" => Some(BlendMode::Transparent),
+                        other => {
+                            return Err(OptionsError(format!("unknown blend mode `{}`", other)))
+                        }
+                    }
+                }
+                "--strand-opacity" => {
+                    let opacity = value(&arg, args.next())?;
+                    options.strand_opacity = match opacity.parse() {
+                        Ok(opacity) if (0.0..=1.0).contains(&opacity) => opacity,
+                        _ => {
+                            return Err(OptionsError(format!(
+                                "invalid strand opacity `{}`",
+                                opacity
+                            )))
+                        }
+                    };
+                }
+                "--lights" => {
+                    options.lights = match value(&arg, args.next())?.as_str() {
+                        "single" => LightSetup::Single,
+                        "three-point" => LightSetup::ThreePoint,
+                        other => {
+                            return Err(OptionsError(format!("unknown light setup `{}`", other)))
+                        }
+                    }
+                }
+                "--animate" => {
+                    options.light_animation = match value(&arg, args.next())?.as_str() {
+                        "none" => LightAnimationPreset::None,
+                        "orbit" => LightAnimationPreset::Orbit,
+                        "path" => LightAnimationPreset::Path,
+                        "cycle" => LightAnimationPreset::Cycle,
+                        other => {
+                            return Err(OptionsError(format!(
+                                "unknown light animation `{}`",
+                                other
+                            )))
+                        }
+                    }
+                }
+                "--max-lights" => {
+                    let max = value(&arg, args.next())?;
+                    options.max_lights = match max.parse() {
+                        Ok(max) if (1..=LIGHT_ARRAY_LIMIT).contains(&max) => max,
+                        _ => {
+                            return Err(OptionsError(format!(
+                                "invalid light count `{}`, expected 1 to {}",
+                                max, LIGHT_ARRAY_LIMIT
+                            )))
+                        }
+                    };
+                }
+                "--shadow-bias" => {
+                    let bias = value(&arg, args.next())?;
+                    options.shadows.bias = bias
+                        .parse()
+                        .map_err(|_| OptionsError(format!("invalid shadow bias `{}`", bias)))?;
+                }
+                "--shadow-pcf" => {
+                    let radius = value(&arg, args.next())?;
+                    options.shadows.pcf_radius = radius.parse().map_err(|_| {
+                        OptionsError(format!("invalid shadow filter radius `{}`", radius))
+                    })?;
+                }
+                "--shadow-size" => {
+                    let size = value(&arg, args.next())?;
+                    options.shadows.map_size = match size.parse() {
+                        Ok(size) if size > 0 => size,
+                        _ => {
+                            return Err(OptionsError(format!("invalid shadow map size `{}`", size)))
+                        }
+                    };
+                }
+                "--msaa" => {
+                    let samples = value(&arg, args.next())?;
+                    options.msaa = match samples.parse::<u16>() {
+                        Ok(samples) if samples == 0 || samples.is_power_of_two() => samples,
+                        _ => {
+                            return Err(OptionsError(format!(
+                                "invalid sample count `{}`, expected 0 or a power of two",
+                                samples
+                            )))
+                        }
+                    };
+                }
+                "--tonemap" => {
+                    options.tone_mapping.operator = match value(&arg, args.next())?.as_str() {
+                        "reinhard" => ToneOperator::Reinhard,
+                        "aces" => ToneOperator::AcesFitted,
+                        "uncharted2" => ToneOperator::Uncharted2,
+                        other => {
+                            return Err(OptionsError(format!("unknown tone curve `{}`", other)))
+                        }
+                    }
+                }
+                "--exposure" => {
+                    let value = value(&arg, args.next())?;
+                    let stops = value
+                        .parse()
+                        .map_err(|_| OptionsError(format!("invalid exposure `{}`", value)))?;
+                    match &mut options.tone_mapping.exposure {
+                        Exposure::Manual { ev } | Exposure::Auto { ev, .. } => *ev = stops,
+                    }
+                }
+                "--opacity-layers" => {
+                    let layers = value(&arg, args.next())?;
+                    options.deep_opacity.layers = match layers.parse() {
+                        Ok(layers) if (1..=OPACITY_LAYER_LIMIT).contains(&layers) => layers,
+                        _ => {
+                            return Err(OptionsError(format!(
+                                "invalid opacity layer count `{}`, expected 1 to {}",
+                                layers, OPACITY_LAYER_LIMIT
+                            )))
+                        }
+                    };
+                }
+                "--opacity-spacing" => {
+                    let spacing = value(&arg, args.next())?;
+                    options.deep_opacity.spacing = match spacing.parse() {
+                        Ok(spacing) if spacing > 0.0 => spacing,
+                        _ => {
+                            return Err(OptionsError(format!(
+                                "invalid opacity layer spacing `{}`",
+                                spacing
+                            )))
+                        }
+                    };
+                }
+                _ => return Err(OptionsError(format!("unknown argument `{}`", arg))),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, OptionsError> {
+    value.ok_or_else(|| OptionsError(format!("`{}` expects a value", flag)))
+}
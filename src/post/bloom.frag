@@ -0,0 +1,15 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+uniform sampler2D scene_color;
+// the first level of the chain, holding the glow of all levels
+uniform sampler2D bloom;
+uniform float intensity;
+
+void main() {
+    vec3 scene = texture(scene_color, TexCoord).rgb;
+    FragColor = vec4(scene + intensity * texture(bloom, TexCoord).rgb, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,159 @@
+//! glow around highlights. the parts of the HDR scene above a threshold are blurred through a
+//! chain of textures of halving size, each downsampled from the last and then upsampled back
+//! into it, so the wide levels spread the glow far at little cost. the sum is added to the scene.
+
+use glium::{Blend, BlendingFunction, Display, DrawParameters, LinearBlendingFactor, uniform};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use glium::Surface;
+use serde::{Deserialize, Serialize};
+
+use crate::render_target::FullscreenPass;
+use crate::shader_source;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    /// brightness above which colour starts to glow
+    pub threshold: f32,
+    /// width of the soft transition around the threshold
+    pub knee: f32,
+    pub intensity: f32,
+    /// textures in the chain, the first has half the size of the scene
+    pub levels: u32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.6,
+            levels: 6,
+        }
+    }
+}
+
+pub struct Bloom {
+    settings: BloomSettings,
+    chain: Vec<Texture2d>,
+    bright: FullscreenPass,
+    downsample: FullscreenPass,
+    upsample: FullscreenPass,
+    combine: FullscreenPass,
+}
+
+impl Bloom {
+    pub fn new(display: &Display, settings: BloomSettings, dimensions: (u32, u32)) -> Self {
+        Self {
+            settings,
+            chain: create_chain(display, dimensions, settings.levels),
+            bright: FullscreenPass::new(display, shader_source!("bright.frag")),
+            downsample: FullscreenPass::new(display, shader_source!("downsample.frag")),
+            upsample: FullscreenPass::new(display, shader_source!("upsample.frag")),
+            combine: FullscreenPass::new(display, shader_source!("bloom.frag")),
+        }
+    }
+
+    /// match the size of the scene
+    pub fn resize(&mut self, display: &Display, (width, height): (u32, u32)) {
+        let first = self.chain.first().map(|level| level.dimensions());
+        if first != Some(((width / 2).max(1), (height / 2).max(1))) {
+            self.chain = create_chain(display, (width, height), self.settings.levels);
+        }
+    }
+
+    /// draw `scene` with its glow into `target`
+    pub fn draw<S: Surface>(&self, display: &Display, scene: &Texture2d, target: &mut S) {
+        let linear = |texture| {
+            Sampler::new(texture)
+                .minify_filter(MinifySamplerFilter::Linear)
+                .magnify_filter(MagnifySamplerFilter::Linear)
+                .wrap_function(SamplerWrapFunction::Clamp)
+        };
+        let texel = |texture: &Texture2d| {
+            let (width, height) = texture.dimensions();
+            [1.0 / width as f32, 1.0 / height as f32]
+        };
+        let framebuffer = |texture| SimpleFrameBuffer::new(display, texture).unwrap();
+
+        let uniforms = uniform! {
+            scene_color: linear(scene),
+            texel: texel(scene),
+            threshold: self.settings.threshold,
+            knee: self.settings.knee,
+        };
+        self.bright.draw(
+            &mut framebuffer(&self.chain[0]),
+            &uniforms,
+            &Default::default(),
+        );
+
+        for pair in self.chain.windows(2) {
+            let uniforms = uniform! {
+                source: linear(&pair[0]),
+                texel: texel(&pair[0]),
+            };
+            self.downsample
+                .draw(&mut framebuffer(&pair[1]), &uniforms, &Default::default());
+        }
+
+        // every level adds its blurred self to the next larger one
+        let additive = BlendingFunction::Addition {
+            source: LinearBlendingFactor::One,
+            destination: LinearBlendingFactor::One,
+        };
+        let params = DrawParameters {
+            blend: Blend {
+                color: additive,
+                alpha: additive,
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            ..Default::default()
+        };
+        for pair in self.chain.windows(2).rev() {
+            let uniforms = uniform! {
+                source: linear(&pair[1]),
+                texel: texel(&pair[1]),
+            };
+            self.upsample
+                .draw(&mut framebuffer(&pair[0]), &uniforms, &params);
+        }
+
+        let uniforms = uniform! {
+            scene_color: Sampler::new(scene)
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+            bloom: linear(&self.chain[0]),
+            // each level added its share, keep the sum independent of their number
+            intensity: self.settings.intensity / self.chain.len() as f32,
+        };
+        self.combine.draw(target, &uniforms, &Default::default());
+    }
+}
+
+/// halving sizes starting at half the scene, as many as `levels` while both sides stay above 1
+fn create_chain(display: &Display, (width, height): (u32, u32), levels: u32) -> Vec<Texture2d> {
+    let mut size = ((width / 2).max(1), (height / 2).max(1));
+    let mut chain = Vec::new();
+    while chain.len() < levels.max(1) as usize {
+        chain.push(
+            Texture2d::empty_with_format(
+                display,
+                UncompressedFloatFormat::F16F16F16F16,
+                MipmapsOption::NoMipmap,
+                size.0,
+                size.1,
+            )
+            .unwrap(),
+        );
+        if size.0 < 4 || size.1 < 4 {
+            break;
+        }
+        size = (size.0 / 2, size.1 / 2);
+    }
+    chain
+}
@@ -0,0 +1,27 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+// linear HDR colour of the scene
+uniform sampler2D scene_color;
+// size of a scene pixel in texture coordinates
+uniform vec2 texel;
+uniform float threshold;
+uniform float knee;
+
+// the part of the scene bright enough to glow, at half its size
+void main() {
+    // four bilinear taps average the 4x4 scene pixels under this one
+    vec3 color = 0.25 * (texture(scene_color, TexCoord + texel * vec2(-1.0, -1.0)).rgb
+        + texture(scene_color, TexCoord + texel * vec2(1.0, -1.0)).rgb
+        + texture(scene_color, TexCoord + texel * vec2(-1.0, 1.0)).rgb
+        + texture(scene_color, TexCoord + texel * vec2(1.0, 1.0)).rgb);
+    float brightness = max(color.r, max(color.g, color.b));
+    // quadratic curve from threshold - knee to threshold + knee, linear above
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-4);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 1e-4);
+    FragColor = vec4(color * contribution, 1.0);
+}
\ No newline at end of file
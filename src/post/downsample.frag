@@ -0,0 +1,20 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+// the next larger level of the chain
+uniform sampler2D source;
+// size of a source pixel in texture coordinates
+uniform vec2 texel;
+
+// the centre and four diagonal bilinear taps of the dual filter (Bjørge 2015)
+void main() {
+    vec3 sum = 4.0 * texture(source, TexCoord).rgb;
+    sum += texture(source, TexCoord + texel * vec2(-1.0, -1.0)).rgb;
+    sum += texture(source, TexCoord + texel * vec2(1.0, -1.0)).rgb;
+    sum += texture(source, TexCoord + texel * vec2(-1.0, 1.0)).rgb;
+    sum += texture(source, TexCoord + texel * vec2(1.0, 1.0)).rgb;
+    FragColor = vec4(sum / 8.0, 1.0);
+}
\ No newline at end of file
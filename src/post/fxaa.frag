@@ -0,0 +1,55 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+// tone mapped colour, sRGB encoded
+uniform sampler2D source;
+// size of a pixel in texture coordinates
+uniform vec2 texel;
+// longest edge the filter follows, in pixels
+uniform float span_max;
+// how much the local contrast shortens the search along the edge
+uniform float reduce_mul;
+uniform float reduce_min;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// FXAA after Timothy Lottes: blur along the direction of the local luma edge
+void main() {
+    vec3 nw = texture(source, TexCoord + texel * vec2(-1.0, -1.0)).rgb;
+    vec3 ne = texture(source, TexCoord + texel * vec2(1.0, -1.0)).rgb;
+    vec3 sw = texture(source, TexCoord + texel * vec2(-1.0, 1.0)).rgb;
+    vec3 se = texture(source, TexCoord + texel * vec2(1.0, 1.0)).rgb;
+    vec3 m = texture(source, TexCoord).rgb;
+    float luma_nw = luma(nw);
+    float luma_ne = luma(ne);
+    float luma_sw = luma(sw);
+    float luma_se = luma(se);
+    float luma_m = luma(m);
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    vec2 dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * reduce_mul, reduce_min);
+    float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, -span_max, span_max) * texel;
+
+    vec3 inner = 0.5 * (texture(source, TexCoord + dir * (1.0 / 3.0 - 0.5)).rgb
+        + texture(source, TexCoord + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 outer = inner * 0.5 + 0.25 * (texture(source, TexCoord - dir * 0.5).rgb
+        + texture(source, TexCoord + dir * 0.5).rgb);
+    // the wide blur crossed another edge if it leaves the local luma range
+    float luma_outer = luma(outer);
+    if (luma_outer < luma_min || luma_outer > luma_max) {
+        FragColor = vec4(inner, 1.0);
+    } else {
+        FragColor = vec4(outer, 1.0);
+    }
+}
\ No newline at end of file
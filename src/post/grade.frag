@@ -0,0 +1,21 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+// tone mapped colour, sRGB encoded like the colours a LUT is made for
+uniform sampler2D source;
+uniform sampler3D lut;
+// entries along each axis of the LUT
+uniform float lut_size;
+// 0 keeps the colour, 1 is the full grade
+uniform float strength;
+
+void main() {
+    vec3 color = clamp(texture(source, TexCoord).rgb, 0.0, 1.0);
+    // the centres of the first and last entries sit half an entry inside the texture
+    vec3 coord = color * ((lut_size - 1.0) / lut_size) + 0.5 / lut_size;
+    vec3 graded = texture(lut, coord).rgb;
+    FragColor = vec4(mix(color, graded, strength), 1.0);
+}
\ No newline at end of file
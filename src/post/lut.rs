@@ -0,0 +1,201 @@
+//! 3D colour lookup tables in the `.cube` format of Adobe and DaVinci Resolve.
+//!
+//! after optional `TITLE` and `DOMAIN_MIN`/`DOMAIN_MAX` lines, `LUT_3D_SIZE n` is followed by
+//! n³ lines of output colours, `r g b` in [0, 1], with red changing fastest and blue slowest.
+//! `#` starts a comment.
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{self, Display as FmtDisplay, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use glium::Display;
+use glium::texture::{ClientFormat, MipmapsOption, RawImage3d, Texture3d, UncompressedFloatFormat};
+
+#[derive(Debug)]
+pub enum LutError {
+    Io(io::Error),
+    Invalid(String),
+}
+
+impl FmtDisplay for LutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LutError::Io(err) => write!(f, "failed to read LUT file: {}", err),
+            LutError::Invalid(message) => write!(f, "invalid LUT file: {}", message),
+        }
+    }
+}
+
+impl Error for LutError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LutError::Io(err) => Some(err),
+            LutError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LutError {
+    fn from(err: io::Error) -> Self {
+        LutError::Io(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CubeLut {
+    /// entries along each axis
+    pub size: usize,
+    /// size³ colours, red index fastest
+    pub data: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    /// the table that leaves every colour as it is
+    pub fn identity(size: usize) -> Self {
+        let step = 1.0 / (size - 1) as f32;
+        let mut data = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push([r as f32 * step, g as f32 * step, b as f32 * step]);
+                }
+            }
+        }
+        Self { size, data }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LutError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, LutError> {
+        let mut size = None;
+        let mut data = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let mut words = line.split_whitespace();
+            let Some(first) = words.next() else {
+                continue;
+            };
+            let invalid =
+                |message: &str| LutError::Invalid(format!("line {}: {}", number + 1, message));
+            match first {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => return Err(invalid("1D tables are not supported")),
+                "LUT_3D_SIZE" => {
+                    let n = words
+                        .next()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .filter(|&n| (2..=256).contains(&n))
+                        .ok_or_else(|| invalid("expected a size from 2 to 256"))?;
+                    size = Some(n);
+                }
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let expected = if first == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    let values: Vec<f32> = words.filter_map(|w| w.parse().ok()).collect();
+                    if values != [expected; 3] {
+                        return Err(invalid("only the domain from 0 to 1 is supported"));
+                    }
+                }
+                _ => {
+                    let values: Vec<f32> = line
+                        .split_whitespace()
+                        .map(|w| w.parse())
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| invalid("expected three numbers"))?;
+                    let [r, g, b] = values[..] else {
+                        return Err(invalid("expected three numbers"));
+                    };
+                    if size.is_none() {
+                        return Err(invalid("colours before LUT_3D_SIZE"));
+                    }
+                    data.push([r, g, b]);
+                }
+            }
+        }
+
+        let size = size.ok_or_else(|| LutError::Invalid(String::from("missing LUT_3D_SIZE")))?;
+        if data.len() != size * size * size {
+            return Err(LutError::Invalid(format!(
+                "expected {} colours for size {}, found {}",
+                size * size * size,
+                size,
+                data.len()
+            )));
+        }
+        Ok(Self { size, data })
+    }
+
+    /// red, green and blue index along x, y and z
+    pub fn texture(&self, display: &Display) -> Texture3d {
+        let size = self.size as u32;
+        let image = RawImage3d {
+            data: Cow::Owned(self.data.iter().flatten().copied().collect()),
+            width: size,
+            height: size,
+            depth: size,
+            format: ClientFormat::F32F32F32,
+        };
+        Texture3d::with_format(
+            display,
+            image,
+            UncompressedFloatFormat::F16F16F16,
+            MipmapsOption::NoMipmap,
+        )
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_small_table() {
+        let text = "# generated
+TITLE \"identity\"
+LUT_3D_SIZE 2
+DOMAIN_MIN 0 0 0
+DOMAIN_MAX 1 1 1
+
+0 0 0
+1 0 0
+0 1 0
+1 1 0 # yellow
+0 0 1
+1 0 1
+0 1 1
+1 1 1
+";
+        assert_eq!(CubeLut::parse(text).unwrap(), CubeLut::identity(2));
+    }
+
+    #[test]
+    fn identity_runs_red_fastest() {
+        let lut = CubeLut::identity(3);
+        assert_eq!(lut.data.len(), 27);
+        assert_eq!(lut.data[1], [0.5, 0.0, 0.0]);
+        assert_eq!(lut.data[3], [0.0, 0.5, 0.0]);
+        assert_eq!(lut.data[9], [0.0, 0.0, 0.5]);
+        assert_eq!(lut.data[26], [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn rejects_a_wrong_number_of_colours() {
+        let err = CubeLut::parse("LUT_3D_SIZE 2\n0 0 0\n1 1 1\n").unwrap_err();
+        assert!(err.to_string().contains("expected 8 colours"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(CubeLut::parse("LUT_3D_SIZE 2\n0 0\n").is_err());
+        assert!(CubeLut::parse("LUT_3D_SIZE 2\n0 0 x\n").is_err());
+        assert!(CubeLut::parse("0 0 0\nLUT_3D_SIZE 2\n").is_err());
+        assert!(CubeLut::parse("LUT_1D_SIZE 4\n").is_err());
+        assert!(CubeLut::parse("LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n").is_err());
+        assert!(CubeLut::parse("").is_err());
+    }
+}
@@ -0,0 +1,391 @@
+//! post-processing between the scene target and the window.
+//!
+//! the stack is a list of passes read from JSON, each with an `enabled` switch:
+//!
+//! ```json
+//! { "passes": [
+//!     { "pass": "bloom", "threshold": 1.0, "intensity": 0.6 },
+//!     { "pass": "color_grade", "lut": "warm.cube", "strength": 0.8 },
+//!     { "pass": "fxaa" },
+//!     { "pass": "vignette", "enabled": false }
+//! ] }
+//! ```
+//!
+//! bloom works on linear HDR colour and runs before the tone mapper, the other passes work on
+//! the tone mapped, sRGB encoded colour after it. within each side the passes run in the order
+//! of the list. missing fields keep their defaults.
+
+use std::error::Error;
+use std::fmt::{self, Display as FmtDisplay, Formatter};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use glium::{Display, Frame, Surface, uniform};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{MipmapsOption, Texture2d, Texture3d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use serde::{Deserialize, Serialize};
+
+use crate::post::bloom::{Bloom, BloomSettings};
+use crate::post::lut::{CubeLut, LutError};
+use crate::render_target::FullscreenPass;
+use crate::shader_source;
+use crate::tone_mapping::ToneMapper;
+
+pub mod bloom;
+pub mod lut;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FxaaSettings {
+    pub enabled: bool,
+    /// longest edge the filter follows, in pixels
+    pub span_max: f32,
+    /// how much the local contrast shortens the search along the edge
+    pub reduce_mul: f32,
+    pub reduce_min: f32,
+}
+
+impl Default for FxaaSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            span_max: 8.0,
+            reduce_mul: 1.0 / 8.0,
+            reduce_min: 1.0 / 128.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VignetteSettings {
+    pub enabled: bool,
+    /// how dark the corners get, 0 to 1
+    pub intensity: f32,
+    /// distance from the centre where darkening starts, 1 is a corner
+    pub radius: f32,
+    pub softness: f32,
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 0.4,
+            radius: 0.9,
+            softness: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorGradeSettings {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// `.cube` file, see [`lut`]
+    pub lut: PathBuf,
+    /// 0 keeps the colour, 1 is the full grade
+    #[serde(default = "full_strength")]
+    pub strength: f32,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn full_strength() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "pass", rename_all = "snake_case")]
+pub enum PostPass {
+    Bloom(BloomSettings),
+    Fxaa(FxaaSettings),
+    Vignette(VignetteSettings),
+    ColorGrade(ColorGradeSettings),
+}
+
+impl PostPass {
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            PostPass::Bloom(settings) => settings.enabled,
+            PostPass::Fxaa(settings) => settings.enabled,
+            PostPass::Vignette(settings) => settings.enabled,
+            PostPass::ColorGrade(settings) => settings.enabled,
+        }
+    }
+
+    /// runs on linear colour before tone mapping
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, PostPass::Bloom(_))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PostSettings {
+    pub passes: Vec<PostPass>,
+}
+
+impl Default for PostSettings {
+    /// bloom only, the multisampled scene target leaves FXAA little to smooth
+    fn default() -> Self {
+        Self {
+            passes: vec![PostPass::Bloom(BloomSettings::default())],
+        }
+    }
+}
+
+impl PostSettings {
+    /// no passes, the scene is only tone mapped
+    pub fn none() -> Self {
+        Self { passes: Vec::new() }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PostError> {
+        let path = path.as_ref();
+        let mut settings: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        // LUT paths are relative to the settings file
+        for pass in settings.passes.iter_mut() {
+            if let PostPass::ColorGrade(grade) = pass {
+                if let Some(dir) = path.parent() {
+                    grade.lut = dir.join(&grade.lut);
+                }
+            }
+        }
+        Ok(settings)
+    }
+}
+
+#[derive(Debug)]
+pub enum PostError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Lut { path: PathBuf, err: LutError },
+}
+
+impl FmtDisplay for PostError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PostError::Io(err) => write!(f, "failed to read post-processing file: {}", err),
+            PostError::Json(err) => write!(f, "invalid post-processing file: {}", err),
+            PostError::Lut { path, err } => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl Error for PostError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PostError::Io(err) => Some(err),
+            PostError::Json(err) => Some(err),
+            PostError::Lut { err, .. } => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for PostError {
+    fn from(err: io::Error) -> Self {
+        PostError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for PostError {
+    fn from(err: serde_json::Error) -> Self {
+        PostError::Json(err)
+    }
+}
+
+/// an enabled pass with what it draws with
+enum Effect {
+    Bloom(Box<Bloom>),
+    Fxaa(FullscreenPass, FxaaSettings),
+    Vignette(FullscreenPass, VignetteSettings),
+    ColorGrade {
+        pass: FullscreenPass,
+        lut: Texture3d,
+        size: usize,
+        strength: f32,
+    },
+}
+
+impl Effect {
+    fn new(display: &Display, pass: &PostPass, dimensions: (u32, u32)) -> Result<Self, PostError> {
+        // passes after the tone mapper write sRGB like it does
+        let display_pass = |fragment| {
+            FullscreenPass::from_builder(display, FullscreenPass::builder(fragment).srgb_output())
+        };
+        Ok(match pass {
+            PostPass::Bloom(settings) => {
+                Effect::Bloom(Box::new(Bloom::new(display, *settings, dimensions)))
+            }
+            PostPass::Fxaa(settings) => {
+                Effect::Fxaa(display_pass(shader_source!("fxaa.frag")), *settings)
+            }
+            PostPass::Vignette(settings) => {
+                Effect::Vignette(display_pass(shader_source!("vignette.frag")), *settings)
+            }
+            PostPass::ColorGrade(settings) => {
+                let lut = CubeLut::load(&settings.lut).map_err(|err| PostError::Lut {
+                    path: settings.lut.clone(),
+                    err,
+                })?;
+                Effect::ColorGrade {
+                    pass: display_pass(shader_source!("grade.frag")),
+                    lut: lut.texture(display),
+                    size: lut.size,
+                    strength: settings.strength,
+                }
+            }
+        })
+    }
+
+    fn resize(&mut self, display: &Display, dimensions: (u32, u32)) {
+        if let Effect::Bloom(bloom) = self {
+            bloom.resize(display, dimensions);
+        }
+    }
+
+    fn draw<S: Surface>(&self, display: &Display, source: &Texture2d, target: &mut S) {
+        let linear = Sampler::new(source)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .wrap_function(SamplerWrapFunction::Clamp);
+        let (width, height) = source.dimensions();
+        let texel = [1.0 / width as f32, 1.0 / height as f32];
+        match self {
+            Effect::Bloom(bloom) => bloom.draw(display, source, target),
+            Effect::Fxaa(pass, settings) => {
+                let uniforms = uniform! {
+                    source: linear,
+                    texel: texel,
+                    span_max: settings.span_max,
+                    reduce_mul: settings.reduce_mul,
+                    reduce_min: settings.reduce_min,
+                };
+                pass.draw(target, &uniforms, &Default::default());
+            }
+            Effect::Vignette(pass, settings) => {
+                let uniforms = uniform! {
+                    source: linear,
+                    intensity: settings.intensity,
+                    radius: settings.radius,
+                    softness: settings.softness,
+                };
+                pass.draw(target, &uniforms, &Default::default());
+            }
+            Effect::ColorGrade {
+                pass,
+                lut,
+                size,
+                strength,
+            } => {
+                let uniforms = uniform! {
+                    source: linear,
+                    lut: Sampler::new(lut)
+                        .minify_filter(MinifySamplerFilter::Linear)
+                        .magnify_filter(MagnifySamplerFilter::Linear)
+                        .wrap_function(SamplerWrapFunction::Clamp),
+                    lut_size: *size as f32,
+                    strength: *strength,
+                };
+                pass.draw(target, &uniforms, &Default::default());
+            }
+        }
+    }
+}
+
+/// the enabled passes of [`PostSettings`] around the tone mapper
+pub struct PostStack {
+    hdr: Vec<Effect>,
+    display: Vec<Effect>,
+    /// targets the passes alternate between
+    hdr_targets: [Texture2d; 2],
+    display_targets: [Texture2d; 2],
+}
+
+impl PostStack {
+    pub fn new(
+        display: &Display,
+        settings: &PostSettings,
+        dimensions: (u32, u32),
+    ) -> Result<Self, PostError> {
+        let mut hdr = Vec::new();
+        let mut display_side = Vec::new();
+        for pass in settings.passes.iter().filter(|pass| pass.is_enabled()) {
+            let effect = Effect::new(display, pass, dimensions)?;
+            if pass.is_hdr() {
+                hdr.push(effect);
+            } else {
+                display_side.push(effect);
+            }
+        }
+        Ok(Self {
+            hdr,
+            display: display_side,
+            hdr_targets: create_targets(display, dimensions, UncompressedFloatFormat::F16F16F16F16),
+            display_targets: create_targets(display, dimensions, UncompressedFloatFormat::U8U8U8U8),
+        })
+    }
+
+    /// match the size of the scene target
+    pub fn resize(&mut self, display: &Display, dimensions: (u32, u32)) {
+        if dimensions == self.hdr_targets[0].dimensions() {
+            return;
+        }
+        self.hdr_targets =
+            create_targets(display, dimensions, UncompressedFloatFormat::F16F16F16F16);
+        self.display_targets =
+            create_targets(display, dimensions, UncompressedFloatFormat::U8U8U8U8);
+        for effect in self.hdr.iter_mut().chain(self.display.iter_mut()) {
+            effect.resize(display, dimensions);
+        }
+    }
+
+    /// run the passes on `scene` and draw the result to `frame`
+    pub fn draw(
+        &self,
+        display: &Display,
+        frame: &mut Frame,
+        scene: &Texture2d,
+        tone_mapper: &ToneMapper,
+    ) {
+        let mut source = scene;
+        for (effect, target) in self.hdr.iter().zip(self.hdr_targets.iter().cycle()) {
+            effect.draw(display, source, &mut framebuffer(display, target));
+            source = target;
+        }
+
+        let Some((last, rest)) = self.display.split_last() else {
+            tone_mapper.draw(frame, source);
+            return;
+        };
+        let mut targets = self.display_targets.iter().cycle();
+        let mut mapped = targets.next().unwrap();
+        tone_mapper.draw(&mut framebuffer(display, mapped), source);
+        for effect in rest {
+            let target = targets.next().unwrap();
+            effect.draw(display, mapped, &mut framebuffer(display, target));
+            mapped = target;
+        }
+        last.draw(display, mapped, frame);
+    }
+}
+
+fn framebuffer<'a>(display: &Display, texture: &'a Texture2d) -> SimpleFrameBuffer<'a> {
+    SimpleFrameBuffer::new(display, texture).unwrap()
+}
+
+fn create_targets(
+    display: &Display,
+    (width, height): (u32, u32),
+    format: UncompressedFloatFormat,
+) -> [Texture2d; 2] {
+    let (width, height) = (width.max(1), height.max(1));
+    [(); 2].map(|_| {
+        Texture2d::empty_with_format(display, format, MipmapsOption::NoMipmap, width, height)
+            .unwrap()
+    })
+}
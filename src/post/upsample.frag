@@ -0,0 +1,24 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+// the next smaller level of the chain, added to the level drawn into
+uniform sampler2D source;
+// size of a source pixel in texture coordinates
+uniform vec2 texel;
+
+// 3x3 tent filter
+void main() {
+    vec3 sum = 4.0 * texture(source, TexCoord).rgb;
+    sum += 2.0 * texture(source, TexCoord + texel * vec2(-1.0, 0.0)).rgb;
+    sum += 2.0 * texture(source, TexCoord + texel * vec2(1.0, 0.0)).rgb;
+    sum += 2.0 * texture(source, TexCoord + texel * vec2(0.0, -1.0)).rgb;
+    sum += 2.0 * texture(source, TexCoord + texel * vec2(0.0, 1.0)).rgb;
+    sum += texture(source, TexCoord + texel * vec2(-1.0, -1.0)).rgb;
+    sum += texture(source, TexCoord + texel * vec2(1.0, -1.0)).rgb;
+    sum += texture(source, TexCoord + texel * vec2(-1.0, 1.0)).rgb;
+    sum += texture(source, TexCoord + texel * vec2(1.0, 1.0)).rgb;
+    FragColor = vec4(sum / 16.0, 1.0);
+}
\ No newline at end of file
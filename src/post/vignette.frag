@@ -0,0 +1,20 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+// tone mapped colour, sRGB encoded
+uniform sampler2D source;
+// how dark the corners get, 0 to 1
+uniform float intensity;
+// distance from the centre where darkening starts, 1 is a corner
+uniform float radius;
+uniform float softness;
+
+void main() {
+    vec3 color = texture(source, TexCoord).rgb;
+    float distance = length(TexCoord - 0.5) * sqrt(2.0);
+    float shade = smoothstep(radius, radius - softness, distance);
+    FragColor = vec4(color * mix(1.0, shade, intensity), 1.0);
+}
\ No newline at end of file
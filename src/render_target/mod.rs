@@ -0,0 +1,272 @@
+//! offscreen targets. the scene is drawn into a floating point colour texture with its own
+//! depth texture, which later passes read, and tone mapped to the window at the end of the
+//! frame, see [`ToneMapper`](crate::tone_mapping::ToneMapper).
+//!
+//! with multisampling the scene is drawn into multisampled textures first and resolved into the
+//! plain ones before the later passes. the window itself is not multisampled: it only receives
+//! the finished frame as one fullscreen quad, whose edges are the window's.
+
+use glium::{
+    BlitMask, BlitTarget, CapabilitiesSource, Display, DrawParameters, implement_vertex, Program,
+    Rect, Surface, VertexBuffer,
+};
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{
+    DepthFormat, DepthTexture2d, DepthTexture2dMultisample, MipmapsOption, Texture2d,
+    Texture2dMultisample, TextureFormat, UncompressedFloatFormat,
+};
+use glium::uniforms::{MagnifySamplerFilter, Uniforms};
+
+use crate::shader::{ProgramBuilder, ShaderSource};
+use crate::shader_source;
+
+const COLOR_FORMAT: UncompressedFloatFormat = UncompressedFloatFormat::F16F16F16F16;
+/// the same for the multisampled and the resolved depth, blitting needs matching formats
+const DEPTH_FORMAT: DepthFormat = DepthFormat::I24;
+/// most samples when the driver cannot list the counts of a format, which every GL 3.3 driver
+/// offers in practice. glium does not report counts above the driver's limit as an error
+const UNLISTED_SAMPLES: u32 = 4;
+
+#[derive(Clone, Copy)]
+struct QuadVertex {
+    position: [f32; 2],
+}
+implement_vertex!(QuadVertex, position location(0));
+
+/// a fragment shader run once for every pixel of a target. the vertex shader hands it
+/// `TexCoord`, running from 0 to 1 across the target
+pub struct FullscreenPass {
+    program: Program,
+    quad: VertexBuffer<QuadVertex>,
+}
+
+impl FullscreenPass {
+    pub fn new(display: &Display, fragment: ShaderSource) -> Self {
+        Self::from_builder(display, Self::builder(fragment))
+    }
+
+    /// the builder [`new`](Self::new) uses, for passes that need defines or includes
+    pub fn builder(fragment: ShaderSource) -> ProgramBuilder {
+        ProgramBuilder::new(shader_source!("fullscreen.vert"), fragment)
+    }
+
+    pub fn from_builder(display: &Display, builder: ProgramBuilder) -> Self {
+        let program = builder
+            .build(display)
+            .unwrap_or_else(|err| panic!("fullscreen pass: {}", err));
+        let corners = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]];
+        let quad =
+            VertexBuffer::new(display, &corners.map(|position| QuadVertex { position })).unwrap();
+        Self { program, quad }
+    }
+
+    pub fn draw<S: Surface, U: Uniforms>(
+        &self,
+        target: &mut S,
+        uniforms: &U,
+        params: &DrawParameters,
+    ) {
+        target
+            .draw(
+                &self.quad,
+                NoIndices(PrimitiveType::TriangleStrip),
+                &self.program,
+                uniforms,
+                params,
+            )
+            .unwrap();
+    }
+}
+
+/// colour and depth the scene is drawn into, sized like the window
+pub struct SceneTarget {
+    color: Texture2d,
+    depth: DepthTexture2d,
+    /// what the scene is drawn into when multisampling
+    multisampled: Option<(Texture2dMultisample, DepthTexture2dMultisample)>,
+    samples: u32,
+}
+
+impl SceneTarget {
+    /// `samples` per pixel, 0 draws straight into the plain textures. unsupported counts fall
+    /// back to the next lower one
+    pub fn new(display: &Display, samples: u32) -> Self {
+        let dimensions = display.get_framebuffer_dimensions();
+        let (color, depth) = create_textures(display, dimensions);
+        let samples = supported_samples(display, samples);
+        let (multisampled, samples) = create_multisampled(display, dimensions, samples);
+        Self {
+            color,
+            depth,
+            multisampled,
+            samples,
+        }
+    }
+
+    /// follow the size of the window, the contents are lost when it changed
+    pub fn resize(&mut self, display: &Display) {
+        let dimensions = display.get_framebuffer_dimensions();
+        if dimensions != self.dimensions() {
+            (self.color, self.depth) = create_textures(display, dimensions);
+            (self.multisampled, self.samples) =
+                create_multisampled(display, dimensions, self.samples);
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.color.dimensions()
+    }
+
+    /// samples per pixel of [`framebuffer`](Self::framebuffer), 0 without multisampling
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// where the scene is drawn, multisampled if enabled
+    pub fn framebuffer(&self, display: &Display) -> SimpleFrameBuffer<'_> {
+        match &self.multisampled {
+            Some((color, depth)) => {
+                SimpleFrameBuffer::with_depth_buffer(display, color, depth).unwrap()
+            }
+            None => self.resolved_framebuffer(display),
+        }
+    }
+
+    /// the plain textures, which hold the scene after [`resolve`](Self::resolve)
+    pub fn resolved_framebuffer(&self, display: &Display) -> SimpleFrameBuffer<'_> {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.color, &self.depth).unwrap()
+    }
+
+    /// average the samples of colour and depth into the plain textures
+    pub fn resolve(&self, display: &Display) {
+        if self.multisampled.is_none() {
+            return;
+        }
+        let (width, height) = self.dimensions();
+        let rect = Rect {
+            left: 0,
+            bottom: 0,
+            width,
+            height,
+        };
+        let target = BlitTarget {
+            left: 0,
+            bottom: 0,
+            width: width as i32,
+            height: height as i32,
+        };
+        self.resolved_framebuffer(display)
+            .blit_buffers_from_simple_framebuffer(
+                &self.framebuffer(display),
+                &rect,
+                &target,
+                MagnifySamplerFilter::Nearest,
+                BlitMask::color_and_depth(),
+            );
+    }
+
+    /// the resolved colour
+    pub fn color(&self) -> &Texture2d {
+        &self.color
+    }
+
+    /// the resolved depth
+    pub fn depth(&self) -> &DepthTexture2d {
+        &self.depth
+    }
+}
+
+fn create_textures(display: &Display, (width, height): (u32, u32)) -> (Texture2d, DepthTexture2d) {
+    // a minimized window reports a size of zero
+    let (width, height) = (width.max(1), height.max(1));
+    let color = Texture2d::empty_with_format(
+        display,
+        COLOR_FORMAT,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+    )
+    .unwrap();
+    let depth = DepthTexture2d::empty_with_format(
+        display,
+        DEPTH_FORMAT,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+    )
+    .unwrap();
+    (color, depth)
+}
+
+/// the most samples up to `requested` that both formats take, as far as the driver tells
+fn supported_samples(display: &Display, requested: u32) -> u32 {
+    if requested == 0 || !glium::texture::is_texture_2d_multisample_supported(display) {
+        return 0;
+    }
+    let formats = &display
+        .get_context()
+        .get_capabilities()
+        .internal_formats_textures;
+    [
+        TextureFormat::UncompressedFloat(COLOR_FORMAT),
+        TextureFormat::DepthFormat(DEPTH_FORMAT),
+    ]
+    .iter()
+    .map(|format| {
+        match formats
+            .get(format)
+            .and_then(|infos| infos.multisamples.as_ref())
+        {
+            Some(counts) => counts
+                .iter()
+                .map(|&count| count as u32)
+                .filter(|&count| count <= requested)
+                .max()
+                .unwrap_or(0),
+            None => requested.min(UNLISTED_SAMPLES),
+        }
+    })
+    .min()
+    .unwrap_or(0)
+}
+
+/// halves the samples until the textures can be created
+fn create_multisampled(
+    display: &Display,
+    (width, height): (u32, u32),
+    mut samples: u32,
+) -> (
+    Option<(Texture2dMultisample, DepthTexture2dMultisample)>,
+    u32,
+) {
+    let (width, height) = (width.max(1), height.max(1));
+    while samples > 1 {
+        let color = Texture2dMultisample::empty_with_format(
+            display,
+            COLOR_FORMAT,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+            samples,
+        );
+        let depth = DepthTexture2dMultisample::empty_with_format(
+            display,
+            DEPTH_FORMAT,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+            samples,
+        );
+        if let (Ok(color), Ok(depth)) = (color, depth) {
+            return (Some((color, depth)), samples);
+        }
+        eprintln!(
+            "no {}x multisampled scene target, trying fewer samples",
+            samples
+        );
+        samples /= 2;
+    }
+    (None, 0)
+}
@@ -0,0 +1,405 @@
+use glium::DrawParameters;
+use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
+use glm::{Mat4, Vec3};
+use num_traits::One;
+
+use crate::{Drawable, FrameState};
+use crate::shadows::ShadowPass;
+use crate::shadows::deep_opacity::transform_sphere;
+use crate::transform::Transform;
+
+/// handle to a node inside a [`SceneGraph`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+struct Node {
+    name: String,
+    local: Transform,
+    /// cached `parent.world * local`, only valid while `dirty` is false
+    world: Mat4,
+    dirty: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    content: Option<Box<dyn Drawable>>,
+}
+
+/// a hierarchy of transforms, each node optionally carrying a drawable.
+///
+/// The world matrix of a node is `parent_world * local`. A drawable attached to a node is drawn
+/// with `world * drawable.transform()`, so its own transform acts as an offset inside the node.
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add an empty node, e.g. a pivot for a light rig
+    pub fn add_node(
+        &mut self,
+        parent: Option<NodeId>,
+        name: impl Into<String>,
+        local: Transform,
+    ) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: name.into(),
+            local,
+            world: Mat4::one(),
+            dirty: true,
+            parent,
+            children: Vec::new(),
+            content: None,
+        });
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    /// add a node holding `drawable`, named after it
+    pub fn add_drawable<D: Drawable + 'static>(
+        &mut self,
+        parent: Option<NodeId>,
+        local: Transform,
+        drawable: D,
+    ) -> NodeId {
+        let id = self.add_node(parent, drawable.name().to_owned(), local);
+        self.node_mut(id).content = Some(Box::new(drawable));
+        id
+    }
+
+    /// first node with the given name, in insertion order
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|n| n.name == name).map(NodeId)
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.node(id).name
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    pub fn local_transform(&self, id: NodeId) -> &Transform {
+        &self.node(id).local
+    }
+
+    /// replace the local transform, invalidating the world matrices of the whole subtree
+    pub fn set_local_transform(&mut self, id: NodeId, local: Transform) {
+        let node = self.node_mut(id);
+        node.local = local;
+        node.dirty = true;
+    }
+
+    /// move `id` under `parent` (or to the roots). Returns false and leaves the graph
+    /// untouched if this would create a cycle
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> bool {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            if a == id {
+                return false;
+            }
+            ancestor = self.node(a).parent;
+        }
+
+        match self.node(id).parent {
+            Some(old) => self.node_mut(old).children.retain(|&c| c != id),
+            None => self.roots.retain(|&r| r != id),
+        }
+        match parent {
+            Some(new) => self.node_mut(new).children.push(id),
+            None => self.roots.push(id),
+        }
+        let node = self.node_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+        true
+    }
+
+    pub fn content(&self, id: NodeId) -> Option<&dyn Drawable> {
+        self.node(id).content.as_deref()
+    }
+
+    pub fn content_mut(&mut self, id: NodeId) -> Option<&mut (dyn Drawable + 'static)> {
+        self.node_mut(id).content.as_deref_mut()
+    }
+
+    /// cached world matrix, up to date after [`update_world_matrices`](Self::update_world_matrices)
+    pub fn world_matrix(&self, id: NodeId) -> Mat4 {
+        self.node(id).world
+    }
+
+    /// recompute the world matrices of every dirty node and its descendants
+    pub fn update_world_matrices(&mut self) {
+        let mut stack: Vec<(NodeId, Mat4, bool)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&r| (r, Mat4::one(), false))
+            .collect();
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let node = self.node_mut(id);
+            let changed = parent_changed || node.dirty;
+            if changed {
+                node.world = parent_world * node.local.matrix();
+                node.dirty = false;
+            }
+            let world = node.world;
+            for &child in node.children.iter().rev() {
+                stack.push((child, world, changed));
+            }
+        }
+    }
+
+    /// run the update hook of every drawable, then refresh the world matrices
+    pub fn update(&mut self, state: &FrameState) {
+        for node in self.nodes.iter_mut() {
+            if let Some(content) = node.content.as_mut() {
+                content.update(state);
+            }
+        }
+        self.update_world_matrices();
+    }
+
+    /// let every drawable render its own maps, with the same model matrices as
+    /// [`draw_with_frame`](Self::draw_with_frame)
+    pub fn render_maps(&mut self) {
+        for node in self.nodes.iter_mut() {
+            if let Some(content) = node.content.as_mut() {
+                let model_mat = node.world * content.transform().matrix();
+                content.render_maps(model_mat);
+            }
+        }
+    }
+
+    /// draw every drawable depth first, parents before children
+    pub fn draw_with_frame(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+            if let Some(content) = node.content.as_ref() {
+                let model_mat = node.world * content.transform().matrix();
+                content.draw_with_model(target, camera_mat, model_mat, dramparams);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+    }
+
+    /// draw the transparent drawables into the order-independent transparency buffers, the
+    /// order does not matter there
+    pub fn draw_transparent(
+        &self,
+        target: &mut MultiOutputFrameBuffer,
+        camera_mat: Mat4,
+        dramparams: &DrawParameters,
+    ) {
+        for node in self.nodes.iter() {
+            if let Some(content) = node.content.as_ref() {
+                let model_mat = node.world * content.transform().matrix();
+                content.draw_transparent(target, camera_mat, model_mat, dramparams);
+            }
+        }
+    }
+
+    /// draw every drawable into the shadow map of `pass`, with the same model matrices as
+    /// [`draw_with_frame`](Self::draw_with_frame)
+    pub fn draw_shadows(&self, target: &mut SimpleFrameBuffer, pass: &ShadowPass) {
+        for node in self.nodes.iter() {
+            if let Some(content) = node.content.as_ref() {
+                content.draw_shadow(target, pass, node.world * content.transform().matrix());
+            }
+        }
+    }
+
+    /// world space sphere around the bounds of every drawable, with the same model matrices
+    /// as [`draw_shadows`](Self::draw_shadows). `None` while no drawable has bounds
+    pub fn bounds(&self) -> Option<(Vec3, f32)> {
+        self.nodes
+            .iter()
+            .filter_map(|node| {
+                let content = node.content.as_ref()?;
+                let (centre, radius) = content.bounds()?;
+                let model_mat = node.world * content.transform().matrix();
+                Some(transform_sphere(&model_mat, centre, radius))
+            })
+            .reduce(enclose)
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+}
+
+/// smallest sphere around both spheres
+fn enclose(a: (Vec3, f32), b: (Vec3, f32)) -> (Vec3, f32) {
+    let ((a_centre, a_radius), (b_centre, b_radius)) = (a, b);
+    let distance = glm::distance(a_centre, b_centre);
+    if distance + b_radius <= a_radius {
+        return a;
+    }
+    if distance + a_radius <= b_radius {
+        return b;
+    }
+    let radius = 0.5 * (distance + a_radius + b_radius);
+    let centre = a_centre + (b_centre - a_centre) * ((radius - a_radius) / distance);
+    (centre, radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::{Vec3, Vec4};
+
+    use super::*;
+
+    fn transform_point(m: &Mat4, p: Vec3) -> Vec3 {
+        let v = *m * Vec4::new(p.x, p.y, p.z, 1.0);
+        Vec3::new(v.x, v.y, v.z)
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(
+            (a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5 && (a.z - b.z).abs() < 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn child_inherits_parent_translation() {
+        let mut graph = SceneGraph::new();
+        let parent = graph.add_node(
+            None,
+            "parent",
+            Transform::from_translation(Vec3::new(1.0, 2.0, 3.0)),
+        );
+        let child = graph.add_node(
+            Some(parent),
+            "child",
+            Transform::from_translation(Vec3::new(0.0, 0.0, -1.0)),
+        );
+        graph.update_world_matrices();
+
+        let origin = transform_point(&graph.world_matrix(child), Vec3::new(0.0, 0.0, 0.0));
+        assert_close(origin, Vec3::new(1.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn rotation_orbits_child_around_parent() {
+        let mut graph = SceneGraph::new();
+        let rig = graph.add_node(
+            None,
+            "rig",
+            Transform::new().with_rotation(Vec3::new(0.0, 90.0, 0.0)),
+        );
+        let light = graph.add_node(
+            Some(rig),
+            "light",
+            Transform::from_translation(Vec3::new(2.0, 0.0, 0.0)),
+        );
+        graph.update_world_matrices();
+
+        // +90 degrees around y maps +x onto -z
+        let pos = transform_point(&graph.world_matrix(light), Vec3::new(0.0, 0.0, 0.0));
+        assert_close(pos, Vec3::new(0.0, 0.0, -2.0));
+    }
+
+    #[test]
+    fn parent_scale_applies_to_child_offset() {
+        let mut graph = SceneGraph::new();
+        let parent = graph.add_node(None, "parent", Transform::new().with_uniform_scale(2.0));
+        let child = graph.add_node(
+            Some(parent),
+            "child",
+            Transform::from_translation(Vec3::new(1.0, 0.0, 0.0)),
+        );
+        graph.update_world_matrices();
+
+        let p = transform_point(&graph.world_matrix(child), Vec3::new(0.0, 1.0, 0.0));
+        assert_close(p, Vec3::new(2.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn dirty_parent_propagates_to_grandchildren() {
+        let mut graph = SceneGraph::new();
+        let a = graph.add_node(None, "a", Transform::new());
+        let b = graph.add_node(
+            Some(a),
+            "b",
+            Transform::from_translation(Vec3::new(1.0, 0.0, 0.0)),
+        );
+        let c = graph.add_node(
+            Some(b),
+            "c",
+            Transform::from_translation(Vec3::new(0.0, 1.0, 0.0)),
+        );
+        graph.update_world_matrices();
+        assert_close(
+            transform_point(&graph.world_matrix(c), Vec3::new(0.0, 0.0, 0.0)),
+            Vec3::new(1.0, 1.0, 0.0),
+        );
+
+        graph.set_local_transform(a, Transform::from_translation(Vec3::new(0.0, 0.0, 5.0)));
+        graph.update_world_matrices();
+        assert_close(
+            transform_point(&graph.world_matrix(c), Vec3::new(0.0, 0.0, 0.0)),
+            Vec3::new(1.0, 1.0, 5.0),
+        );
+    }
+
+    #[test]
+    fn reparenting_updates_world_and_rejects_cycles() {
+        let mut graph = SceneGraph::new();
+        let a = graph.add_node(
+            None,
+            "a",
+            Transform::from_translation(Vec3::new(3.0, 0.0, 0.0)),
+        );
+        let b = graph.add_node(None, "b", Transform::new());
+        graph.update_world_matrices();
+
+        assert!(graph.set_parent(b, Some(a)));
+        assert!(!graph.set_parent(a, Some(b)));
+        graph.update_world_matrices();
+
+        assert_eq!(graph.children(a), &[b]);
+        assert_eq!(graph.find("b"), Some(b));
+        assert_close(
+            transform_point(&graph.world_matrix(b), Vec3::new(0.0, 0.0, 0.0)),
+            Vec3::new(3.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn enclosing_spheres_touch_both() {
+        let a = (Vec3::new(0.0, 0.0, 0.0), 1.0);
+        let b = (Vec3::new(4.0, 0.0, 0.0), 1.0);
+        let (centre, radius) = enclose(a, b);
+        assert_close(centre, Vec3::new(2.0, 0.0, 0.0));
+        assert!((radius - 3.0).abs() < 1e-5);
+        // a sphere inside the other adds nothing
+        let inner = (Vec3::new(0.5, 0.0, 0.0), 0.2);
+        assert_eq!(enclose(a, inner), a);
+        assert_eq!(enclose(inner, a), a);
+    }
+}
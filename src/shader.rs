@@ -0,0 +1,918 @@
+//! building programs from named shader sources.
+//!
+//! sources go through a small preprocessor before they reach the driver: `#include "name"`
+//! lines are replaced by the named source and `#define`s given to the builder are inserted
+//! after `#version`. every line of the expanded text remembers where it came from, so line
+//! numbers in driver logs are rewritten to `file:line` of the original sources.
+//!
+//! the builder also knows which uniforms the sources declare, which lets it compare them with
+//! the uniforms the Rust side binds: a declared but unbound uniform silently stays zero, a bound
+//! but undeclared one is usually a typo.
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt::{self, Display as FmtDisplay, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glium::{Display, Program, ProgramCreationError};
+use glium::program::{ProgramCreationInput, ShaderType};
+use glium::uniforms::Uniforms;
+
+/// shader text with the name used in diagnostics
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderSource {
+    pub name: String,
+    pub text: String,
+    /// directory includes are looked up in, for sources read from disk
+    pub dir: Option<PathBuf>,
+}
+
+impl ShaderSource {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            text: text.into(),
+            dir: None,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ShaderError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| ShaderError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        Ok(Self {
+            name: path.display().to_string(),
+            text,
+            dir: path.parent().map(Path::to_path_buf),
+        })
+    }
+
+    /// source compiled into the binary, named after its path next to `module_file`
+    pub fn embedded(module_file: &str, path: &str, text: &str) -> Self {
+        let name = Path::new(module_file)
+            .parent()
+            .map(|dir| dir.join(path).display().to_string())
+            .unwrap_or_else(|| path.to_string());
+        Self::new(name, text)
+    }
+}
+
+/// embed a shader next to the calling module, like `include_str!`, keeping its path as name
+#[macro_export]
+macro_rules! shader_source {
+    ($path:literal) => {
+        $crate::shader::ShaderSource::embedded(file!(), $path, include_str!($path))
+    };
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io {
+        path: PathBuf,
+        err: io::Error,
+    },
+    /// an `#include` that cannot be resolved or includes itself
+    Include {
+        file: String,
+        line: usize,
+        message: String,
+    },
+    /// the driver log with line numbers pointing into the original files
+    Compile {
+        stage: ShaderType,
+        log: String,
+    },
+    Link {
+        log: String,
+    },
+    /// the context cannot build this program at all, e.g. no geometry shader support
+    Unsupported(ProgramCreationError),
+    /// the bound uniforms do not match the declared ones
+    Uniforms(UniformReport),
+}
+
+impl FmtDisplay for ShaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, err } => {
+                write!(f, "failed to read shader {}: {}", path.display(), err)
+            }
+            ShaderError::Include {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+            ShaderError::Compile { stage, log } => {
+                write!(
+                    f,
+                    "failed to compile the {} shader:\n{}",
+                    stage_name(*stage),
+                    log
+                )
+            }
+            ShaderError::Link { log } => write!(f, "failed to link the program:\n{}", log),
+            ShaderError::Unsupported(err) => write!(f, "{}", err),
+            ShaderError::Uniforms(report) => write!(f, "{}", report),
+        }
+    }
+}
+
+impl Error for ShaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShaderError::Io { err, .. } => Some(err),
+            ShaderError::Unsupported(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn stage_name(stage: ShaderType) -> &'static str {
+    match stage {
+        ShaderType::Vertex => "vertex",
+        ShaderType::Geometry => "geometry",
+        ShaderType::Fragment => "fragment",
+        ShaderType::TesselationControl => "tessellation control",
+        ShaderType::TesselationEvaluation => "tessellation evaluation",
+        ShaderType::Compute => "compute",
+    }
+}
+
+/// uniforms that are declared and bound differently
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UniformReport {
+    /// declared in the sources but never bound, these keep their default of zero
+    pub unbound: Vec<String>,
+    /// bound but declared nowhere, most likely misnamed on one side
+    pub undeclared: Vec<String>,
+}
+
+impl UniformReport {
+    pub fn compare<'a>(
+        declared: impl IntoIterator<Item = &'a str>,
+        bound: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let declared: BTreeSet<&str> = declared.into_iter().collect();
+        // members of struct arrays are bound one by one, `lights[0].color` declares `lights`
+        let bound: BTreeSet<&str> = bound
+            .into_iter()
+            .map(|name| name.split(['[', '.']).next().unwrap_or(name))
+            .collect();
+        Self {
+            unbound: declared.difference(&bound).map(|s| s.to_string()).collect(),
+            undeclared: bound.difference(&declared).map(|s| s.to_string()).collect(),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.unbound.is_empty() && self.undeclared.is_empty()
+    }
+}
+
+impl FmtDisplay for UniformReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.unbound.is_empty() {
+            parts.push(format!(
+                "declared but unbound uniforms: {}",
+                self.unbound.join(", ")
+            ));
+        }
+        if !self.undeclared.is_empty() {
+            parts.push(format!(
+                "bound but undeclared uniforms: {}",
+                self.undeclared.join(", ")
+            ));
+        }
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// expanded shader text and the origin of each of its lines
+#[derive(Clone, Debug, Default)]
+pub struct Preprocessed {
+    pub text: String,
+    files: Vec<String>,
+    /// (index into `files`, 1-based line) for every output line
+    origins: Vec<(usize, usize)>,
+}
+
+impl Preprocessed {
+    /// file and line an output line (1-based) came from
+    pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+        let &(file, line) = self.origins.get(line.checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+
+    /// rewrite the `0:LINE` and `0(LINE)` locations drivers put in their logs
+    pub fn remap_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.remap_log_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn remap_log_line(&self, line: &str) -> String {
+        let bytes = line.as_bytes();
+        for start in 0..bytes.len() {
+            let at_boundary = start == 0 || !bytes[start - 1].is_ascii_alphanumeric();
+            if bytes[start] != b'0' || !at_boundary || start + 1 >= bytes.len() {
+                continue;
+            }
+            let separator = bytes[start + 1];
+            if separator != b':' && separator != b'(' {
+                continue;
+            }
+            let digits_start = start + 2;
+            let digits_end = bytes[digits_start..]
+                .iter()
+                .position(|b| !b.is_ascii_digit())
+                .map_or(bytes.len(), |p| digits_start + p);
+            if digits_end == digits_start {
+                continue;
+            }
+            // `0(12)` has to be closed, `0:12` is complete as it is
+            let end = if separator == b'(' {
+                if bytes.get(digits_end) != Some(&b')') {
+                    continue;
+                }
+                digits_end + 1
+            } else {
+                digits_end
+            };
+            let Some((file, original)) = line[digits_start..digits_end]
+                .parse()
+                .ok()
+                .and_then(|n| self.origin(n))
+            else {
+                continue;
+            };
+            return format!("{}{}:{}{}", &line[..start], file, original, &line[end..]);
+        }
+        line.to_string()
+    }
+
+    /// names of all uniforms declared outside of uniform blocks, leaving out the ones in
+    /// `#if`, `#ifdef` and `#ifndef` branches that the defines in the text switch off
+    pub fn declared_uniforms(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut conditions = Conditions::default();
+        for line in self.text.lines() {
+            let mut line = line.trim();
+            if let Some(directive) = line.strip_prefix('#') {
+                conditions.directive(directive);
+                continue;
+            }
+            if !conditions.active() {
+                continue;
+            }
+            if line.starts_with("layout") {
+                match line.find(')') {
+                    Some(end) => line = line[end + 1..].trim_start(),
+                    None => continue,
+                }
+            }
+            let Some(rest) = line.strip_prefix("uniform ") else {
+                continue;
+            };
+            if rest.contains('{') || !rest.contains(';') {
+                continue;
+            }
+            let declaration = &rest[..rest.find(';').unwrap()];
+            // `uniform [precision] type a, b[4]`
+            let mut parts = declaration.splitn(2, |c: char| c.is_whitespace());
+            let mut ty = parts.next().unwrap_or("");
+            let mut vars = parts.next().unwrap_or("").trim();
+            if matches!(ty, "lowp" | "mediump" | "highp") {
+                let mut parts = vars.splitn(2, |c: char| c.is_whitespace());
+                ty = parts.next().unwrap_or("");
+                vars = parts.next().unwrap_or("").trim();
+            }
+            if ty.is_empty() {
+                continue;
+            }
+            for var in vars.split(',') {
+                let name = var.split(['[', '=']).next().unwrap_or("").trim();
+                if !name.is_empty() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names
+    }
+}
+
+/// the defines and open conditionals met while scanning expanded text
+#[derive(Debug, Default)]
+struct Conditions {
+    defines: HashMap<String, String>,
+    branches: Vec<Branch>,
+}
+
+#[derive(Debug)]
+struct Branch {
+    /// the text around the conditional is active
+    outer: bool,
+    /// one of the branches so far was active
+    taken: bool,
+    active: bool,
+}
+
+impl Conditions {
+    fn active(&self) -> bool {
+        self.branches.last().is_none_or(|branch| branch.active)
+    }
+
+    /// apply a preprocessor line without its leading `#`
+    fn directive(&mut self, directive: &str) {
+        let directive = directive.split("//").next().unwrap_or("").trim();
+        let (keyword, rest) = directive
+            .split_once(char::is_whitespace)
+            .unwrap_or((directive, ""));
+        let rest = rest.trim();
+        match keyword {
+            "define" if self.active() => {
+                let (name, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                // function-like macros only count for `defined`
+                let name = name.split('(').next().unwrap_or(name);
+                self.defines
+                    .insert(name.to_string(), value.trim().to_string());
+            }
+            "undef" if self.active() => {
+                self.defines.remove(rest);
+            }
+            "ifdef" => self.open(self.defines.contains_key(rest)),
+            "ifndef" => self.open(!self.defines.contains_key(rest)),
+            "if" => self.open(self.evaluate(rest)),
+            "elif" => self.switch(self.evaluate(rest)),
+            "else" => self.switch(true),
+            "endif" => {
+                self.branches.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn open(&mut self, condition: bool) {
+        let outer = self.active();
+        self.branches.push(Branch {
+            outer,
+            taken: outer && condition,
+            active: outer && condition,
+        });
+    }
+
+    fn switch(&mut self, condition: bool) {
+        if let Some(branch) = self.branches.last_mut() {
+            branch.active = branch.outer && !branch.taken && condition;
+            branch.taken |= branch.active;
+        }
+    }
+
+    /// an `#if` condition. expressions beyond integers, defines, `defined`, `!`, `&&`, `||`
+    /// and comparisons count as true, so their uniforms are rather reported than missed
+    fn evaluate(&self, expression: &str) -> bool {
+        Expression::new(expression, &self.defines, 0)
+            .value()
+            .is_none_or(|value| value != 0)
+    }
+}
+
+/// recursive descent over the tokens of an `#if` expression
+struct Expression<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+    defines: &'a HashMap<String, String>,
+    /// how many macros deep this expression is expanded
+    depth: usize,
+}
+
+impl<'a> Expression<'a> {
+    fn new(text: &'a str, defines: &'a HashMap<String, String>, depth: usize) -> Self {
+        let mut tokens = Vec::new();
+        let mut rest = text.trim_start();
+        while let Some(c) = rest.chars().next() {
+            let len = if c.is_ascii_alphanumeric() || c == '_' {
+                rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len())
+            } else if ["&&", "||", "==", "!=", "<=", ">="]
+                .iter()
+                .any(|op| rest.starts_with(op))
+            {
+                2
+            } else {
+                c.len_utf8()
+            };
+            tokens.push(&rest[..len]);
+            rest = rest[len..].trim_start();
+        }
+        Self {
+            tokens,
+            position: 0,
+            defines,
+            depth,
+        }
+    }
+
+    /// the value of the whole expression, `None` if it cannot be evaluated
+    fn value(&mut self) -> Option<i64> {
+        let value = self.or()?;
+        (self.position == self.tokens.len()).then_some(value)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek()?;
+        self.position += 1;
+        Some(token)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Option<i64> {
+        let mut value = self.and()?;
+        while self.eat("||") {
+            let rhs = self.and()?;
+            value = (value != 0 || rhs != 0) as i64;
+        }
+        Some(value)
+    }
+
+    fn and(&mut self) -> Option<i64> {
+        let mut value = self.comparison()?;
+        while self.eat("&&") {
+            let rhs = self.comparison()?;
+            value = (value != 0 && rhs != 0) as i64;
+        }
+        Some(value)
+    }
+
+    fn comparison(&mut self) -> Option<i64> {
+        let value = self.unary()?;
+        let Some(op @ ("==" | "!=" | "<" | ">" | "<=" | ">=")) = self.peek() else {
+            return Some(value);
+        };
+        self.position += 1;
+        let rhs = self.unary()?;
+        let result = match op {
+            "==" => value == rhs,
+            "!=" => value != rhs,
+            "<" => value < rhs,
+            ">" => value > rhs,
+            "<=" => value <= rhs,
+            _ => value >= rhs,
+        };
+        Some(result as i64)
+    }
+
+    fn unary(&mut self) -> Option<i64> {
+        if self.eat("!") {
+            return Some((self.unary()? == 0) as i64);
+        }
+        if self.eat("(") {
+            let value = self.or()?;
+            return self.eat(")").then_some(value);
+        }
+        let token = self.next()?;
+        if token == "defined" {
+            let parenthesized = self.eat("(");
+            let name = self.next()?;
+            if parenthesized && !self.eat(")") {
+                return None;
+            }
+            return Some(self.defines.contains_key(name) as i64);
+        }
+        if let Ok(number) = token.parse() {
+            return Some(number);
+        }
+        if !token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            return None;
+        }
+        // like in C, names that are not defined are 0
+        match self.defines.get(token) {
+            None => Some(0),
+            Some(value) if self.depth < 16 => {
+                Expression::new(value, self.defines, self.depth + 1).value()
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+/// collects the sources of a program and builds it
+#[derive(Clone, Debug)]
+pub struct ProgramBuilder {
+    vertex: ShaderSource,
+    fragment: ShaderSource,
+    geometry: Option<ShaderSource>,
+    includes: HashMap<String, ShaderSource>,
+    extensions: Vec<String>,
+    defines: Vec<(String, String)>,
+    srgb_output: bool,
+}
+
+impl ProgramBuilder {
+    pub fn new(vertex: ShaderSource, fragment: ShaderSource) -> Self {
+        Self {
+            vertex,
+            fragment,
+            geometry: None,
+            includes: HashMap::new(),
+            extensions: Vec::new(),
+            defines: Vec::new(),
+            srgb_output: false,
+        }
+    }
+
+    pub fn geometry(mut self, geometry: ShaderSource) -> Self {
+        self.geometry = Some(geometry);
+        self
+    }
+
+    /// make `source` available to `#include "key"`. sources read from disk also find the files
+    /// next to them
+    pub fn include(mut self, key: impl Into<String>, source: ShaderSource) -> Self {
+        self.includes.insert(key.into(), source);
+        self
+    }
+
+    /// `#extension name : enable` in every stage, right after `#version`. drivers without it
+    /// only warn, the shaders check for it with `#ifdef name`
+    pub fn extension(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        if !self.extensions.contains(&name) {
+            self.extensions.push(name);
+        }
+        self
+    }
+
+    /// `#define name value` in every stage, right after `#version`
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.push((name.into(), value.into()));
+        self
+    }
+
+    /// the fragment shader encodes sRGB itself, so the framebuffer must not encode it again
+    pub fn srgb_output(mut self) -> Self {
+        self.srgb_output = true;
+        self
+    }
+
+    pub fn preprocess(&self, stage: ShaderType) -> Result<Preprocessed, ShaderError> {
+        let source = match stage {
+            ShaderType::Vertex => &self.vertex,
+            ShaderType::Fragment => &self.fragment,
+            ShaderType::Geometry => self.geometry.as_ref().unwrap_or(&self.vertex),
+            _ => &self.vertex,
+        };
+        let mut out = Preprocessed::default();
+        let mut stack = Vec::new();
+        self.expand(source, &mut out, &mut stack, true)?;
+        Ok(out)
+    }
+
+    fn expand(
+        &self,
+        source: &ShaderSource,
+        out: &mut Preprocessed,
+        stack: &mut Vec<String>,
+        root: bool,
+    ) -> Result<(), ShaderError> {
+        let file = out.files.len();
+        out.files.push(source.name.clone());
+        stack.push(source.name.clone());
+
+        let mut defines_pending = root;
+        for (index, line) in source.text.lines().enumerate() {
+            let number = index + 1;
+            let trimmed = line.trim_start();
+            if let Some(rest) = trimmed.strip_prefix("#include") {
+                let included = self.resolve_include(source, rest.trim(), number)?;
+                if stack.contains(&included.name) {
+                    return Err(ShaderError::Include {
+                        file: source.name.clone(),
+                        line: number,
+                        message: format!("`{}` includes itself", included.name),
+                    });
+                }
+                self.expand(&included, out, stack, false)?;
+                continue;
+            }
+            push_line(out, line, file, number);
+            if defines_pending && trimmed.starts_with("#version") {
+                self.push_defines(out, file, number);
+                defines_pending = false;
+            }
+        }
+        if defines_pending && !(self.defines.is_empty() && self.extensions.is_empty()) {
+            // no `#version`, the defines go in front
+            let mut with_defines = Preprocessed {
+                files: out.files.clone(),
+                ..Preprocessed::default()
+            };
+            self.push_defines(&mut with_defines, file, 1);
+            with_defines.text.push_str(&out.text);
+            with_defines.origins.extend_from_slice(&out.origins);
+            *out = with_defines;
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    fn push_defines(&self, out: &mut Preprocessed, file: usize, line: usize) {
+        for name in &self.extensions {
+            push_line(out, &format!("#extension {} : enable", name), file, line);
+        }
+        for (name, value) in &self.defines {
+            push_line(out, &format!("#define {} {}", name, value), file, line);
+        }
+    }
+
+    fn resolve_include(
+        &self,
+        includer: &ShaderSource,
+        argument: &str,
+        line: usize,
+    ) -> Result<ShaderSource, ShaderError> {
+        let error = |message: String| ShaderError::Include {
+            file: includer.name.clone(),
+            line,
+            message,
+        };
+        let key = argument
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .ok_or_else(|| error(format!("expected `#include \"name\"`, got `{}`", argument)))?;
+        if let Some(source) = self.includes.get(key) {
+            return Ok(source.clone());
+        }
+        match &includer.dir {
+            Some(dir) => ShaderSource::from_file(dir.join(key))
+                .map_err(|err| error(format!("cannot include `{}`: {}", key, err))),
+            None => Err(error(format!("unknown include `{}`", key))),
+        }
+    }
+
+    fn stages(&self) -> Vec<ShaderType> {
+        let mut stages = vec![ShaderType::Vertex, ShaderType::Fragment];
+        if self.geometry.is_some() {
+            stages.push(ShaderType::Geometry);
+        }
+        stages
+    }
+
+    pub fn build(&self, display: &Display) -> Result<Program, ShaderError> {
+        let vertex = self.preprocess(ShaderType::Vertex)?;
+        let fragment = self.preprocess(ShaderType::Fragment)?;
+        let geometry = self
+            .geometry
+            .as_ref()
+            .map(|_| self.preprocess(ShaderType::Geometry))
+            .transpose()?;
+
+        Program::new(
+            display,
+            ProgramCreationInput::SourceCode {
+                vertex_shader: &vertex.text,
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: geometry.as_ref().map(|g| g.text.as_str()),
+                fragment_shader: &fragment.text,
+                transform_feedback_varyings: None,
+                outputs_srgb: self.srgb_output,
+                uses_point_size: false,
+            },
+        )
+        .map_err(|err| match err {
+            ProgramCreationError::CompilationError(log, stage) => {
+                let expanded = match stage {
+                    ShaderType::Vertex => Some(&vertex),
+                    ShaderType::Fragment => Some(&fragment),
+                    ShaderType::Geometry => geometry.as_ref(),
+                    _ => None,
+                };
+                ShaderError::Compile {
+                    stage,
+                    log: expanded.map_or(log.clone(), |e| e.remap_log(&log)),
+                }
+            }
+            ProgramCreationError::LinkingError(log) => ShaderError::Link { log },
+            other => ShaderError::Unsupported(other),
+        })
+    }
+
+    /// compare the uniforms declared by any stage with the ones `uniforms` binds
+    pub fn uniform_report<U: Uniforms>(&self, uniforms: &U) -> Result<UniformReport, ShaderError> {
+        let mut declared = BTreeSet::new();
+        for stage in self.stages() {
+            declared.extend(self.preprocess(stage)?.declared_uniforms());
+        }
+        let mut bound = Vec::new();
+        uniforms.visit_values(|name, _| bound.push(name.to_string()));
+        Ok(UniformReport::compare(
+            declared.iter().map(String::as_str),
+            bound.iter().map(String::as_str),
+        ))
+    }
+
+    /// like [`uniform_report`](Self::uniform_report), but any mismatch is an error
+    pub fn check_uniforms<U: Uniforms>(&self, uniforms: &U) -> Result<(), ShaderError> {
+        let report = self.uniform_report(uniforms)?;
+        if report.is_ok() {
+            Ok(())
+        } else {
+            Err(ShaderError::Uniforms(report))
+        }
+    }
+}
+
+fn push_line(out: &mut Preprocessed, line: &str, file: usize, number: usize) {
+    out.text.push_str(line);
+    out.text.push('\n');
+    out.origins.push((file, number));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glium::uniform;
+
+    fn program(vertex: &str, fragment: &str) -> ProgramBuilder {
+        ProgramBuilder::new(
+            ShaderSource::new("main.vert", vertex),
+            ShaderSource::new("main.frag", fragment),
+        )
+    }
+
+    fn uniforms(text: &str) -> Vec<String> {
+        let builder = program(text, "");
+        builder
+            .preprocess(ShaderType::Vertex)
+            .unwrap()
+            .declared_uniforms()
+    }
+
+    #[test]
+    fn log_lines_point_into_nested_includes() {
+        let builder = program("#version 330\n#include \"outer\"\nvoid main() {}", "")
+            .include(
+                "outer",
+                ShaderSource::new("outer.glsl", "// a\n#include \"inner\"\n// b"),
+            )
+            .include("inner", ShaderSource::new("inner.glsl", "// c\nbroken"));
+        let vertex = builder.preprocess(ShaderType::Vertex).unwrap();
+        assert_eq!(vertex.origin(1), Some(("main.vert", 1)));
+        assert_eq!(vertex.origin(2), Some(("outer.glsl", 1)));
+        assert_eq!(vertex.origin(4), Some(("inner.glsl", 2)));
+        assert_eq!(vertex.origin(5), Some(("outer.glsl", 3)));
+        assert_eq!(vertex.origin(6), Some(("main.vert", 3)));
+        assert_eq!(vertex.origin(7), None);
+
+        let log = "0:4(1): error: syntax error\nERROR: 0:6: oops\n0(5) : warning\nno location";
+        assert_eq!(
+            vertex.remap_log(log),
+            "inner.glsl:2(1): error: syntax error\nERROR: main.vert:3: oops\n\
+             outer.glsl:3 : warning\nno location"
+        );
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let builder = program("#include \"a\"", "")
+            .include("a", ShaderSource::new("a.glsl", "#include \"b\""))
+            .include("b", ShaderSource::new("b.glsl", "// b\n#include \"a\""));
+        match builder.preprocess(ShaderType::Vertex) {
+            Err(ShaderError::Include { file, line, .. }) => {
+                assert_eq!((file.as_str(), line), ("b.glsl", 2));
+            }
+            other => panic!("expected an include error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_includes_are_errors() {
+        let builder = program("// x\n#include \"nowhere\"", "");
+        match builder.preprocess(ShaderType::Vertex) {
+            Err(ShaderError::Include {
+                file,
+                line,
+                message,
+            }) => {
+                assert_eq!((file.as_str(), line), ("main.vert", 2));
+                assert!(message.contains("nowhere"), "{}", message);
+            }
+            other => panic!("expected an include error, got {:?}", other),
+        }
+        let unquoted = program("#include nowhere", "");
+        assert!(matches!(
+            unquoted.preprocess(ShaderType::Vertex),
+            Err(ShaderError::Include { .. })
+        ));
+    }
+
+    #[test]
+    fn defines_follow_the_version() {
+        let builder = program("#version 330\nvoid main() {}", "void main() {}")
+            .define("A", "1")
+            .define("B", "two");
+        let vertex = builder.preprocess(ShaderType::Vertex).unwrap();
+        assert_eq!(
+            vertex.text,
+            "#version 330\n#define A 1\n#define B two\nvoid main() {}\n"
+        );
+        // the defines take the line of `#version`, so later lines keep their numbers
+        assert_eq!(vertex.origin(3), Some(("main.vert", 1)));
+        assert_eq!(vertex.origin(4), Some(("main.vert", 2)));
+
+        let fragment = builder.preprocess(ShaderType::Fragment).unwrap();
+        assert_eq!(
+            fragment.text,
+            "#define A 1\n#define B two\nvoid main() {}\n"
+        );
+        assert_eq!(fragment.origin(3), Some(("main.frag", 1)));
+    }
+
+    #[test]
+    fn extensions_come_before_the_defines() {
+        let builder = program("#version 330\nvoid main() {}", "")
+            .define("A", "1")
+            .extension("GL_ARB_sample_shading")
+            .extension("GL_ARB_sample_shading");
+        let vertex = builder.preprocess(ShaderType::Vertex).unwrap();
+        assert_eq!(
+            vertex.text,
+            "#version 330\n#extension GL_ARB_sample_shading : enable\n#define A 1\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn declarations_are_parsed() {
+        let text = "uniform mat4 model;\n\
+                    layout(location = 0) uniform highp vec3 a, b[4];\n\
+                    uniform Block { float inside; };\n\
+                    in vec3 position;";
+        assert_eq!(uniforms(text), ["model", "a", "b"]);
+    }
+
+    #[test]
+    fn inactive_branches_declare_nothing() {
+        let text = "#define LIGHTS 2\n\
+                    #ifdef SHADOWS\nuniform sampler2D shadow_map;\n#else\nuniform float ambient;\n#endif\n\
+                    #ifndef SHADOWS\nuniform float fog;\n#endif\n\
+                    #if LIGHTS > 1 && !defined(SHADOWS)\nuniform vec3 fill;\n\
+                    #elif LIGHTS > 0\nuniform vec3 key;\n#else\nuniform vec3 none;\n#endif\n\
+                    #if 0\n#ifdef LIGHTS\nuniform float nested;\n#else\nuniform float nested_else;\n#endif\n#endif\n\
+                    uniform float after;";
+        assert_eq!(uniforms(text), ["ambient", "fog", "fill", "after"]);
+
+        let defined = program(text, "").define("SHADOWS", "");
+        let declared = defined
+            .preprocess(ShaderType::Vertex)
+            .unwrap()
+            .declared_uniforms();
+        assert_eq!(declared, ["shadow_map", "key", "after"]);
+    }
+
+    #[test]
+    fn unknown_conditions_keep_their_uniforms() {
+        let text = "#if __VERSION__ >= 400 + 10\nuniform float maybe;\n#endif";
+        assert_eq!(uniforms(text), ["maybe"]);
+    }
+
+    #[test]
+    fn reports_unbound_and_undeclared_uniforms() {
+        let builder = program(
+            "uniform mat4 model;\nuniform float unused;",
+            "struct Light { vec3 color; };\nuniform Light lights[2];",
+        );
+        let report = builder
+            .uniform_report(&uniform! {
+                model: [[0.0f32; 4]; 4],
+                misnamed: 1.0f32,
+            })
+            .unwrap();
+        assert_eq!(report.unbound, ["lights", "unused"]);
+        assert_eq!(report.undeclared, ["misnamed"]);
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.to_string(),
+            "declared but unbound uniforms: lights, unused; bound but undeclared uniforms: misnamed"
+        );
+
+        let members = UniformReport::compare(["lights"], ["lights[0].color", "lights[1].color"]);
+        assert!(members.is_ok());
+    }
+}
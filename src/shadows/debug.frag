@@ -0,0 +1,36 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+uniform sampler2D depth_map;
+uniform samplerCube depth_cube;
+// cubemap face in the order +x, -x, +y, -y, +z, -z, -1 shows depth_map
+uniform int face;
+
+void main() {
+    float depth;
+    if (face < 0) {
+        depth = texture(depth_map, TexCoord).r;
+    } else {
+        // the direction through this texel of the face, as the cubemap lookup lays them out
+        vec2 st = TexCoord * 2.0 - 1.0;
+        vec3 dir;
+        if (face == 0) {
+            dir = vec3(1.0, -st.y, -st.x);
+        } else if (face == 1) {
+            dir = vec3(-1.0, -st.y, st.x);
+        } else if (face == 2) {
+            dir = vec3(st.x, 1.0, st.y);
+        } else if (face == 3) {
+            dir = vec3(st.x, -1.0, -st.y);
+        } else if (face == 4) {
+            dir = vec3(st.x, -st.y, 1.0);
+        } else {
+            dir = vec3(-st.x, -st.y, -1.0);
+        }
+        depth = texture(depth_cube, dir).r;
+    }
+    FragColor = vec4(vec3(depth), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,96 @@
+//! debug view of the shadow maps, drawn as tiles over the top left corner of the frame: one
+//! row with the maps of the directional lights, then one row of six faces per cubemap
+
+use glium::{
+    Display, DrawParameters, Frame, implement_vertex, Program, Rect, Surface, uniform, VertexBuffer,
+};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler};
+
+use crate::shader::ProgramBuilder;
+use crate::shader_source;
+use crate::shadows::ShadowMaps;
+
+/// width and height of a tile in pixels
+const TILE_SIZE: u32 = 128;
+/// gap between the tiles and around them
+const TILE_GAP: u32 = 4;
+
+#[derive(Clone, Copy)]
+struct Vertex {
+    position: [f32; 2],
+}
+implement_vertex!(Vertex, position location(0));
+
+pub struct ShadowDebugView {
+    program: Program,
+    quad: VertexBuffer<Vertex>,
+}
+
+impl ShadowDebugView {
+    pub fn new(display: &Display) -> Self {
+        let program =
+            ProgramBuilder::new(shader_source!("debug.vert"), shader_source!("debug.frag"))
+                .build(display)
+                .unwrap_or_else(|err| panic!("shadow debug view: {}", err));
+        let corners = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]];
+        let quad =
+            VertexBuffer::new(display, &corners.map(|position| Vertex { position })).unwrap();
+        Self { program, quad }
+    }
+
+    /// draw the maps the lights rendered into this frame, white is far away
+    pub fn draw(&self, frame: &mut Frame, maps: &ShadowMaps) {
+        let layout = maps.layout();
+        let (_, height) = frame.get_dimensions();
+        // both samplers always need a texture of their type, `face` picks the one shown
+        let (Some(first_map), Some(first_cube)) = (maps.directional_map(0), maps.point_map(0))
+        else {
+            return;
+        };
+
+        // (row, column, 2D map, cubemap, face)
+        let mut tiles = Vec::new();
+        for slot in 0..layout.directional().len() {
+            let map = maps.directional_map(slot).unwrap_or(first_map);
+            tiles.push((0, slot as u32, map, first_cube, -1));
+        }
+        let first_row = u32::from(!layout.directional().is_empty());
+        for slot in 0..layout.point().len() {
+            let cube = maps.point_map(slot).unwrap_or(first_cube);
+            for face in 0..6 {
+                tiles.push((first_row + slot as u32, face as u32, first_map, cube, face));
+            }
+        }
+
+        for (row, column, map, cube, face) in tiles {
+            let uniforms = uniform! {
+                depth_map: Sampler::new(map)
+                    .minify_filter(MinifySamplerFilter::Linear)
+                    .magnify_filter(MagnifySamplerFilter::Linear),
+                depth_cube: Sampler::new(cube)
+                    .minify_filter(MinifySamplerFilter::Linear)
+                    .magnify_filter(MagnifySamplerFilter::Linear),
+                face: face,
+            };
+            let params = DrawParameters {
+                viewport: Some(Rect {
+                    left: TILE_GAP + column * (TILE_SIZE + TILE_GAP),
+                    bottom: height.saturating_sub((row + 1) * (TILE_SIZE + TILE_GAP)),
+                    width: TILE_SIZE,
+                    height: TILE_SIZE,
+                }),
+                ..Default::default()
+            };
+            frame
+                .draw(
+                    &self.quad,
+                    NoIndices(PrimitiveType::TriangleStrip),
+                    &self.program,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,57 @@
+// deep opacity maps of the strands, filled in by DeepOpacityMaps in
+// src/shadows/deep_opacity.rs. every light with a map has the nearest strand depth seen from
+// the light in one layer of deep_opacity_depth, and the opacity accumulated up to
+// deep_opacity_spacing, 2 * deep_opacity_spacing, ... behind it in the layers of
+// deep_opacity_layers, four boundaries per texel
+
+// slots, kept in sync with src/shadows/deep_opacity.rs
+#define DEEP_OPACITY_SLOTS 4
+
+uniform sampler2DArray deep_opacity_depth;
+uniform sampler2DArray deep_opacity_layers;
+// index of the light every slot belongs to, -1 for none
+uniform int deep_opacity_lights[DEEP_OPACITY_SLOTS];
+// world to the light's view and its view to clip space, the maps store the view depth
+uniform mat4 deep_opacity_views[DEEP_OPACITY_SLOTS];
+uniform mat4 deep_opacity_projections[DEEP_OPACITY_SLOTS];
+// boundaries per slot, a multiple of four, 0 while the maps are disabled
+uniform int deep_opacity_layer_count;
+// view depth between two boundaries
+uniform float deep_opacity_spacing;
+
+// opacity accumulated in front of boundary, boundary 0 is the nearest strand itself
+float deep_opacity_at(int slot, vec2 coord, int boundary) {
+    if (boundary <= 0) {
+        return 0.0;
+    }
+    int layer = slot * (deep_opacity_layer_count / 4) + (boundary - 1) / 4;
+    vec4 opacity = texture(deep_opacity_layers, vec3(coord, float(layer)));
+    return opacity[(boundary - 1) % 4];
+}
+
+// fraction of the light of lights[light] that reaches pos through the strands in front of it,
+// 1 for lights without a map
+float hair_transmittance(int light, vec3 pos) {
+    if (deep_opacity_layer_count <= 0) {
+        return 1.0;
+    }
+    for (int slot = 0; slot < DEEP_OPACITY_SLOTS; ++slot) {
+        if (deep_opacity_lights[slot] != light) {
+            continue;
+        }
+        vec4 view_pos = deep_opacity_views[slot] * vec4(pos, 1.0);
+        vec4 clip = deep_opacity_projections[slot] * view_pos;
+        vec2 coord = clip.xy / clip.w * 0.5 + 0.5;
+        if (any(lessThan(coord, vec2(0.0))) || any(greaterThan(coord, vec2(1.0)))) {
+            return 1.0;
+        }
+        float nearest = texture(deep_opacity_depth, vec3(coord, float(slot))).r;
+        float t = clamp((-view_pos.z - nearest) / deep_opacity_spacing, 0.0,
+                        float(deep_opacity_layer_count));
+        int below = min(int(t), deep_opacity_layer_count - 1);
+        float opacity = mix(deep_opacity_at(slot, coord, below),
+                            deep_opacity_at(slot, coord, below + 1), t - float(below));
+        return exp(-opacity);
+    }
+    return 1.0;
+}
\ No newline at end of file
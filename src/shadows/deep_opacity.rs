@@ -0,0 +1,539 @@
+//! deep opacity maps, the self-shadowing of hair. a shadow map only knows whether anything is
+//! in front of a point, but light passes through a few strands and fades gradually deeper in
+//! the hair. every light gets two passes over the strands from its view: the first keeps the
+//! depth of the nearest strand, the second adds up the opacity of the strands in layers of
+//! `spacing` behind it. the strand shader reads both through `deep_opacity.glsl` and dims
+//! diffuse and specular light by the opacity in front of a fragment.
+//!
+//! the maps are fitted to the strands' bounding sphere, so they belong to one drawable rather
+//! than being shared like [`ShadowMaps`](crate::shadows::ShadowMaps).
+
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt::{self, Display as FmtDisplay, Formatter};
+
+use glium::{
+    Blend, BlendingFunction, Display, DrawParameters, LinearBlendingFactor, Program, Surface,
+    uniform,
+};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::IndicesSource;
+use glium::texture::{MipmapsOption, Texture2dArray, TextureCreationError, UncompressedFloatFormat};
+use glium::uniforms::{
+    MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior, SamplerWrapFunction,
+    Uniforms, UniformValue,
+};
+use glium::vertex::MultiVerticesSource;
+use glm::{Mat4, Vec3, Vec4};
+use num_traits::One;
+
+use crate::lights::{LightKind, LightRig};
+use crate::shader::{ProgramBuilder, ShaderError};
+use crate::shader_source;
+use crate::shadows::{ortho, up_vector};
+use crate::transform::mat4_uniform;
+
+/// lights with a map, kept in sync with `deep_opacity.glsl`
+pub const DEEP_OPACITY_SLOTS: usize = 4;
+
+/// most layers behind the nearest strand. GL 3.3 only promises 256 array texture layers, every
+/// slot takes a quarter of them and packs four layers into each
+pub const OPACITY_LAYER_LIMIT: u32 = 256;
+
+/// what the depth layers are cleared to, further away than any strand
+const CLEAR_DEPTH: f32 = 1.0e30;
+
+/// make `#include "deep_opacity.glsl"` available
+pub fn include_deep_opacity(builder: ProgramBuilder) -> ProgramBuilder {
+    builder.include("deep_opacity.glsl", shader_source!("deep_opacity.glsl"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeepOpacitySettings {
+    /// render deep opacity maps at all
+    pub enabled: bool,
+    /// layers behind the nearest strand, rounded up to a multiple of four and clamped to
+    /// [`OPACITY_LAYER_LIMIT`]
+    pub layers: u32,
+    /// depth of a layer in world units
+    pub spacing: f32,
+    /// opacity a single strand adds, light through `n` strands is dimmed by `exp(-n * opacity)`
+    pub strand_opacity: f32,
+    /// width and height of every map
+    pub map_size: u32,
+}
+
+impl Default for DeepOpacitySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            layers: 8,
+            spacing: 0.02,
+            strand_opacity: 0.2,
+            map_size: 256,
+        }
+    }
+}
+
+impl DeepOpacitySettings {
+    /// array layers of one light's opacity, four boundaries each
+    fn texture_layers(&self) -> u32 {
+        self.layers.clamp(1, OPACITY_LAYER_LIMIT).div_ceil(4)
+    }
+}
+
+/// the view a light renders its map from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeepOpacityView {
+    /// index of the light in the rig
+    pub light: usize,
+    /// world to the light's view space
+    pub view: Mat4,
+    pub projection: Mat4,
+}
+
+impl DeepOpacityView {
+    /// views of the first lights of `rig` that cast shadows, fitted to the sphere at `centre`.
+    /// point and spot lights inside the sphere get none
+    pub fn fit(rig: &LightRig, centre: Vec3, radius: f32) -> Vec<Self> {
+        let radius = radius.max(1e-3);
+        rig.lights()
+            .iter()
+            .enumerate()
+            .filter(|(_, light)| light.cast_shadows)
+            .filter_map(|(light, scene_light)| {
+                let (view, projection) = match scene_light.kind {
+                    LightKind::Directional { direction } => {
+                        let direction = glm::normalize(direction);
+                        let eye = centre - direction * (radius + 1.0);
+                        let view = glm::ext::look_at(eye, centre, up_vector(direction));
+                        (view, ortho(radius, 2.0 * radius + 2.0))
+                    }
+                    LightKind::Point { position } | LightKind::Spot { position, .. } => {
+                        let distance = glm::distance(position, centre);
+                        if distance <= radius * 1.01 {
+                            return None;
+                        }
+                        let direction = (centre - position) / distance;
+                        let view = glm::ext::look_at(position, centre, up_vector(direction));
+                        let fov = (2.0 * (radius / distance).asin()).min(PI * 0.9);
+                        let near = (distance - radius).max(1e-3);
+                        let projection = glm::ext::perspective(fov, 1.0, near, distance + radius);
+                        (view, projection)
+                    }
+                };
+                Some(Self {
+                    light,
+                    view,
+                    projection,
+                })
+            })
+            .take(DEEP_OPACITY_SLOTS)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum DeepOpacityError {
+    Texture(TextureCreationError),
+    Shader(ShaderError),
+}
+
+impl FmtDisplay for DeepOpacityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DeepOpacityError::Texture(err) => {
+                write!(f, "failed to create deep opacity maps: {}", err)
+            }
+            DeepOpacityError::Shader(err) => write!(f, "deep opacity maps: {}", err),
+        }
+    }
+}
+
+impl Error for DeepOpacityError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DeepOpacityError::Texture(err) => Some(err),
+            DeepOpacityError::Shader(err) => Some(err),
+        }
+    }
+}
+
+impl From<TextureCreationError> for DeepOpacityError {
+    fn from(err: TextureCreationError) -> Self {
+        DeepOpacityError::Texture(err)
+    }
+}
+
+impl From<ShaderError> for DeepOpacityError {
+    fn from(err: ShaderError) -> Self {
+        DeepOpacityError::Shader(err)
+    }
+}
+
+/// the nearest strand depth and the layered opacity of up to [`DEEP_OPACITY_SLOTS`] lights
+pub struct DeepOpacityMaps {
+    display: Display,
+    settings: DeepOpacitySettings,
+    /// one layer per slot
+    depth: Texture2dArray,
+    /// `texture_layers` per slot
+    opacity: Texture2dArray,
+    /// depth and opacity programs, `None` while the maps are disabled
+    programs: Option<(Program, Program)>,
+}
+
+impl DeepOpacityMaps {
+    pub fn new(display: &Display, settings: DeepOpacitySettings) -> Result<Self, DeepOpacityError> {
+        // disabled maps are never sampled, but the samplers still need textures
+        let size = if settings.enabled {
+            settings.map_size.max(1)
+        } else {
+            1
+        };
+        let slots = DEEP_OPACITY_SLOTS as u32;
+        let depth = Texture2dArray::empty_with_format(
+            display,
+            UncompressedFloatFormat::F32,
+            MipmapsOption::NoMipmap,
+            size,
+            size,
+            slots,
+        )?;
+        let opacity = Texture2dArray::empty_with_format(
+            display,
+            UncompressedFloatFormat::F16F16F16F16,
+            MipmapsOption::NoMipmap,
+            size,
+            size,
+            slots * settings.texture_layers(),
+        )?;
+        let programs = if settings.enabled {
+            let build = |fragment| {
+                ProgramBuilder::new(shader_source!("opacity.vert"), fragment).build(display)
+            };
+            Some((
+                build(shader_source!("opacity_depth.frag"))?,
+                build(shader_source!("opacity.frag"))?,
+            ))
+        } else {
+            None
+        };
+        Ok(Self {
+            display: display.clone(),
+            settings,
+            depth,
+            opacity,
+            programs,
+        })
+    }
+
+    /// maps that never render
+    pub fn disabled(display: &Display) -> Self {
+        let settings = DeepOpacitySettings {
+            enabled: false,
+            ..DeepOpacitySettings::default()
+        };
+        // builds no programs, only single texel textures in formats GL 3.3 requires
+        Self::new(display, settings).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn settings(&self) -> &DeepOpacitySettings {
+        &self.settings
+    }
+
+    /// render the maps of `views` from the strands, drawn as lines with the position at
+    /// location 0
+    pub fn render<'b, V, I>(
+        &self,
+        views: &[DeepOpacityView],
+        vertices: V,
+        indices: I,
+        model_mat: Mat4,
+    ) where
+        V: MultiVerticesSource<'b> + Copy,
+        I: Into<IndicesSource<'b>> + Copy,
+    {
+        let Some((depth_program, opacity_program)) = &self.programs else {
+            return;
+        };
+        let depth_params = DrawParameters {
+            blend: Blend {
+                color: BlendingFunction::Min,
+                alpha: BlendingFunction::Min,
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            ..Default::default()
+        };
+        let additive = BlendingFunction::Addition {
+            source: LinearBlendingFactor::One,
+            destination: LinearBlendingFactor::One,
+        };
+        let opacity_params = DrawParameters {
+            blend: Blend {
+                color: additive,
+                alpha: additive,
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            ..Default::default()
+        };
+        let nearest_depth = Sampler::new(&self.depth)
+            .minify_filter(MinifySamplerFilter::Nearest)
+            .magnify_filter(MagnifySamplerFilter::Nearest);
+        let texture_layers = self.settings.texture_layers();
+
+        for (slot, view) in views.iter().enumerate().take(DEEP_OPACITY_SLOTS) {
+            let layer = self.depth.main_level().layer(slot as u32).unwrap();
+            let mut target = SimpleFrameBuffer::new(&self.display, layer).unwrap();
+            target.clear_color(CLEAR_DEPTH, CLEAR_DEPTH, CLEAR_DEPTH, CLEAR_DEPTH);
+            let uniforms = uniform! {
+                model: mat4_uniform(&model_mat),
+                view: mat4_uniform(&view.view),
+                projection: mat4_uniform(&view.projection),
+            };
+            target
+                .draw(vertices, indices, depth_program, &uniforms, &depth_params)
+                .unwrap();
+
+            for layer_index in 0..texture_layers {
+                let layer = self
+                    .opacity
+                    .main_level()
+                    .layer(slot as u32 * texture_layers + layer_index)
+                    .unwrap();
+                let mut target = SimpleFrameBuffer::new(&self.display, layer).unwrap();
+                target.clear_color(0.0, 0.0, 0.0, 0.0);
+                let uniforms = uniform! {
+                    model: mat4_uniform(&model_mat),
+                    view: mat4_uniform(&view.view),
+                    projection: mat4_uniform(&view.projection),
+                    nearest_depth: nearest_depth,
+                    slot: slot as i32,
+                    first_boundary: (layer_index * 4 + 1) as i32,
+                    spacing: self.settings.spacing,
+                    strand_opacity: self.settings.strand_opacity,
+                };
+                target
+                    .draw(
+                        vertices,
+                        indices,
+                        opacity_program,
+                        &uniforms,
+                        &opacity_params,
+                    )
+                    .unwrap();
+            }
+        }
+    }
+
+    /// uniforms `deep_opacity.glsl` reads for maps rendered with `views`, chained with `others`
+    pub fn with_uniforms<'m, U: Uniforms>(
+        &'m self,
+        views: &'m [DeepOpacityView],
+        others: U,
+    ) -> DeepOpacityUniforms<'m, U> {
+        DeepOpacityUniforms {
+            maps: self,
+            views,
+            others,
+        }
+    }
+}
+
+/// deep opacity uniforms chained with the per-draw ones
+pub struct DeepOpacityUniforms<'m, U> {
+    maps: &'m DeepOpacityMaps,
+    views: &'m [DeepOpacityView],
+    others: U,
+}
+
+impl<U: Uniforms> Uniforms for DeepOpacityUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+        let settings = &self.maps.settings;
+        let nearest = SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            ..Default::default()
+        };
+        let linear = SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            ..nearest
+        };
+        // depths of neighbouring texels can belong to different strands, opacity blends fine
+        f(
+            "deep_opacity_depth",
+            UniformValue::Texture2dArray(&self.maps.depth, Some(nearest)),
+        );
+        f(
+            "deep_opacity_layers",
+            UniformValue::Texture2dArray(&self.maps.opacity, Some(linear)),
+        );
+        for slot in 0..DEEP_OPACITY_SLOTS {
+            let view = self.views.get(slot);
+            f(
+                &format!("deep_opacity_lights[{}]", slot),
+                UniformValue::SignedInt(view.map_or(-1, |view| view.light as i32)),
+            );
+            f(
+                &format!("deep_opacity_views[{}]", slot),
+                UniformValue::Mat4(mat4_uniform(&view.map_or_else(Mat4::one, |view| view.view))),
+            );
+            f(
+                &format!("deep_opacity_projections[{}]", slot),
+                UniformValue::Mat4(mat4_uniform(
+                    &view.map_or_else(Mat4::one, |view| view.projection),
+                )),
+            );
+        }
+        let layer_count = if self.maps.programs.is_some() {
+            settings.texture_layers() * 4
+        } else {
+            0
+        };
+        f(
+            "deep_opacity_layer_count",
+            UniformValue::SignedInt(layer_count as i32),
+        );
+        f(
+            "deep_opacity_spacing",
+            UniformValue::Float(settings.spacing),
+        );
+        self.others.visit_values(f);
+    }
+}
+
+/// the sphere around the local `centre` and `radius` after `model_mat`
+pub fn transform_sphere(model_mat: &Mat4, centre: Vec3, radius: f32) -> (Vec3, f32) {
+    let world = *model_mat * Vec4::new(centre.x, centre.y, centre.z, 1.0);
+    let scale = (0..3)
+        .map(|k| glm::length(model_mat[k].truncate(3)))
+        .fold(0.0, f32::max);
+    (Vec3::new(world.x, world.y, world.z), radius * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lights::SceneLight;
+    use crate::transform::Transform;
+
+    const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+    fn project(view: &DeepOpacityView, p: Vec3) -> Vec3 {
+        let clip = view.projection * view.view * Vec4::new(p.x, p.y, p.z, 1.0);
+        Vec3::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w)
+    }
+
+    fn assert_inside(view: &DeepOpacityView, centre: Vec3, radius: f32) {
+        let middle = project(view, centre);
+        assert!(
+            middle.x.abs() < 1e-4 && middle.y.abs() < 1e-4,
+            "{:?}",
+            middle
+        );
+        for axis in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ] {
+            for side in [-1.0, 1.0] {
+                let p = project(view, centre + axis * (side * radius));
+                for k in 0..3 {
+                    assert!(p[k].abs() <= 1.0 + 1e-4, "{:?}", p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn spheres_follow_the_model_matrix() {
+        let centre = Vec3::new(1.0, 0.0, 0.0);
+        let moved = Transform::from_translation(Vec3::new(0.0, 2.0, 0.0)).matrix();
+        assert_eq!(
+            transform_sphere(&moved, centre, 0.5),
+            (Vec3::new(1.0, 2.0, 0.0), 0.5)
+        );
+
+        // the longest axis decides the radius
+        let stretched = Transform::new()
+            .with_scale(Vec3::new(1.0, 3.0, 2.0))
+            .matrix();
+        let (world, radius) = transform_sphere(&stretched, centre, 0.5);
+        assert_eq!(world, centre);
+        assert!((radius - 1.5).abs() < 1e-5);
+
+        let turned = Transform::new()
+            .with_rotation(Vec3::new(30.0, 45.0, 0.0))
+            .with_uniform_scale(2.0)
+            .matrix();
+        let (world, radius) = transform_sphere(&turned, centre, 0.5);
+        assert!((glm::length(world) - 2.0).abs() < 1e-5);
+        assert!((radius - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn views_frame_the_sphere() {
+        let centre = Vec3::new(0.5, 1.0, -1.0);
+        let radius = 0.8;
+        let rig = LightRig::new(vec![
+            SceneLight::directional(Vec3::new(-1.0, -2.0, -0.5), WHITE),
+            SceneLight::point(Vec3::new(4.0, 3.0, 2.0), WHITE),
+            SceneLight::spot(
+                Vec3::new(0.0, 5.0, -1.0),
+                Vec3::new(0.0, -1.0, 0.0),
+                20.0,
+                30.0,
+                WHITE,
+            ),
+        ]);
+        let views = DeepOpacityView::fit(&rig, centre, radius);
+        assert_eq!(
+            views.iter().map(|view| view.light).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        for view in &views {
+            assert_inside(view, centre, radius);
+        }
+    }
+
+    #[test]
+    fn lights_inside_or_without_shadows_get_no_view() {
+        let centre = Vec3::new(0.0, 0.0, 0.0);
+        let rig = LightRig::new(vec![
+            SceneLight::point(Vec3::new(0.2, 0.0, 0.0), WHITE),
+            SceneLight::point(Vec3::new(3.0, 0.0, 0.0), WHITE).without_shadows(),
+            SceneLight::point(Vec3::new(0.0, 3.0, 0.0), WHITE),
+        ]);
+        let views = DeepOpacityView::fit(&rig, centre, 1.0);
+        assert_eq!(views.len(), 1);
+        assert_eq!(views[0].light, 2);
+    }
+
+    #[test]
+    fn views_stop_at_the_slots() {
+        let lights = (0..DEEP_OPACITY_SLOTS + 2)
+            .map(|i| SceneLight::point(Vec3::new(5.0, i as f32, 0.0), WHITE))
+            .collect();
+        let views = DeepOpacityView::fit(&LightRig::new(lights), Vec3::new(0.0, 0.0, 0.0), 1.0);
+        let lights: Vec<_> = views.iter().map(|view| view.light).collect();
+        assert_eq!(lights, (0..DEEP_OPACITY_SLOTS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn layers_are_bounded() {
+        let layers = |layers| DeepOpacitySettings {
+            layers,
+            ..DeepOpacitySettings::default()
+        };
+        assert_eq!(layers(0).texture_layers(), 1);
+        assert_eq!(layers(9).texture_layers(), 3);
+        let most = layers(u32::MAX).texture_layers() * DEEP_OPACITY_SLOTS as u32;
+        assert_eq!(most, OPACITY_LAYER_LIMIT);
+    }
+}
@@ -0,0 +1,16 @@
+#version 330 core
+
+in vec3 FragPos;
+
+uniform vec3 light_pos;
+// point and spot lights store the distance to the light over the far plane, 0 keeps the
+// window depth of directional lights
+uniform float far_plane;
+
+void main() {
+    if (far_plane > 0.0) {
+        gl_FragDepth = length(FragPos - light_pos) / far_plane;
+    } else {
+        gl_FragDepth = gl_FragCoord.z;
+    }
+}
\ No newline at end of file
@@ -0,0 +1,15 @@
+#version 330 core
+
+// position of any vertex layout, the rest is not read
+layout (location = 0) in vec3 position;
+
+out vec3 FragPos;
+
+uniform mat4 light_mat;
+uniform mat4 model;
+
+void main() {
+    vec4 world_pos = model * vec4(position, 1.0);
+    gl_Position = light_mat * world_pos;
+    FragPos = vec3(world_pos);
+}
\ No newline at end of file
@@ -0,0 +1,555 @@
+//! shadow maps for the lights of a [`LightRig`]. directional lights render the scene's depth
+//! through an orthographic box into a 2D depth texture, point and spot lights render the
+//! distance to the light into a depth cubemap. lit shaders read both through `shadows.glsl`,
+//! which `lights.glsl` includes, and soften the edges with percentage-closer filtering.
+//!
+//! the shaders have a fixed number of map slots per kind, GLSL 3.30 only indexes sampler arrays
+//! with constants. lights past the free slots stay unshadowed.
+
+use std::cell::{Ref, RefCell};
+use std::f32::consts::FRAC_PI_2;
+
+use glium::{Display, DrawParameters, Program, Surface, uniform};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::IndicesSource;
+use glium::texture::{CubeLayer, DepthCubemap, DepthTexture2d};
+use glium::uniforms::{
+    DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior,
+    SamplerWrapFunction, Uniforms, UniformValue,
+};
+use glium::vertex::MultiVerticesSource;
+use glm::{Mat4, Vec3, Vec4};
+use num_traits::One;
+
+use crate::lights::{LightKind, LightRig};
+use crate::shader::ProgramBuilder;
+use crate::shader_source;
+use crate::transform::mat4_uniform;
+
+pub mod debug;
+pub mod deep_opacity;
+
+/// map slots for directional lights, kept in sync with `shadows.glsl`
+pub const DIRECTIONAL_SLOTS: usize = 2;
+/// map slots for point and spot lights, kept in sync with `shadows.glsl`
+pub const POINT_SLOTS: usize = 2;
+
+/// what directional maps cover while the scene has no bounds, a sphere around the origin
+const DEFAULT_EXTENT: f32 = 4.0;
+/// room in front of and behind the scene's bounds in a directional light's box
+const DIRECTIONAL_MARGIN: f32 = 1.0;
+/// near plane of the cubemap faces
+const POINT_NEAR: f32 = 0.05;
+
+/// the six faces of a cubemap with the direction they look in and their up vector, as the
+/// cubemap lookup lays them out
+const CUBE_FACES: [(CubeLayer, [f32; 3], [f32; 3]); 6] = [
+    (CubeLayer::PositiveX, [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    (CubeLayer::NegativeX, [-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    (CubeLayer::PositiveY, [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    (CubeLayer::NegativeY, [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    (CubeLayer::PositiveZ, [0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    (CubeLayer::NegativeZ, [0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
+/// make `#include "shadows.glsl"` available, [`include_lights`](crate::lights::include_lights)
+/// does this for every lit program
+pub fn include_shadows(builder: ProgramBuilder) -> ProgramBuilder {
+    builder.include("shadows.glsl", shader_source!("shadows.glsl"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    /// render shadow maps at all
+    pub enabled: bool,
+    /// subtracted from the depth a fragment is compared with, against shadow acne. in the
+    /// normalized depth of the maps
+    pub bias: f32,
+    /// world units a fragment moves along its normal before the lookup, against acne on
+    /// surfaces at grazing angles
+    pub normal_offset: f32,
+    /// half size of the filter kernel in texels, 0 takes one bilinear comparison
+    pub pcf_radius: u32,
+    /// width and height of every map, cubemaps per face
+    pub map_size: u32,
+    /// far plane of point and spot lights without a range
+    pub point_far: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bias: 0.002,
+            normal_offset: 0.02,
+            pcf_radius: 1,
+            map_size: 1024,
+            point_far: 25.0,
+        }
+    }
+}
+
+/// a directional light rendering into a 2D map
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalShadow {
+    /// index of the light in the rig
+    pub light: usize,
+    /// world to the light's clip space
+    pub light_mat: Mat4,
+}
+
+/// a point or spot light rendering into a cubemap
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointShadow {
+    /// index of the light in the rig
+    pub light: usize,
+    pub position: Vec3,
+    /// distance stored as depth 1
+    pub far: f32,
+}
+
+/// which lights of a rig get a shadow map, in slot order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShadowLayout {
+    /// slot of every light of the rig, -1 for none, what `lights[i].shadow` receives
+    slots: Vec<i32>,
+    directional: Vec<DirectionalShadow>,
+    point: Vec<PointShadow>,
+}
+
+impl ShadowLayout {
+    /// hand out the slots in rig order to the lights that cast shadows, directional maps cover
+    /// the sphere `bounds`
+    pub fn new(rig: &LightRig, settings: &ShadowSettings, bounds: (Vec3, f32)) -> Self {
+        let mut layout = Self::default();
+        for (index, light) in rig.lights().iter().enumerate() {
+            let slot = if !settings.enabled || !light.cast_shadows {
+                None
+            } else {
+                match light.kind {
+                    LightKind::Directional { direction }
+                        if layout.directional.len() < DIRECTIONAL_SLOTS =>
+                    {
+                        layout.directional.push(DirectionalShadow {
+                            light: index,
+                            light_mat: directional_matrix(glm::normalize(direction), bounds),
+                        });
+                        Some(layout.directional.len() - 1)
+                    }
+                    LightKind::Point { position } | LightKind::Spot { position, .. }
+                        if layout.point.len() < POINT_SLOTS =>
+                    {
+                        layout.point.push(PointShadow {
+                            light: index,
+                            position,
+                            far: light.range.unwrap_or(settings.point_far),
+                        });
+                        Some(layout.point.len() - 1)
+                    }
+                    _ => None,
+                }
+            };
+            layout.slots.push(slot.map_or(-1, |slot| slot as i32));
+        }
+        layout
+    }
+
+    /// slot of the light at `index`, 2D and cube slots are counted separately
+    pub fn slot(&self, index: usize) -> Option<usize> {
+        let slot = *self.slots.get(index)?;
+        (slot >= 0).then_some(slot as usize)
+    }
+
+    pub fn directional(&self) -> &[DirectionalShadow] {
+        &self.directional
+    }
+
+    pub fn point(&self) -> &[PointShadow] {
+        &self.point
+    }
+}
+
+/// the depth program and maps the shadow pass renders into, shared by every lit drawable
+/// through [`FrameState`](crate::FrameState)
+#[derive(Debug)]
+pub struct ShadowMaps {
+    settings: ShadowSettings,
+    directional: Vec<DepthTexture2d>,
+    point: Vec<DepthCubemap>,
+    /// `None` while shadows are disabled
+    program: Option<Program>,
+    /// the lights the maps were last rendered for
+    layout: RefCell<ShadowLayout>,
+}
+
+impl ShadowMaps {
+    pub fn new(display: &Display, settings: ShadowSettings) -> Self {
+        // disabled maps are never sampled, but every slot needs a texture of the right type
+        let size = if settings.enabled {
+            settings.map_size.max(1)
+        } else {
+            1
+        };
+        let directional = (0..DIRECTIONAL_SLOTS)
+            .map(|_| DepthTexture2d::empty(display, size, size).unwrap())
+            .collect();
+        let point = (0..POINT_SLOTS)
+            .map(|_| DepthCubemap::empty(display, size).unwrap())
+            .collect();
+        let program = settings.enabled.then(|| {
+            ProgramBuilder::new(shader_source!("depth.vert"), shader_source!("depth.frag"))
+                .build(display)
+                .unwrap_or_else(|err| panic!("shadows: {}", err))
+        });
+        Self {
+            settings,
+            directional,
+            point,
+            program,
+            layout: RefCell::default(),
+        }
+    }
+
+    /// maps that never render, what drawables bind until the frame hands them real ones
+    pub fn disabled(display: &Display) -> Self {
+        Self::new(
+            display,
+            ShadowSettings {
+                enabled: false,
+                ..ShadowSettings::default()
+            },
+        )
+    }
+
+    pub fn settings(&self) -> &ShadowSettings {
+        &self.settings
+    }
+
+    pub fn directional_map(&self, slot: usize) -> Option<&DepthTexture2d> {
+        self.directional.get(slot)
+    }
+
+    pub fn point_map(&self, slot: usize) -> Option<&DepthCubemap> {
+        self.point.get(slot)
+    }
+
+    /// which lights the last [`render`](Self::render) gave a map
+    pub fn layout(&self) -> Ref<'_, ShadowLayout> {
+        self.layout.borrow()
+    }
+
+    /// render the maps of the rig's shadow casting lights, `draw` is called once per map and
+    /// cubemap face and should draw every shadow caster with the given pass. directional maps
+    /// cover `bounds`, the sphere around the casters, e.g. from
+    /// [`SceneGraph::bounds`](crate::scene_graph::SceneGraph::bounds)
+    pub fn render<F>(
+        &self,
+        display: &Display,
+        rig: &LightRig,
+        bounds: Option<(Vec3, f32)>,
+        mut draw: F,
+    ) where
+        F: FnMut(&mut SimpleFrameBuffer, &ShadowPass),
+    {
+        let Some(program) = &self.program else {
+            return;
+        };
+        let bounds = bounds.unwrap_or((Vec3::new(0.0, 0.0, 0.0), DEFAULT_EXTENT));
+        *self.layout.borrow_mut() = ShadowLayout::new(rig, &self.settings, bounds);
+        let layout = self.layout.borrow();
+        let params = DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for (map, shadow) in self.directional.iter().zip(layout.directional()) {
+            let mut target = SimpleFrameBuffer::depth_only(display, map).unwrap();
+            target.clear_depth(1.0);
+            let pass = ShadowPass {
+                program,
+                light_mat: shadow.light_mat,
+                light_pos: Vec3::new(0.0, 0.0, 0.0),
+                far: 0.0,
+                params: &params,
+            };
+            draw(&mut target, &pass);
+        }
+
+        for (map, shadow) in self.point.iter().zip(layout.point()) {
+            let projection = glm::ext::perspective(FRAC_PI_2, 1.0, POINT_NEAR, shadow.far);
+            for (layer, [fx, fy, fz], [ux, uy, uz]) in CUBE_FACES {
+                let image = map.main_level().image(layer);
+                let mut target = SimpleFrameBuffer::depth_only(display, image).unwrap();
+                target.clear_depth(1.0);
+                let view = glm::ext::look_at(
+                    shadow.position,
+                    shadow.position + Vec3::new(fx, fy, fz),
+                    Vec3::new(ux, uy, uz),
+                );
+                let pass = ShadowPass {
+                    program,
+                    light_mat: projection * view,
+                    light_pos: shadow.position,
+                    far: shadow.far,
+                    params: &params,
+                };
+                draw(&mut target, &pass);
+            }
+        }
+    }
+
+    /// uniforms `shadows.glsl` reads for the lights the maps were rendered for, chained with
+    /// `others`
+    pub fn with_uniforms<U: Uniforms>(&self, others: U) -> ShadowUniforms<'_, U> {
+        ShadowUniforms {
+            maps: self,
+            layout: self.layout(),
+            others,
+        }
+    }
+}
+
+/// one map or cubemap face being rendered, handed to
+/// [`Drawable::draw_shadow`](crate::Drawable::draw_shadow)
+pub struct ShadowPass<'a> {
+    program: &'a Program,
+    light_mat: Mat4,
+    light_pos: Vec3,
+    /// 0 for directional lights, which keep the window depth
+    far: f32,
+    params: &'a DrawParameters<'a>,
+}
+
+impl ShadowPass<'_> {
+    /// draw the depth of a mesh into the map, only the position at location 0 is read
+    pub fn draw<'b, V, I>(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        vertices: V,
+        indices: I,
+        model_mat: Mat4,
+    ) where
+        V: MultiVerticesSource<'b>,
+        I: Into<IndicesSource<'b>>,
+    {
+        let uniforms = uniform! {
+            light_mat: mat4_uniform(&self.light_mat),
+            model: mat4_uniform(&model_mat),
+            light_pos: *self.light_pos.as_array(),
+            far_plane: self.far,
+        };
+        target
+            .draw(vertices, indices, self.program, &uniforms, self.params)
+            .unwrap();
+    }
+}
+
+/// shadow uniforms chained with the per-draw ones
+pub struct ShadowUniforms<'m, U> {
+    maps: &'m ShadowMaps,
+    layout: Ref<'m, ShadowLayout>,
+    others: U,
+}
+
+impl<U: Uniforms> Uniforms for ShadowUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
+        let settings = &self.maps.settings;
+        for (i, slot) in self.layout.slots.iter().enumerate() {
+            f(
+                &format!("lights[{}].shadow", i),
+                UniformValue::SignedInt(*slot),
+            );
+        }
+        f("shadow_bias", UniformValue::Float(settings.bias));
+        f(
+            "shadow_normal_offset",
+            UniformValue::Float(settings.normal_offset),
+        );
+        f(
+            "shadow_pcf_radius",
+            UniformValue::SignedInt(settings.pcf_radius as i32),
+        );
+
+        // the hardware compares, and blends the results of the four nearest texels
+        let behavior = SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            depth_texture_comparison: Some(DepthTextureComparison::LessOrEqual),
+            ..Default::default()
+        };
+        for (slot, map) in self.maps.directional.iter().enumerate() {
+            let light_mat = self
+                .layout
+                .directional
+                .get(slot)
+                .map_or_else(Mat4::one, |shadow| shadow.light_mat);
+            f(
+                &format!("directional_shadow_maps[{}]", slot),
+                UniformValue::DepthTexture2d(map, Some(behavior)),
+            );
+            f(
+                &format!("directional_shadow_mats[{}]", slot),
+                UniformValue::Mat4(mat4_uniform(&light_mat)),
+            );
+        }
+        for (slot, map) in self.maps.point.iter().enumerate() {
+            let far = self.layout.point.get(slot).map_or(1.0, |shadow| shadow.far);
+            f(
+                &format!("point_shadow_maps[{}]", slot),
+                UniformValue::DepthCubemap(map, Some(behavior)),
+            );
+            f(
+                &format!("point_shadow_far[{}]", slot),
+                UniformValue::Float(far),
+            );
+        }
+        self.others.visit_values(f);
+    }
+}
+
+/// orthographic view of the box around the sphere `(centre, radius)`, looking along the
+/// light's `direction`
+fn directional_matrix(direction: Vec3, (centre, radius): (Vec3, f32)) -> Mat4 {
+    let radius = radius.max(1e-3);
+    let eye = centre - direction * (radius + DIRECTIONAL_MARGIN);
+    let view = glm::ext::look_at(eye, centre, up_vector(direction));
+    ortho(radius, 2.0 * (radius + DIRECTIONAL_MARGIN)) * view
+}
+
+/// up vector for a view looking along `direction`, any that is not parallel to it
+pub(crate) fn up_vector(direction: Vec3) -> Vec3 {
+    if direction.y.abs() > 0.99 {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    }
+}
+
+/// symmetric orthographic projection from `-extent` to `extent` and from 0 to `far`, glm has
+/// no `ortho`
+pub(crate) fn ortho(extent: f32, far: f32) -> Mat4 {
+    Mat4::new(
+        Vec4::new(1.0 / extent, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, 1.0 / extent, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, -2.0 / far, 0.0),
+        Vec4::new(0.0, 0.0, -1.0, 1.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lights::SceneLight;
+
+    const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+    fn origin_bounds() -> (Vec3, f32) {
+        (Vec3::new(0.0, 0.0, 0.0), 2.0)
+    }
+
+    fn project(mat: &Mat4, p: Vec3) -> Vec3 {
+        let clip = *mat * Vec4::new(p.x, p.y, p.z, 1.0);
+        Vec3::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w)
+    }
+
+    #[test]
+    fn slots_go_to_shadow_casters_in_rig_order() {
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let rig = LightRig::new(vec![
+            SceneLight::point(up, WHITE),
+            SceneLight::directional(-up, WHITE),
+            SceneLight::point(up, WHITE).without_shadows(),
+            SceneLight::spot(up, -up, 20.0, 30.0, WHITE),
+            SceneLight::directional(-up, WHITE),
+            SceneLight::point(up, WHITE),
+            SceneLight::directional(-up, WHITE),
+        ]);
+        let layout = ShadowLayout::new(&rig, &ShadowSettings::default(), origin_bounds());
+        let slots: Vec<_> = (0..rig.len()).map(|i| layout.slot(i)).collect();
+        assert_eq!(
+            slots,
+            [Some(0), Some(0), None, Some(1), Some(1), None, None]
+        );
+        assert_eq!(layout.slot(rig.len()), None);
+        let point: Vec<_> = layout.point().iter().map(|shadow| shadow.light).collect();
+        assert_eq!(point, [0, 3]);
+        let directional: Vec<_> = layout.directional().iter().map(|s| s.light).collect();
+        assert_eq!(directional, [1, 4]);
+    }
+
+    #[test]
+    fn disabled_shadows_hand_out_no_slots() {
+        let rig = LightRig::three_point();
+        let settings = ShadowSettings {
+            enabled: false,
+            ..ShadowSettings::default()
+        };
+        let layout = ShadowLayout::new(&rig, &settings, origin_bounds());
+        assert!(layout.directional().is_empty() && layout.point().is_empty());
+        assert!((0..rig.len()).all(|i| layout.slot(i).is_none()));
+        assert_eq!(layout.slots, vec![-1; rig.len()]);
+    }
+
+    #[test]
+    fn point_maps_reach_the_light_range() {
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let rig = LightRig::new(vec![
+            SceneLight::point(position, WHITE).with_range(7.0),
+            SceneLight::point(position, WHITE),
+        ]);
+        let settings = ShadowSettings::default();
+        let layout = ShadowLayout::new(&rig, &settings, origin_bounds());
+        assert_eq!(layout.point()[0].far, 7.0);
+        assert_eq!(layout.point()[1].far, settings.point_far);
+        assert_eq!(layout.point()[0].position, position);
+    }
+
+    #[test]
+    fn directional_maps_cover_the_bounds() {
+        let centre = Vec3::new(5.0, -1.0, 2.0);
+        let radius = 3.0;
+        for direction in [
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.5),
+            Vec3::new(-0.2, 0.3, 1.0),
+        ] {
+            let rig = LightRig::new(vec![SceneLight::directional(direction, WHITE)]);
+            let layout = ShadowLayout::new(&rig, &ShadowSettings::default(), (centre, radius));
+            let light_mat = layout.directional()[0].light_mat;
+
+            let middle = project(&light_mat, centre);
+            assert!(
+                middle.x.abs() < 1e-4 && middle.y.abs() < 1e-4,
+                "{:?}",
+                middle
+            );
+            let axes = [
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ];
+            for axis in axes {
+                for side in [-1.0, 1.0] {
+                    let p = project(&light_mat, centre + axis * (side * radius));
+                    for k in 0..3 {
+                        assert!(p[k].abs() <= 1.0 + 1e-4, "{:?} {:?}", direction, p);
+                    }
+                }
+            }
+            // the side facing the light is nearer
+            let direction = glm::normalize(direction);
+            let front = project(&light_mat, centre - direction * radius);
+            let back = project(&light_mat, centre + direction * radius);
+            assert!(front.z < back.z);
+        }
+    }
+}
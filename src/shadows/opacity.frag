@@ -0,0 +1,20 @@
+#version 330 core
+
+in float ViewDepth;
+
+out vec4 FragColor;
+
+// nearest strand depth of every light, from the first pass
+uniform sampler2DArray nearest_depth;
+uniform int slot;
+// boundary written to the red channel of this target, counted from 1
+uniform int first_boundary;
+uniform float spacing;
+uniform float strand_opacity;
+
+// blended additively, every strand adds its opacity to each boundary it lies in front of
+void main() {
+    float nearest = texelFetch(nearest_depth, ivec3(gl_FragCoord.xy, slot), 0).r;
+    vec4 boundaries = (vec4(first_boundary) + vec4(0.0, 1.0, 2.0, 3.0)) * spacing;
+    FragColor = strand_opacity * vec4(lessThan(vec4(ViewDepth - nearest), boundaries));
+}
\ No newline at end of file
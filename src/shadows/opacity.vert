@@ -0,0 +1,17 @@
+#version 330 core
+
+// position of any vertex layout, the rest is not read
+layout (location = 0) in vec3 position;
+
+// distance in front of the light along its view direction
+out float ViewDepth;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+void main() {
+    vec4 view_pos = view * model * vec4(position, 1.0);
+    gl_Position = projection * view_pos;
+    ViewDepth = -view_pos.z;
+}
\ No newline at end of file
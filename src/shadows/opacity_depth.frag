@@ -0,0 +1,10 @@
+#version 330 core
+
+in float ViewDepth;
+
+out vec4 FragColor;
+
+// blended with min, so the nearest strand is left
+void main() {
+    FragColor = vec4(ViewDepth);
+}
\ No newline at end of file
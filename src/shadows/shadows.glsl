@@ -0,0 +1,86 @@
+// shadow maps of the lights, filled in by ShadowMaps in src/shadows/mod.rs. included by
+// lights.glsl after the Light struct
+
+// slots per kind, kept in sync with src/shadows/mod.rs. sampler arrays may only be indexed
+// with constants, so the lookups below are unrolled per slot
+#define DIRECTIONAL_SHADOW_SLOTS 2
+#define POINT_SHADOW_SLOTS 2
+
+uniform sampler2DShadow directional_shadow_maps[DIRECTIONAL_SHADOW_SLOTS];
+// world to the light's clip space
+uniform mat4 directional_shadow_mats[DIRECTIONAL_SHADOW_SLOTS];
+// the cubemaps hold the distance to the light divided by the far plane
+uniform samplerCubeShadow point_shadow_maps[POINT_SHADOW_SLOTS];
+uniform float point_shadow_far[POINT_SHADOW_SLOTS];
+
+// subtracted from the depth a fragment is compared with
+uniform float shadow_bias;
+// world units a fragment moves along its normal before the lookup
+uniform float shadow_normal_offset;
+// half size of the filter kernel in texels
+uniform int shadow_pcf_radius;
+
+float directional_pcf(sampler2DShadow map, mat4 light_mat, vec3 pos) {
+    vec4 clip = light_mat * vec4(pos, 1.0);
+    vec3 coord = clip.xyz / clip.w * 0.5 + 0.5;
+    // outside the box the map covers
+    if (any(lessThan(coord, vec3(0.0))) || any(greaterThan(coord, vec3(1.0)))) {
+        return 1.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(map, 0));
+    float lit = 0.0;
+    for (int x = -shadow_pcf_radius; x <= shadow_pcf_radius; ++x) {
+        for (int y = -shadow_pcf_radius; y <= shadow_pcf_radius; ++y) {
+            lit += texture(map, vec3(coord.xy + vec2(x, y) * texel, coord.z - shadow_bias));
+        }
+    }
+    float side = float(2 * shadow_pcf_radius + 1);
+    return lit / (side * side);
+}
+
+float point_pcf(samplerCubeShadow map, float far_plane, vec3 light_pos, vec3 pos) {
+    vec3 to_pos = pos - light_pos;
+    float dist = length(to_pos);
+    float depth = dist / far_plane - shadow_bias;
+    // past the far plane nothing was rendered
+    if (depth > 1.0) {
+        return 1.0;
+    }
+    // a face spans 90 degrees, so one texel is about 2 * dist / size wide at this distance
+    float texel = 2.0 * dist / float(textureSize(map, 0).x);
+    float lit = 0.0;
+    for (int x = -shadow_pcf_radius; x <= shadow_pcf_radius; ++x) {
+        for (int y = -shadow_pcf_radius; y <= shadow_pcf_radius; ++y) {
+            for (int z = -shadow_pcf_radius; z <= shadow_pcf_radius; ++z) {
+                lit += texture(map, vec4(to_pos + vec3(x, y, z) * texel, depth));
+            }
+        }
+    }
+    float side = float(2 * shadow_pcf_radius + 1);
+    return lit / (side * side * side);
+}
+
+// fraction of the light reaching pos, 1 for lights without a shadow map. surfaces pass their
+// normal, fibres have none and pass the direction towards the light instead
+float light_shadow(Light light, vec3 pos, vec3 normal) {
+    if (light.shadow < 0) {
+        return 1.0;
+    }
+    vec3 p = pos + normal * shadow_normal_offset;
+    if (light.kind == DIRECTIONAL_LIGHT) {
+        if (light.shadow == 0) {
+            return directional_pcf(directional_shadow_maps[0], directional_shadow_mats[0], p);
+        }
+        if (light.shadow == 1) {
+            return directional_pcf(directional_shadow_maps[1], directional_shadow_mats[1], p);
+        }
+        return 1.0;
+    }
+    if (light.shadow == 0) {
+        return point_pcf(point_shadow_maps[0], point_shadow_far[0], light.position, p);
+    }
+    if (light.shadow == 1) {
+        return point_pcf(point_shadow_maps[1], point_shadow_far[1], light.position, p);
+    }
+    return 1.0;
+}
\ No newline at end of file
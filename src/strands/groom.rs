@@ -0,0 +1,371 @@
+//! procedural hair grooming: guide hairs grown from a base mesh along its normals, then a dense
+//! set of render hairs interpolated from the guides
+
+use std::f32::consts::PI;
+
+use glm::Vec3;
+
+use crate::mesh::MeshData;
+use crate::strands::{to_vec3, Strand};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroomParams {
+    pub guide_count: usize,
+    pub hair_count: usize,
+    /// segments per strand
+    pub segments: usize,
+    pub length: f32,
+    /// random length variation, as a fraction of `length`
+    pub length_variation: f32,
+    /// radius of the helical curl, 0 for straight hair
+    pub curl_radius: f32,
+    /// number of curl turns over the strand
+    pub curl_turns: f32,
+    /// how strongly the strands bend down towards -y, 0 keeps them along the normal
+    pub gravity: f32,
+    /// 0..1, how far the tips of render hairs are pulled onto their closest guide
+    pub clumping: f32,
+    /// amplitude of the random jitter, growing from root to tip
+    pub frizz: f32,
+    pub root_width: f32,
+    pub tip_width: f32,
+    pub seed: u64,
+}
+
+impl Default for GroomParams {
+    fn default() -> Self {
+        Self {
+            guide_count: 64,
+            hair_count: 4000,
+            segments: 16,
+            length: 0.6,
+            length_variation: 0.2,
+            curl_radius: 0.0,
+            curl_turns: 3.0,
+            gravity: 0.6,
+            clumping: 0.3,
+            frizz: 0.005,
+            root_width: 0.004,
+            tip_width: 0.001,
+            seed: 1,
+        }
+    }
+}
+
+/// result of grooming, the guides are kept for debugging and re-interpolation
+#[derive(Clone, Debug, Default)]
+pub struct Groom {
+    pub guides: Vec<Strand>,
+    pub hairs: Vec<Strand>,
+}
+
+/// grow guides on `base` and interpolate render hairs from them.
+/// the same base and params always produce the same groom
+pub fn groom(base: &MeshData, params: &GroomParams) -> Groom {
+    let sampler = SurfaceSampler::new(base);
+    if sampler.is_empty() || params.guide_count == 0 {
+        return Groom::default();
+    }
+    let mut rng = Rng::new(params.seed);
+
+    let guides: Vec<Guide> = (0..params.guide_count)
+        .map(|_| {
+            let (root, normal) = sampler.sample(&mut rng);
+            grow_guide(root, normal, params, &mut rng)
+        })
+        .collect();
+
+    let hairs = (0..params.hair_count)
+        .map(|_| {
+            let (root, _) = sampler.sample(&mut rng);
+            interpolate_hair(root, &guides, params, &mut rng)
+        })
+        .collect();
+
+    Groom {
+        guides: guides
+            .into_iter()
+            .map(|g| strand_from_points(g.points, params))
+            .collect(),
+        hairs,
+    }
+}
+
+struct Guide {
+    points: Vec<Vec3>,
+}
+
+impl Guide {
+    fn root(&self) -> Vec3 {
+        self.points[0]
+    }
+}
+
+fn grow_guide(root: Vec3, normal: Vec3, params: &GroomParams, rng: &mut Rng) -> Guide {
+    let segments = params.segments.max(1);
+    let length = params.length * (1.0 + params.length_variation * rng.signed());
+    let step = length / segments as f32;
+    let down = Vec3::new(0.0, -1.0, 0.0);
+
+    // centre line: start along the normal and bend towards gravity a bit more each segment
+    let mut centre = vec![root];
+    let mut dir = normal;
+    for _ in 0..segments {
+        let bent = dir + down * (params.gravity / segments as f32 * 4.0);
+        if glm::length(bent) > 1e-6 {
+            dir = glm::normalize(bent);
+        }
+        let last = *centre.last().unwrap();
+        centre.push(last + dir * step);
+    }
+
+    // helical curl around the centre line, faded in so the root stays on the surface
+    let (b1, b2) = perpendicular_basis(normal);
+    let phase = rng.next_f32() * 2.0 * PI;
+    let points = centre
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let t = i as f32 / segments as f32;
+            let fade = smoothstep(0.0, 0.15, t);
+            let angle = phase + t * params.curl_turns * 2.0 * PI;
+            c + (b1 * angle.cos() + b2 * angle.sin()) * (params.curl_radius * fade)
+        })
+        .collect();
+    Guide { points }
+}
+
+/// blend the shapes of the closest guides, pull towards the closest one and add frizz
+fn interpolate_hair(root: Vec3, guides: &[Guide], params: &GroomParams, rng: &mut Rng) -> Strand {
+    const NEIGHBOURS: usize = 3;
+
+    let mut nearest: Vec<(f32, usize)> = guides
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (glm::distance(g.root(), root), i))
+        .collect();
+    nearest.sort_by(|a, b| a.0.total_cmp(&b.0));
+    nearest.truncate(NEIGHBOURS);
+
+    let weights: Vec<f32> = nearest.iter().map(|&(d, _)| 1.0 / (d + 1e-4)).collect();
+    let total: f32 = weights.iter().sum();
+    let closest = &guides[nearest[0].1];
+
+    let segments = params.segments.max(1);
+    let points = (0..=segments)
+        .map(|i| {
+            let t = i as f32 / segments as f32;
+            let mut offset = Vec3::new(0.0, 0.0, 0.0);
+            for (&(_, g), w) in nearest.iter().zip(&weights) {
+                let guide = &guides[g];
+                offset = offset + (guide.points[i] - guide.root()) * (w / total);
+            }
+            let blended = root + offset;
+            // clumping keeps the root in place and moves the tip onto the guide
+            let pull = params.clumping * t;
+            let clumped = blended * (1.0 - pull) + closest.points[i] * pull;
+            let frizz = Vec3::new(rng.signed(), rng.signed(), rng.signed()) * (params.frizz * t);
+            clumped + frizz
+        })
+        .collect();
+    strand_from_points(points, params)
+}
+
+fn strand_from_points(points: Vec<Vec3>, params: &GroomParams) -> Strand {
+    Strand::tapered(
+        points.into_iter().map(|p| *p.as_array()).collect(),
+        params.root_width,
+        params.tip_width,
+    )
+}
+
+/// area weighted random points on a triangle mesh
+struct SurfaceSampler<'a> {
+    mesh: &'a MeshData,
+    /// running sum of triangle areas
+    cumulative_area: Vec<f32>,
+}
+
+impl<'a> SurfaceSampler<'a> {
+    fn new(mesh: &'a MeshData) -> Self {
+        let mut total = 0.0;
+        let cumulative_area = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] =
+                    [t[0], t[1], t[2]].map(|i| to_vec3(mesh.vertices[i as usize].position));
+                total += 0.5 * glm::length(glm::cross(b - a, c - a));
+                total
+            })
+            .collect();
+        Self {
+            mesh,
+            cumulative_area,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !matches!(self.cumulative_area.last(), Some(&a) if a > 0.0)
+    }
+
+    /// position and interpolated unit normal of a random surface point
+    fn sample(&self, rng: &mut Rng) -> (Vec3, Vec3) {
+        let total = *self.cumulative_area.last().unwrap();
+        let target = rng.next_f32() * total;
+        let tri = self
+            .cumulative_area
+            .partition_point(|&a| a < target)
+            .min(self.cumulative_area.len() - 1);
+
+        // uniform barycentric coordinates
+        let (mut u, mut v) = (rng.next_f32(), rng.next_f32());
+        if u + v > 1.0 {
+            u = 1.0 - u;
+            v = 1.0 - v;
+        }
+        let w = 1.0 - u - v;
+
+        let [a, b, c] =
+            [0, 1, 2].map(|k| self.mesh.vertices[self.mesh.indices[tri * 3 + k] as usize]);
+        let position = to_vec3(a.position) * w + to_vec3(b.position) * u + to_vec3(c.position) * v;
+        let normal = to_vec3(a.normal) * w + to_vec3(b.normal) * u + to_vec3(c.normal) * v;
+        let normal = if glm::length(normal) > 1e-6 {
+            glm::normalize(normal)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        (position, normal)
+    }
+}
+
+/// small deterministic generator (SplitMix64), so grooms are reproducible across platforms
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// uniform in [-1, 1)
+    fn signed(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+}
+
+fn perpendicular_basis(n: Vec3) -> (Vec3, Vec3) {
+    let helper = if n.y.abs() < 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let b1 = glm::normalize(glm::cross(n, helper));
+    let b2 = glm::cross(n, b1);
+    (b1, b2)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh;
+
+    use super::*;
+
+    fn small() -> GroomParams {
+        GroomParams {
+            guide_count: 8,
+            hair_count: 50,
+            segments: 6,
+            ..GroomParams::default()
+        }
+    }
+
+    fn arc_length(strand: &Strand) -> f32 {
+        strand
+            .points
+            .windows(2)
+            .map(|p| glm::distance(to_vec3(p[0]), to_vec3(p[1])))
+            .sum()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_groom() {
+        let base = mesh::uv_sphere(0.5, 8, 16);
+        let a = groom(&base, &small());
+        let b = groom(&base, &small());
+        assert_eq!(a.guides, b.guides);
+        assert_eq!(a.hairs, b.hairs);
+    }
+
+    #[test]
+    fn different_seeds_give_different_grooms() {
+        let base = mesh::uv_sphere(0.5, 8, 16);
+        let a = groom(&base, &small());
+        let b = groom(&base, &GroomParams { seed: 2, ..small() });
+        assert_ne!(a.guides, b.guides);
+        assert_ne!(a.hairs, b.hairs);
+    }
+
+    #[test]
+    fn counts_follow_the_params() {
+        let params = small();
+        let groom = groom(&mesh::plane(1.0, 2), &params);
+        assert_eq!(groom.guides.len(), params.guide_count);
+        assert_eq!(groom.hairs.len(), params.hair_count);
+        for strand in groom.guides.iter().chain(&groom.hairs) {
+            assert_eq!(strand.points.len(), params.segments + 1);
+            assert_eq!(strand.widths.len(), params.segments + 1);
+            assert_eq!(strand.widths[0], params.root_width);
+            assert!((strand.widths[params.segments] - params.tip_width).abs() < 1e-7);
+        }
+    }
+
+    #[test]
+    fn lengths_follow_the_params() {
+        let params = GroomParams {
+            length_variation: 0.2,
+            ..small()
+        };
+        for guide in groom(&mesh::uv_sphere(0.5, 8, 16), &params).guides {
+            let length = arc_length(&guide);
+            assert!(length >= params.length * 0.8 - 1e-4, "{}", length);
+            assert!(length <= params.length * 1.2 + 1e-4, "{}", length);
+        }
+
+        // straight up from a plane every guide has the same shape, and unclumped so has every hair
+        let params = GroomParams {
+            length_variation: 0.0,
+            gravity: 0.0,
+            clumping: 0.0,
+            frizz: 0.0,
+            ..small()
+        };
+        let groom = groom(&mesh::plane(1.0, 2), &params);
+        for strand in groom.guides.iter().chain(&groom.hairs) {
+            assert!((arc_length(strand) - params.length).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn empty_base_gives_an_empty_groom() {
+        let groom = groom(&MeshData::default(), &small());
+        assert!(groom.guides.is_empty() && groom.hairs.is_empty());
+    }
+}
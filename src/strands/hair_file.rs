@@ -0,0 +1,587 @@
+//! reader and writer for Cem Yuksel's binary `.hair` format.
+//!
+//! the file starts with a 128 byte little endian header: the `HAIR` signature, hair count,
+//! point count, a bit field telling which arrays follow, default segment count, thickness,
+//! transparency and colour, and an 88 byte info string. the arrays follow in the order
+//! segments (u16 per hair), points (3 f32), thickness (f32), transparency (f32) and
+//! colour (3 f32 per point). arrays that are missing take the header default
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::strands::Strand;
+
+const SIGNATURE: &[u8; 4] = b"HAIR";
+const INFO_LEN: usize = 88;
+
+const HAS_SEGMENTS: u32 = 1 << 0;
+const HAS_POINTS: u32 = 1 << 1;
+const HAS_THICKNESS: u32 = 1 << 2;
+const HAS_TRANSPARENCY: u32 = 1 << 3;
+const HAS_COLOR: u32 = 1 << 4;
+
+#[derive(Debug)]
+pub enum HairError {
+    Io(io::Error),
+    /// the data does not start with the `HAIR` signature
+    Signature,
+    Invalid(String),
+}
+
+impl Display for HairError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HairError::Io(err) => write!(f, "failed to read hair file: {}", err),
+            HairError::Signature => write!(f, "not a hair file, the signature is missing"),
+            HairError::Invalid(message) => write!(f, "invalid hair file: {}", message),
+        }
+    }
+}
+
+impl Error for HairError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HairError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for HairError {
+    fn from(err: io::Error) -> Self {
+        HairError::Io(err)
+    }
+}
+
+/// contents of a `.hair` file, the optional arrays are `None` when the file uses the default
+#[derive(Clone, Debug, PartialEq)]
+pub struct HairFile {
+    /// segments of every hair, a hair has one point more than segments
+    pub segments: Option<Vec<u16>>,
+    pub default_segments: u32,
+    pub points: Vec<[f32; 3]>,
+    /// per point
+    pub thickness: Option<Vec<f32>>,
+    pub default_thickness: f32,
+    /// per point
+    pub transparency: Option<Vec<f32>>,
+    pub default_transparency: f32,
+    /// per point
+    pub colors: Option<Vec<[f32; 3]>>,
+    pub default_color: [f32; 3],
+    /// free form text, at most 88 bytes are stored
+    pub info: String,
+    pub hair_count: usize,
+}
+
+impl Default for HairFile {
+    fn default() -> Self {
+        Self {
+            segments: None,
+            default_segments: 0,
+            points: Vec::new(),
+            thickness: None,
+            default_thickness: 1.0,
+            transparency: None,
+            default_transparency: 0.0,
+            colors: None,
+            default_color: [1.0, 1.0, 1.0],
+            info: String::new(),
+            hair_count: 0,
+        }
+    }
+}
+
+impl HairFile {
+    /// store strands with their points and widths, every strand must have at least two points
+    /// and at most 65536
+    pub fn from_strands(strands: &[Strand]) -> Self {
+        let segments: Vec<u16> = strands
+            .iter()
+            .map(|s| s.points.len().saturating_sub(1) as u16)
+            .collect();
+        let mut file = Self {
+            hair_count: strands.len(),
+            points: strands
+                .iter()
+                .flat_map(|s| s.points.iter().copied())
+                .collect(),
+            thickness: Some(
+                strands
+                    .iter()
+                    .flat_map(|s| {
+                        (0..s.points.len()).map(|i| s.widths.get(i).copied().unwrap_or(0.0))
+                    })
+                    .collect(),
+            ),
+            ..Self::default()
+        };
+        // a single segment count goes into the header
+        match segments.first() {
+            Some(&first) if segments.iter().all(|&s| s == first) => {
+                file.default_segments = first as u32
+            }
+            _ => file.segments = Some(segments),
+        }
+        file
+    }
+
+    /// segment count of hair `i`
+    pub fn hair_segments(&self, i: usize) -> usize {
+        match &self.segments {
+            Some(segments) => segments[i] as usize,
+            None => self.default_segments as usize,
+        }
+    }
+
+    pub fn point_thickness(&self, i: usize) -> f32 {
+        self.thickness
+            .as_ref()
+            .map_or(self.default_thickness, |t| t[i])
+    }
+
+    pub fn point_transparency(&self, i: usize) -> f32 {
+        self.transparency
+            .as_ref()
+            .map_or(self.default_transparency, |t| t[i])
+    }
+
+    pub fn point_color(&self, i: usize) -> [f32; 3] {
+        self.colors.as_ref().map_or(self.default_color, |c| c[i])
+    }
+
+    /// split the points into strands, the thickness becomes the strand width
+    pub fn strands(&self) -> Vec<Strand> {
+        let mut strands = Vec::with_capacity(self.hair_count);
+        let mut start = 0;
+        for hair in 0..self.hair_count {
+            let end = start + self.hair_segments(hair) + 1;
+            strands.push(Strand {
+                points: self.points[start..end].to_vec(),
+                widths: (start..end).map(|i| self.point_thickness(i)).collect(),
+            });
+            start = end;
+        }
+        strands
+    }
+
+    /// mean colour of the roots and of the tips, `None` without a colour array
+    pub fn root_and_tip_colors(&self) -> Option<([f32; 3], [f32; 3])> {
+        let colors = self.colors.as_ref()?;
+        if self.hair_count == 0 {
+            return None;
+        }
+        let mut root = [0.0; 3];
+        let mut tip = [0.0; 3];
+        let mut start = 0;
+        for hair in 0..self.hair_count {
+            let end = start + self.hair_segments(hair);
+            for k in 0..3 {
+                root[k] += colors[start][k];
+                tip[k] += colors[end][k];
+            }
+            start = end + 1;
+        }
+        let n = self.hair_count as f32;
+        Some((root.map(|c| c / n), tip.map(|c| c / n)))
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, HairError> {
+        let mut header = [0u8; 128];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != SIGNATURE {
+            return Err(HairError::Signature);
+        }
+        let u32_at = |o: usize| u32::from_le_bytes(header[o..o + 4].try_into().unwrap());
+        let f32_at = |o: usize| f32::from_le_bytes(header[o..o + 4].try_into().unwrap());
+
+        let hair_count = u32_at(4) as usize;
+        let point_count = u32_at(8) as usize;
+        let flags = u32_at(12);
+        let info = &header[40..40 + INFO_LEN];
+        let info_end = info.iter().position(|&b| b == 0).unwrap_or(INFO_LEN);
+
+        let mut file = Self {
+            hair_count,
+            default_segments: u32_at(16),
+            default_thickness: f32_at(20),
+            default_transparency: f32_at(24),
+            default_color: [f32_at(28), f32_at(32), f32_at(36)],
+            info: String::from_utf8_lossy(&info[..info_end]).into_owned(),
+            ..Self::default()
+        };
+
+        // the arrays are read from memory, after the header counts are checked against what
+        // the file holds, so a bad header cannot make the allocations below huge
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+        let array_bytes = [
+            (HAS_SEGMENTS, hair_count.checked_mul(2)),
+            (HAS_POINTS, point_count.checked_mul(12)),
+            (HAS_THICKNESS, point_count.checked_mul(4)),
+            (HAS_TRANSPARENCY, point_count.checked_mul(4)),
+            (HAS_COLOR, point_count.checked_mul(12)),
+        ]
+        .into_iter()
+        .filter(|&(flag, _)| flags & flag != 0)
+        .try_fold(0usize, |sum, (_, bytes)| sum.checked_add(bytes?));
+        match array_bytes {
+            Some(needed) if needed <= body.len() => {}
+            _ => {
+                return Err(HairError::Invalid(format!(
+                    "{} hairs and {} points do not fit in the {} bytes after the header",
+                    hair_count,
+                    point_count,
+                    body.len()
+                )))
+            }
+        }
+        let mut reader = body.as_slice();
+
+        if flags & HAS_SEGMENTS != 0 {
+            let mut bytes = vec![0u8; hair_count * 2];
+            reader.read_exact(&mut bytes)?;
+            file.segments = Some(
+                bytes
+                    .chunks_exact(2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                    .collect(),
+            );
+        }
+        if flags & HAS_POINTS == 0 {
+            return Err(HairError::Invalid(String::from(
+                "the file has no point array",
+            )));
+        }
+        file.points = read_f32s(&mut reader, point_count * 3)?
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+        if flags & HAS_THICKNESS != 0 {
+            file.thickness = Some(read_f32s(&mut reader, point_count)?);
+        }
+        if flags & HAS_TRANSPARENCY != 0 {
+            file.transparency = Some(read_f32s(&mut reader, point_count)?);
+        }
+        if flags & HAS_COLOR != 0 {
+            file.colors = Some(
+                read_f32s(&mut reader, point_count * 3)?
+                    .chunks_exact(3)
+                    .map(|c| [c[0], c[1], c[2]])
+                    .collect(),
+            );
+        }
+
+        let expected = match &file.segments {
+            Some(segments) => Some(segments.iter().map(|&s| s as usize + 1).sum()),
+            None => hair_count.checked_mul(file.default_segments as usize + 1),
+        };
+        match expected {
+            Some(expected) if expected == point_count => Ok(file),
+            Some(expected) => Err(HairError::Invalid(format!(
+                "{} hairs need {} points, the header has {}",
+                hair_count, expected, point_count
+            ))),
+            None => Err(HairError::Invalid(format!(
+                "{} hairs of {} segments are too many points",
+                hair_count, file.default_segments
+            ))),
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), HairError> {
+        self.validate()?;
+        let mut flags = HAS_POINTS;
+        for (present, flag) in [
+            (self.segments.is_some(), HAS_SEGMENTS),
+            (self.thickness.is_some(), HAS_THICKNESS),
+            (self.transparency.is_some(), HAS_TRANSPARENCY),
+            (self.colors.is_some(), HAS_COLOR),
+        ] {
+            if present {
+                flags |= flag;
+            }
+        }
+
+        let mut header = Vec::with_capacity(128);
+        header.extend_from_slice(SIGNATURE);
+        for value in [
+            self.hair_count as u32,
+            self.points.len() as u32,
+            flags,
+            self.default_segments,
+        ] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [
+            self.default_thickness,
+            self.default_transparency,
+            self.default_color[0],
+            self.default_color[1],
+            self.default_color[2],
+        ] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut info = [0u8; INFO_LEN];
+        let len = self.info.len().min(INFO_LEN);
+        info[..len].copy_from_slice(&self.info.as_bytes()[..len]);
+        header.extend_from_slice(&info);
+        writer.write_all(&header)?;
+
+        if let Some(segments) = &self.segments {
+            for s in segments {
+                writer.write_all(&s.to_le_bytes())?;
+            }
+        }
+        write_f32s(&mut writer, self.points.iter().flatten())?;
+        if let Some(thickness) = &self.thickness {
+            write_f32s(&mut writer, thickness)?;
+        }
+        if let Some(transparency) = &self.transparency {
+            write_f32s(&mut writer, transparency)?;
+        }
+        if let Some(colors) = &self.colors {
+            write_f32s(&mut writer, colors.iter().flatten())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// make sure the arrays agree with each other before writing
+    fn validate(&self) -> Result<(), HairError> {
+        let point_count = self.points.len();
+        if let Some(segments) = &self.segments {
+            if segments.len() != self.hair_count {
+                return Err(HairError::Invalid(format!(
+                    "{} segment counts for {} hairs",
+                    segments.len(),
+                    self.hair_count
+                )));
+            }
+        }
+        let expected: usize = (0..self.hair_count)
+            .map(|i| self.hair_segments(i) + 1)
+            .sum();
+        if expected != point_count {
+            return Err(HairError::Invalid(format!(
+                "{} hairs need {} points, got {}",
+                self.hair_count, expected, point_count
+            )));
+        }
+        for (name, len) in [
+            ("thickness", self.thickness.as_ref().map(Vec::len)),
+            ("transparency", self.transparency.as_ref().map(Vec::len)),
+            ("colour", self.colors.as_ref().map(Vec::len)),
+        ] {
+            if let Some(len) = len.filter(|&len| len != point_count) {
+                return Err(HairError::Invalid(format!(
+                    "{} {} values for {} points",
+                    len, name, point_count
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn load_hair(path: impl AsRef<Path>) -> Result<HairFile, HairError> {
+    HairFile::read(BufReader::new(File::open(path)?))
+}
+
+pub fn save_hair(path: impl AsRef<Path>, file: &HairFile) -> Result<(), HairError> {
+    file.write(BufWriter::new(File::create(path)?))
+}
+
+fn read_f32s<R: Read>(reader: &mut R, count: usize) -> Result<Vec<f32>, HairError> {
+    let mut bytes = vec![0u8; count * 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn write_f32s<'a, W: Write>(
+    writer: &mut W,
+    values: impl IntoIterator<Item = &'a f32>,
+) -> Result<(), HairError> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synthetic() -> HairFile {
+        HairFile {
+            segments: Some(vec![1, 2]),
+            points: vec![
+                [0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 0.5, 0.0],
+                [1.0, 1.0, 0.5],
+            ],
+            thickness: Some(vec![0.1, 0.05, 0.2, 0.1, 0.0]),
+            transparency: Some(vec![0.0, 0.5, 0.0, 0.25, 1.0]),
+            colors: Some(vec![
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 0.0, 1.0],
+                [0.5, 0.5, 0.5],
+                [1.0, 1.0, 1.0],
+            ]),
+            info: String::from("synthetic test hair"),
+            hair_count: 2,
+            ..HairFile::default()
+        }
+    }
+
+    fn round_trip(file: &HairFile) -> HairFile {
+        let mut bytes = Vec::new();
+        file.write(&mut bytes).unwrap();
+        HairFile::read(bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn round_trip_with_every_array() {
+        let file = synthetic();
+        let mut bytes = Vec::new();
+        file.write(&mut bytes).unwrap();
+        // header, 2 segment counts, 5 points, thickness, transparency and colour per point
+        assert_eq!(bytes.len(), 128 + 2 * 2 + 5 * 4 * (3 + 1 + 1 + 3));
+        assert_eq!(HairFile::read(bytes.as_slice()).unwrap(), file);
+    }
+
+    #[test]
+    fn round_trip_with_defaults_only() {
+        let file = HairFile {
+            default_segments: 2,
+            points: vec![[0.0; 3], [0.0, 1.0, 0.0], [0.0, 2.0, 0.0]],
+            default_thickness: 0.3,
+            default_transparency: 0.1,
+            default_color: [0.2, 0.3, 0.4],
+            hair_count: 1,
+            ..HairFile::default()
+        };
+        let read = round_trip(&file);
+        assert_eq!(read, file);
+        assert_eq!(read.point_thickness(2), 0.3);
+        assert_eq!(read.point_transparency(0), 0.1);
+        assert_eq!(read.point_color(1), [0.2, 0.3, 0.4]);
+    }
+
+    #[test]
+    fn strands_split_by_segments() {
+        let strands = synthetic().strands();
+        assert_eq!(strands.len(), 2);
+        assert_eq!(strands[0].points, vec![[0.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(strands[1].points.len(), 3);
+        assert_eq!(strands[1].widths, vec![0.2, 0.1, 0.0]);
+    }
+
+    #[test]
+    fn strands_round_trip() {
+        let strands = vec![
+            Strand::new(vec![[0.0; 3], [1.0, 2.0, 3.0]], 0.01),
+            Strand::tapered(vec![[0.0; 3], [0.0, 1.0, 0.0], [0.0, 2.0, 0.0]], 0.02, 0.0),
+        ];
+        let read = round_trip(&HairFile::from_strands(&strands));
+        assert_eq!(read.strands(), strands);
+
+        // equal lengths go into the header instead of the segment array
+        let uniform = HairFile::from_strands(&strands[..1]);
+        assert_eq!(uniform.segments, None);
+        assert_eq!(uniform.default_segments, 1);
+        assert_eq!(round_trip(&uniform).strands(), strands[..1].to_vec());
+    }
+
+    #[test]
+    fn root_and_tip_colors_are_averaged() {
+        let (root, tip) = synthetic().root_and_tip_colors().unwrap();
+        assert_eq!(root, [0.5, 0.0, 0.5]);
+        assert_eq!(tip, [0.5, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn long_info_is_truncated() {
+        let mut file = synthetic();
+        file.info = "x".repeat(200);
+        assert_eq!(round_trip(&file).info, "x".repeat(INFO_LEN));
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        let mut bytes = Vec::new();
+        synthetic().write(&mut bytes).unwrap();
+
+        let mut wrong_signature = bytes.clone();
+        wrong_signature[0] = b'X';
+        assert!(matches!(
+            HairFile::read(wrong_signature.as_slice()),
+            Err(HairError::Signature)
+        ));
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(matches!(
+            HairFile::read(truncated),
+            Err(HairError::Invalid(_))
+        ));
+        assert!(matches!(
+            HairFile::read(&bytes[..100]),
+            Err(HairError::Io(_))
+        ));
+
+        // point count in the header no longer matches the segments
+        let mut wrong_count = bytes.clone();
+        wrong_count[8..12].copy_from_slice(&4u32.to_le_bytes());
+        assert!(matches!(
+            HairFile::read(wrong_count.as_slice()),
+            Err(HairError::Invalid(_))
+        ));
+
+        let mut inconsistent = synthetic();
+        inconsistent.thickness = Some(vec![1.0]);
+        assert!(matches!(
+            inconsistent.write(Vec::new()),
+            Err(HairError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn bogus_header_counts_are_rejected_before_allocating() {
+        let mut bytes = Vec::new();
+        synthetic().write(&mut bytes).unwrap();
+
+        for (offset, count) in [(4, u32::MAX), (8, u32::MAX), (8, 1 << 30)] {
+            let mut bogus = bytes.clone();
+            bogus[offset..offset + 4].copy_from_slice(&count.to_le_bytes());
+            let err = HairFile::read(bogus.as_slice()).unwrap_err();
+            assert!(err.to_string().contains("do not fit"), "{}", err);
+        }
+
+        // without a segment array the point count follows from the header alone, a huge hair
+        // count is caught by the arithmetic rather than by counting the hairs one by one
+        let mut bytes = Vec::new();
+        HairFile {
+            default_segments: 1,
+            hair_count: 1,
+            points: vec![[0.0; 3]; 2],
+            ..HairFile::default()
+        }
+        .write(&mut bytes)
+        .unwrap();
+        bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            HairFile::read(bytes.as_slice()),
+            Err(HairError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+use glm::Vec3;
+
+use crate::hair_strands::StrandVertex;
+use crate::mesh::sphere_around;
+
+pub mod groom;
+pub mod hair_file;
+pub mod ribbon;
+
+/// a single hair fibre, a polyline running from root to tip
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Strand {
+    pub points: Vec<[f32; 3]>,
+    /// fibre width at every point, in world units
+    pub widths: Vec<f32>,
+}
+
+impl Strand {
+    /// strand with the same width at every point
+    pub fn new(points: Vec<[f32; 3]>, width: f32) -> Self {
+        let widths = vec![width; points.len()];
+        Self { points, widths }
+    }
+
+    /// strand whose width falls off linearly from `root_width` to `tip_width`
+    pub fn tapered(points: Vec<[f32; 3]>, root_width: f32, tip_width: f32) -> Self {
+        let mut strand = Self::new(points, 0.0);
+        strand.widths = strand
+            .params()
+            .into_iter()
+            .map(|t| root_width + (tip_width - root_width) * t)
+            .collect();
+        strand
+    }
+
+    /// root-to-tip parameter of every point by arc length, 0 at the root and 1 at the tip
+    pub fn params(&self) -> Vec<f32> {
+        let lengths = self.cumulative_lengths();
+        let total = *lengths.last().unwrap_or(&0.0);
+        let count = self.points.len();
+        lengths
+            .iter()
+            .enumerate()
+            .map(|(i, &l)| {
+                if total > 0.0 {
+                    l / total
+                } else if count > 1 {
+                    i as f32 / (count - 1) as f32
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// arc length from the root to every point
+    pub fn cumulative_lengths(&self) -> Vec<f32> {
+        let mut lengths = Vec::with_capacity(self.points.len());
+        let mut total = 0.0;
+        for (i, &p) in self.points.iter().enumerate() {
+            if i > 0 {
+                total += glm::distance(to_vec3(self.points[i - 1]), to_vec3(p));
+            }
+            lengths.push(total);
+        }
+        lengths
+    }
+
+    /// unit tangent at point `i`, central differences inside the strand,
+    /// one sided at the root and the tip
+    pub fn tangent(&self, i: usize) -> Vec3 {
+        let last = self.points.len().saturating_sub(1);
+        let prev = to_vec3(self.points[i.saturating_sub(1)]);
+        let next = to_vec3(self.points[(i + 1).min(last)]);
+        let d = next - prev;
+        if glm::length(d) > f32::EPSILON {
+            glm::normalize(d)
+        } else {
+            Vec3::new(0.0, -1.0, 0.0)
+        }
+    }
+}
+
+/// flatten strands into line list geometry, two indices per segment
+pub fn line_geometry(strands: &[Strand]) -> (Vec<StrandVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for strand in strands {
+        let base = vertices.len() as u32;
+        for (i, param) in strand.params().into_iter().enumerate() {
+            vertices.push(StrandVertex {
+                position: strand.points[i],
+                tangent: *strand.tangent(i).as_array(),
+                param,
+                width: strand.widths.get(i).copied().unwrap_or(0.0),
+            });
+        }
+        for i in 1..strand.points.len() as u32 {
+            indices.extend_from_slice(&[base + i - 1, base + i]);
+        }
+    }
+    (vertices, indices)
+}
+
+/// centre the strands on the origin and scale them, widths included, so the largest side of
+/// their bounding box is `size` long
+pub fn fit_strands(strands: &mut [Strand], size: f32) {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for p in strands.iter().flat_map(|s| &s.points) {
+        for k in 0..3 {
+            min[k] = min[k].min(p[k]);
+            max[k] = max[k].max(p[k]);
+        }
+    }
+    let extent = (0..3).map(|k| max[k] - min[k]).fold(0.0, f32::max);
+    if !extent.is_finite() || extent <= 0.0 {
+        return;
+    }
+    let centre = [0, 1, 2].map(|k| 0.5 * (min[k] + max[k]));
+    let scale = size / extent;
+    for strand in strands {
+        for p in &mut strand.points {
+            for k in 0..3 {
+                p[k] = (p[k] - centre[k]) * scale;
+            }
+        }
+        for w in &mut strand.widths {
+            *w *= scale;
+        }
+    }
+}
+
+/// centre and radius of a sphere around every point of the strands, `None` without points
+pub fn bounding_sphere(strands: &[Strand]) -> Option<(Vec3, f32)> {
+    sphere_around(strands.iter().flat_map(|s| &s.points))
+}
+
+pub(crate) fn to_vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
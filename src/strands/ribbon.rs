@@ -0,0 +1,207 @@
+//! expansion of strand polylines into camera-facing ribbons
+
+use glm::Vec3;
+
+use crate::hair_strands::RibbonVertex;
+use crate::strands::{to_vec3, Strand};
+
+/// how strand widths turn into ribbon widths. the taper comes from the strands' own widths
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RibbonParams {
+    /// multiplier for every strand width
+    pub width_scale: f32,
+}
+
+impl Default for RibbonParams {
+    fn default() -> Self {
+        Self { width_scale: 1.0 }
+    }
+}
+
+impl RibbonParams {
+    /// ribbon width at a point of the given strand width
+    pub fn width(&self, strand_width: f32) -> f32 {
+        strand_width * self.width_scale
+    }
+}
+
+/// triangle indices of the ribbons, they only depend on the strand layout and not on the camera
+pub fn ribbon_indices(strands: &[Strand]) -> Vec<u32> {
+    let mut indices = Vec::new();
+    let mut base = 0u32;
+    for strand in strands {
+        for i in 1..strand.points.len() as u32 {
+            // left and right vertex of the previous and the current point
+            let (l0, r0) = (base + 2 * (i - 1), base + 2 * (i - 1) + 1);
+            let (l1, r1) = (base + 2 * i, base + 2 * i + 1);
+            indices.extend_from_slice(&[l0, r0, l1, r0, r1, l1]);
+        }
+        base += 2 * strand.points.len() as u32;
+    }
+    indices
+}
+
+/// expand every strand point into a left and a right vertex, offset perpendicular to both the
+/// strand tangent and the direction towards `camera_pos`, so the triangles of
+/// [`ribbon_indices`] are always counter-clockwise seen from the camera.
+///
+/// `tex_coord` is `(across, along)`: 0 on the left edge and 1 on the right edge, 0 at the root
+/// and 1 at the tip.
+pub fn expand_ribbons(
+    strands: &[Strand],
+    camera_pos: Vec3,
+    params: &RibbonParams,
+) -> Vec<RibbonVertex> {
+    let mut vertices = Vec::with_capacity(strands.iter().map(|s| 2 * s.points.len()).sum());
+    for strand in strands {
+        let mut last_side = None;
+        for (i, param) in strand.params().into_iter().enumerate() {
+            let position = to_vec3(strand.points[i]);
+            let tangent = strand.tangent(i);
+            let side = ribbon_side(tangent, camera_pos - position)
+                .or(last_side)
+                .unwrap_or_else(|| fallback_side(tangent));
+            last_side = Some(side);
+
+            let width = params.width(strand.widths.get(i).copied().unwrap_or(0.0));
+            let offset = side * (0.5 * width);
+            for (pos, across) in [(position - offset, 0.0), (position + offset, 1.0)] {
+                vertices.push(RibbonVertex {
+                    position: *pos.as_array(),
+                    tangent: *tangent.as_array(),
+                    tex_coord: [across, param],
+                });
+            }
+        }
+    }
+    vertices
+}
+
+/// unit vector across the ribbon, `None` when looking straight down the strand
+pub fn ribbon_side(tangent: Vec3, to_camera: Vec3) -> Option<Vec3> {
+    let side = glm::cross(tangent, to_camera);
+    if glm::length(side) > 1e-6 {
+        Some(glm::normalize(side))
+    } else {
+        None
+    }
+}
+
+fn fallback_side(tangent: Vec3) -> Vec3 {
+    let helper = if tangent.x.abs() < 0.9 {
+        Vec3::new(1.0, 0.0, 0.0)
+    } else {
+        Vec3::new(0.0, 0.0, 1.0)
+    };
+    glm::normalize(glm::cross(tangent, helper))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::CameraBuilder;
+
+    use super::*;
+
+    fn vertical_strand() -> Strand {
+        // root at the top, growing down, 2 units in front of the default camera
+        Strand::new(
+            vec![[0.0, 0.5, 1.0], [0.0, 0.0, 1.0], [0.0, -0.5, 1.0]],
+            0.1,
+        )
+    }
+
+    #[test]
+    fn ribbon_spans_along_camera_right() {
+        let camera = CameraBuilder::default().build();
+        let vertices = expand_ribbons(
+            &[vertical_strand()],
+            camera.get_camera_pos(),
+            &RibbonParams::default(),
+        );
+        assert_eq!(vertices.len(), 6);
+
+        let right = camera.get_right();
+        for pair in vertices.chunks_exact(2) {
+            let across = to_vec3(pair[1].position) - to_vec3(pair[0].position);
+            assert!((glm::length(across) - 0.1).abs() < 1e-5);
+            // perpendicular to the view, so the whole width is visible
+            assert!(glm::dot(glm::normalize(across), right).abs() > 0.999);
+            assert!(glm::dot(across, camera.get_front()).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn ribbon_follows_a_rotated_camera() {
+        // looking along -x from the +x side
+        let camera = CameraBuilder::default()
+            .pos(Vec3::new(3.0, 0.0, 1.0))
+            .yaw(180.0)
+            .build();
+        let vertices = expand_ribbons(
+            &[vertical_strand()],
+            camera.get_camera_pos(),
+            &RibbonParams::default(),
+        );
+
+        let across = to_vec3(vertices[1].position) - to_vec3(vertices[0].position);
+        assert!(glm::dot(glm::normalize(across), camera.get_right()).abs() > 0.999);
+        assert!(glm::dot(glm::normalize(across), camera.get_up()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn width_follows_the_strand_widths() {
+        let camera = CameraBuilder::default().build();
+        let strand = Strand::tapered(vertical_strand().points, 0.1, 0.05);
+        let params = RibbonParams { width_scale: 2.0 };
+        let vertices = expand_ribbons(&[strand], camera.get_camera_pos(), &params);
+        let widths: Vec<f32> = vertices
+            .chunks_exact(2)
+            .map(|p| glm::distance(to_vec3(p[0].position), to_vec3(p[1].position)))
+            .collect();
+
+        assert!((widths[0] - 0.2).abs() < 1e-5);
+        assert!((widths[1] - 0.15).abs() < 1e-5);
+        assert!((widths[2] - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn uvs_run_along_and_across() {
+        let camera = CameraBuilder::default().build();
+        let vertices = expand_ribbons(
+            &[vertical_strand()],
+            camera.get_camera_pos(),
+            &RibbonParams::default(),
+        );
+        let uvs: Vec<[f32; 2]> = vertices.iter().map(|v| v.tex_coord).collect();
+        assert_eq!(
+            uvs,
+            vec![
+                [0.0, 0.0],
+                [1.0, 0.0],
+                [0.0, 0.5],
+                [1.0, 0.5],
+                [0.0, 1.0],
+                [1.0, 1.0]
+            ]
+        );
+        for v in &vertices {
+            assert_eq!(v.tangent, [0.0, -1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn triangles_face_the_camera() {
+        let camera = CameraBuilder::default().build();
+        let strands = [vertical_strand()];
+        let vertices = expand_ribbons(&strands, camera.get_camera_pos(), &RibbonParams::default());
+        let indices = ribbon_indices(&strands);
+        assert_eq!(indices.len(), 12);
+
+        for tri in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| to_vec3(vertices[tri[k] as usize].position));
+            let normal = glm::cross(b - a, c - a);
+            // counter-clockwise from the camera means the normal points back at it
+            assert!(glm::dot(normal, camera.get_front()) < 0.0);
+        }
+    }
+}
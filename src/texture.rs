@@ -0,0 +1,86 @@
+//! texture loading from disk or from embedded bytes, in any format the `image` crate decodes
+
+use std::error::Error;
+use std::fmt::{self, Display as FmtDisplay, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glium::Display;
+use glium::texture::{RawImage2d, SrgbTexture2d, Texture2d, TextureCreationError};
+use image::{DynamicImage, ImageError};
+use image::io::Reader;
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io { path: PathBuf, err: io::Error },
+    Decode { path: PathBuf, err: ImageError },
+    Upload(TextureCreationError),
+}
+
+impl FmtDisplay for TextureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::Io { path, err } => {
+                write!(f, "failed to read texture {}: {}", path.display(), err)
+            }
+            TextureError::Decode { path, err } => {
+                write!(f, "failed to decode texture {}: {}", path.display(), err)
+            }
+            TextureError::Upload(err) => write!(f, "failed to upload texture: {:?}", err),
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureError::Io { err, .. } => Some(err),
+            TextureError::Decode { err, .. } => Some(err),
+            TextureError::Upload(_) => None,
+        }
+    }
+}
+
+impl From<TextureCreationError> for TextureError {
+    fn from(err: TextureCreationError) -> Self {
+        TextureError::Upload(err)
+    }
+}
+
+/// colour texture, decoded from sRGB when sampled
+pub fn load_srgb_texture(
+    display: &Display,
+    path: impl AsRef<Path>,
+) -> Result<SrgbTexture2d, TextureError> {
+    let image = read_image(path.as_ref())?;
+    Ok(SrgbTexture2d::new(display, raw_image(image))?)
+}
+
+/// data texture such as a shift map, sampled as stored
+pub fn load_linear_texture(
+    display: &Display,
+    path: impl AsRef<Path>,
+) -> Result<Texture2d, TextureError> {
+    let image = read_image(path.as_ref())?;
+    Ok(Texture2d::new(display, raw_image(image))?)
+}
+
+/// flip to the bottom-up row order OpenGL expects
+fn raw_image(image: DynamicImage) -> RawImage2d<'static, u8> {
+    let image = image.to_rgba8();
+    let dimensions = image.dimensions();
+    RawImage2d::from_raw_rgba_reversed(&image.into_raw(), dimensions)
+}
+
+fn read_image(path: &Path) -> Result<DynamicImage, TextureError> {
+    let reader = Reader::open(path)
+        .and_then(Reader::with_guessed_format)
+        .map_err(|err| TextureError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+    reader.decode().map_err(|err| TextureError::Decode {
+        path: path.to_path_buf(),
+        err,
+    })
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+out float AdaptedLuminance;
+
+// log luminance of the frame, its last mip level is the average
+uniform sampler2D log_luminance;
+uniform float average_level;
+// the value of the last frame, 0 before the first
+uniform sampler2D adapted_luminance;
+uniform float delta_time;
+uniform float speed;
+
+// see adapt in tone_mapping/mod.rs
+void main() {
+    float measured = exp(textureLod(log_luminance, vec2(0.5), average_level).r);
+    float adapted = texture(adapted_luminance, vec2(0.5)).r;
+    if (adapted <= 0.0) {
+        AdaptedLuminance = measured;
+    } else {
+        AdaptedLuminance = adapted + (measured - adapted) * (1.0 - exp(-delta_time * speed));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out float LogLuminance;
+
+// linear colour of the scene, sampled down to the size of the luminance texture
+uniform sampler2D scene_color;
+
+void main() {
+    float luminance = dot(texture(scene_color, TexCoord).rgb, vec3(0.2126, 0.7152, 0.0722));
+    // averaged as logarithms, so a few bright highlights do not dominate
+    LogLuminance = log(max(luminance, 1e-4));
+}
\ No newline at end of file
@@ -0,0 +1,379 @@
+//! HDR resolve of the scene target, with the CPU reference of the curves in `tonemap.frag`.
+//!
+//! the scene is lit in linear floating point colour, so highlights can exceed 1. the resolve
+//! scales it by an exposure, compresses it into [0, 1] with a tone curve and encodes sRGB for
+//! the window. automatic exposure measures the average log luminance of every frame and adapts
+//! to it over time, like an eye adapting to a brighter room.
+
+use std::time::Duration;
+
+use glium::{Display, Surface, uniform};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler};
+use serde::{Deserialize, Serialize};
+
+use crate::render_target::FullscreenPass;
+use crate::shader_source;
+
+/// width and height of the luminance texture, its last mip level holds the average
+const LUMINANCE_SIZE: u32 = 256;
+/// Rec. 709 weights of the linear channels
+const LUMINANCE_WEIGHTS: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// the curve compressing HDR colour into the displayable range
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneOperator {
+    /// `x / (1 + x)` per channel, soft but desaturates little
+    Reinhard,
+    /// Stephen Hill's fit of the ACES reference and output transforms
+    #[default]
+    AcesFitted,
+    /// John Hable's filmic curve from Uncharted 2
+    Uncharted2,
+}
+
+impl ToneOperator {
+    /// value of the `operator` uniform
+    pub fn uniform(self) -> i32 {
+        match self {
+            ToneOperator::Reinhard => 0,
+            ToneOperator::AcesFitted => 1,
+            ToneOperator::Uncharted2 => 2,
+        }
+    }
+
+    /// tone map linear `color`, already exposed. clamped like the shader does, since the
+    /// filmic curves overshoot beyond their white point
+    pub fn apply(self, color: [f32; 3]) -> [f32; 3] {
+        let mapped = match self {
+            ToneOperator::Reinhard => color.map(reinhard),
+            ToneOperator::AcesFitted => aces_fitted(color),
+            ToneOperator::Uncharted2 => color.map(uncharted2),
+        };
+        mapped.map(|c| c.clamp(0.0, 1.0))
+    }
+}
+
+/// how bright the scene is drawn
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Exposure {
+    /// colour times `2^ev`
+    Manual { ev: f32 },
+    /// maps the adapted average luminance to `key`, then applies `ev` as compensation.
+    /// `speed` is the rate of adaptation per second
+    Auto { key: f32, ev: f32, speed: f32 },
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Exposure::Manual { ev: 0.0 }
+    }
+}
+
+impl Exposure {
+    /// automatic exposure with middle grey as key
+    pub fn auto(ev: f32) -> Self {
+        Exposure::Auto {
+            key: 0.18,
+            ev,
+            speed: 1.5,
+        }
+    }
+
+    /// factor the scene colour is multiplied with, given the adapted average luminance
+    pub fn scale(self, adapted_luminance: f32) -> f32 {
+        match self {
+            Exposure::Manual { ev } => ev.exp2(),
+            Exposure::Auto { key, ev, .. } => key / adapted_luminance.max(1e-4) * ev.exp2(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToneMapSettings {
+    pub operator: ToneOperator,
+    pub exposure: Exposure,
+}
+
+pub fn luminance(color: [f32; 3]) -> f32 {
+    color
+        .iter()
+        .zip(LUMINANCE_WEIGHTS)
+        .map(|(c, w)| c * w)
+        .sum()
+}
+
+pub fn reinhard(x: f32) -> f32 {
+    x / (1.0 + x)
+}
+
+pub fn aces_fitted(color: [f32; 3]) -> [f32; 3] {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f32; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f32; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let rrt_and_odt = |v: f32| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.432951) + 0.238081;
+        a / b
+    };
+    let fitted = mul(&INPUT, color).map(rrt_and_odt);
+    mul(&OUTPUT, fitted).map(|c| c.clamp(0.0, 1.0))
+}
+
+pub fn uncharted2(x: f32) -> f32 {
+    const EXPOSURE_BIAS: f32 = 2.0;
+    /// linear value mapped to white
+    const WHITE: f32 = 11.2;
+    let curve = |x: f32| {
+        let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+        (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+    };
+    curve(EXPOSURE_BIAS * x) / curve(WHITE)
+}
+
+/// encode a linear channel in [0, 1] with the sRGB transfer function
+pub fn srgb_encode(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// move `adapted` toward `measured`, exponentially so the rate does not depend on the frame rate
+pub fn adapt(adapted: f32, measured: f32, delta_time: f32, speed: f32) -> f32 {
+    adapted + (measured - adapted) * (1.0 - (-delta_time * speed).exp())
+}
+
+fn mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// draws the scene target to the window through the tone curve
+pub struct ToneMapper {
+    settings: ToneMapSettings,
+    /// log luminance of the frame, mipmapped down to its average
+    luminance: Texture2d,
+    /// adapted average luminance, read from one while the other is written
+    adapted: [Texture2d; 2],
+    current: usize,
+    luminance_pass: FullscreenPass,
+    adapt_pass: FullscreenPass,
+    tonemap_pass: FullscreenPass,
+}
+
+impl ToneMapper {
+    pub fn new(display: &Display, settings: ToneMapSettings) -> Self {
+        let luminance = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F32,
+            MipmapsOption::EmptyMipmaps,
+            LUMINANCE_SIZE,
+            LUMINANCE_SIZE,
+        )
+        .unwrap();
+        let adapted = [(); 2].map(|_| {
+            let texture = Texture2d::empty_with_format(
+                display,
+                UncompressedFloatFormat::F32,
+                MipmapsOption::NoMipmap,
+                1,
+                1,
+            )
+            .unwrap();
+            // 0 is taken as not adapted yet, the first measurement is used as is
+            texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+            texture
+        });
+        let tonemap = FullscreenPass::builder(shader_source!("tonemap.frag")).srgb_output();
+        Self {
+            settings,
+            luminance,
+            adapted,
+            current: 0,
+            luminance_pass: FullscreenPass::new(display, shader_source!("luminance.frag")),
+            adapt_pass: FullscreenPass::new(display, shader_source!("adapt.frag")),
+            tonemap_pass: FullscreenPass::from_builder(display, tonemap),
+        }
+    }
+
+    pub fn settings(&self) -> ToneMapSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: ToneMapSettings) {
+        self.settings = settings;
+    }
+
+    /// measure the average luminance of `scene` and adapt to it, only with automatic exposure
+    pub fn update_exposure(&mut self, display: &Display, scene: &Texture2d, delta_time: Duration) {
+        let Exposure::Auto { speed, .. } = self.settings.exposure else {
+            return;
+        };
+
+        let uniforms = uniform! {
+            scene_color: Sampler::new(scene)
+                .minify_filter(MinifySamplerFilter::Linear)
+                .magnify_filter(MagnifySamplerFilter::Linear),
+        };
+        let mut target = SimpleFrameBuffer::new(display, self.luminance.main_level()).unwrap();
+        self.luminance_pass
+            .draw(&mut target, &uniforms, &Default::default());
+        // SAFETY: the texture is not bound to a framebuffer being drawn into
+        unsafe { self.luminance.generate_mipmaps() };
+
+        let previous = &self.adapted[self.current];
+        let next = &self.adapted[1 - self.current];
+        let uniforms = uniform! {
+            log_luminance: Sampler::new(&self.luminance)
+                .minify_filter(MinifySamplerFilter::NearestMipmapNearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+            average_level: (self.luminance.get_mipmap_levels() - 1) as f32,
+            adapted_luminance: Sampler::new(previous)
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+            delta_time: delta_time.as_secs_f32(),
+            speed: speed,
+        };
+        let mut target = SimpleFrameBuffer::new(display, next).unwrap();
+        self.adapt_pass
+            .draw(&mut target, &uniforms, &Default::default());
+        self.current = 1 - self.current;
+    }
+
+    /// tone map `scene` into `target`, in sRGB
+    pub fn draw<S: Surface>(&self, target: &mut S, scene: &Texture2d) {
+        let (key, ev, auto) = match self.settings.exposure {
+            Exposure::Manual { ev } => (1.0, ev, false),
+            Exposure::Auto { key, ev, .. } => (key, ev, true),
+        };
+        let uniforms = uniform! {
+            scene_color: Sampler::new(scene)
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+            adapted_luminance: Sampler::new(&self.adapted[self.current])
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+            auto_exposure: auto,
+            key: key,
+            exposure: ev.exp2(),
+            operator: self.settings.operator.uniform(),
+        };
+        self.tonemap_pass
+            .draw(target, &uniforms, &Default::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneOperator; 3] = [
+        ToneOperator::Reinhard,
+        ToneOperator::AcesFitted,
+        ToneOperator::Uncharted2,
+    ];
+
+    fn grey(operator: ToneOperator, x: f32) -> f32 {
+        operator.apply([x; 3])[1]
+    }
+
+    #[test]
+    fn curves_map_black_to_black() {
+        for operator in OPERATORS {
+            assert!(grey(operator, 0.0).abs() < 0.01, "{:?}", operator);
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic_and_bounded() {
+        for operator in OPERATORS {
+            let mut last = grey(operator, 0.0);
+            for i in 1..=2000 {
+                let value = grey(operator, i as f32 * 0.05);
+                assert!(value >= last, "{:?} decreases at {}", operator, i);
+                assert!(value <= 1.0, "{:?} exceeds 1 at {}", operator, i);
+                last = value;
+            }
+        }
+    }
+
+    #[test]
+    fn curves_keep_highlights_apart() {
+        // the clipping this replaces mapped everything above 1 to the same white
+        for operator in OPERATORS {
+            assert!(
+                grey(operator, 1.5) > grey(operator, 1.0) + 0.01,
+                "{:?}",
+                operator
+            );
+        }
+    }
+
+    #[test]
+    fn reinhard_maps_one_to_half() {
+        assert_eq!(reinhard(1.0), 0.5);
+        assert!(reinhard(1e6) < 1.0);
+    }
+
+    #[test]
+    fn aces_saturates_to_white() {
+        let white = aces_fitted([100.0; 3]);
+        assert!(white.iter().all(|&c| c > 0.99), "{:?}", white);
+        // mid grey stays in the middle of the range
+        let mid = grey(ToneOperator::AcesFitted, 0.18);
+        assert!((0.1..0.3).contains(&mid), "{}", mid);
+    }
+
+    #[test]
+    fn uncharted2_maps_white_point_to_one() {
+        assert!((uncharted2(11.2 / 2.0) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn srgb_encoding_is_continuous() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-6);
+        let below = srgb_encode(0.0031308);
+        let above = srgb_encode(0.0031309);
+        assert!((above - below).abs() < 1e-4);
+        // linear middle grey is about 46% in sRGB
+        assert!((srgb_encode(0.18) - 0.461).abs() < 0.001);
+    }
+
+    #[test]
+    fn auto_exposure_maps_average_to_key() {
+        let exposure = Exposure::auto(0.0);
+        assert!((exposure.scale(2.0) * 2.0 - 0.18).abs() < 1e-6);
+        assert_eq!(Exposure::Manual { ev: 1.0 }.scale(2.0), 2.0);
+        assert_eq!(Exposure::auto(1.0).scale(0.18), 2.0);
+    }
+
+    #[test]
+    fn adaptation_converges_independent_of_frame_rate() {
+        let run = |steps: u32| {
+            let dt = 1.0 / steps as f32;
+            (0..steps).fold(0.1, |adapted, _| adapt(adapted, 1.0, dt, 1.5))
+        };
+        assert!((run(30) - run(144)).abs() < 1e-4);
+        let converged = (0..600).fold(0.1, |adapted, _| adapt(adapted, 1.0, 1.0 / 60.0, 1.5));
+        assert!((converged - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn luminance_weights_sum_to_one() {
+        assert!((luminance([1.0; 3]) - 1.0).abs() < 1e-6);
+    }
+}
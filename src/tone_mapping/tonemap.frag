@@ -0,0 +1,71 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+// linear HDR colour of the scene
+uniform sampler2D scene_color;
+// average luminance the exposure has adapted to
+uniform sampler2D adapted_luminance;
+uniform bool auto_exposure;
+// luminance the adapted average is mapped to
+uniform float key;
+// 2^ev, on top of the automatic exposure
+uniform float exposure;
+// 0 Reinhard, 1 ACES fitted, 2 Uncharted 2, the curves are mirrored in tone_mapping/mod.rs
+uniform int operator;
+
+// sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT, column major
+const mat3 ACES_INPUT = mat3(
+    0.59719, 0.07600, 0.02840,
+    0.35458, 0.90834, 0.13383,
+    0.04823, 0.01566, 0.83777
+);
+
+// ODT_SAT => XYZ => D60_2_D65 => sRGB
+const mat3 ACES_OUTPUT = mat3(
+     1.60475, -0.10208, -0.00327,
+    -0.53108,  1.10813, -0.07276,
+    -0.07367, -0.00605,  1.07602
+);
+
+vec3 aces_fitted(vec3 color) {
+    color = ACES_INPUT * color;
+    vec3 a = color * (color + 0.0245786) - 0.000090537;
+    vec3 b = color * (0.983729 * color + 0.432951) + 0.238081;
+    return clamp(ACES_OUTPUT * (a / b), 0.0, 1.0);
+}
+
+vec3 hable(vec3 x) {
+    const float A = 0.15, B = 0.50, C = 0.10, D = 0.20, E = 0.02, F = 0.30;
+    return (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F;
+}
+
+vec3 uncharted2(vec3 color) {
+    const float WHITE = 11.2;
+    return hable(2.0 * color) / hable(vec3(WHITE));
+}
+
+vec3 srgb_encode(vec3 color) {
+    vec3 low = 12.92 * color;
+    vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, lessThanEqual(color, vec3(0.0031308)));
+}
+
+void main() {
+    vec3 color = texture(scene_color, TexCoord).rgb * exposure;
+    if (auto_exposure) {
+        color *= key / max(texture(adapted_luminance, vec2(0.5)).r, 1e-4);
+    }
+
+    if (operator == 0) {
+        color = color / (1.0 + color);
+    } else if (operator == 1) {
+        color = aces_fitted(color);
+    } else {
+        color = uncharted2(color);
+    }
+    // the program is built with sRGB output, the framebuffer does not encode again
+    FragColor = vec4(srgb_encode(clamp(color, 0.0, 1.0)), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,205 @@
+use glm::{GenSquareMat, Mat3, Mat4, Vec3};
+use num_traits::One;
+
+/// translation, rotation and scale of a drawable, applied in the order scale -> rotate -> translate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    /// euler angles in degrees, (pitch around x, yaw around y, roll around z),
+    /// applied in the order roll -> pitch -> yaw
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Vec3::new(0.0, 0.0, 0.0),
+            scale: Vec3::one(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_translation(mut self, translation: Vec3) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    /// euler angles in degrees
+    pub fn with_rotation(mut self, rotation: Vec3) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_uniform_scale(self, scale: f32) -> Self {
+        self.with_scale(Vec3::new(scale, scale, scale))
+    }
+
+    /// set the rotation from a unit quaternion `[x, y, z, w]`, as used by glTF
+    pub fn with_quaternion(mut self, [x, y, z, w]: [f32; 4]) -> Self {
+        // rows of the rotation matrix needed to recover Ry * Rx * Rz
+        let m02 = 2.0 * (x * z + w * y);
+        let m10 = 2.0 * (x * y + w * z);
+        let m11 = 1.0 - 2.0 * (x * x + z * z);
+        let m12 = 2.0 * (y * z - w * x);
+        let m22 = 1.0 - 2.0 * (x * x + y * y);
+
+        let pitch = (-m12).clamp(-1.0, 1.0).asin();
+        let yaw = m02.atan2(m22);
+        let roll = m10.atan2(m11);
+        self.rotation = Vec3::new(pitch.to_degrees(), yaw.to_degrees(), roll.to_degrees());
+        self
+    }
+
+    /// model matrix, T * Ry * Rx * Rz * S
+    pub fn matrix(&self) -> Mat4 {
+        let m = glm::ext::translate(&Mat4::one(), self.translation);
+        let m = glm::ext::rotate(&m, glm::radians(self.rotation.y), Vec3::new(0.0, 1.0, 0.0));
+        let m = glm::ext::rotate(&m, glm::radians(self.rotation.x), Vec3::new(1.0, 0.0, 0.0));
+        let m = glm::ext::rotate(&m, glm::radians(self.rotation.z), Vec3::new(0.0, 0.0, 1.0));
+        glm::ext::scale(&m, self.scale)
+    }
+}
+
+/// inverse transpose of the upper 3x3 part of `model`, used to transform normals.
+/// falls back to the identity for singular matrices (e.g. a zero scale)
+pub fn normal_matrix(model: &Mat4) -> Mat3 {
+    let upper = Mat3::new(
+        model.c0.truncate(3),
+        model.c1.truncate(3),
+        model.c2.truncate(3),
+    );
+    upper
+        .inverse()
+        .map(|inv| glm::transpose(&inv))
+        .unwrap_or_else(Mat3::one)
+}
+
+/// column-major array suitable for a `mat4` uniform
+pub fn mat4_uniform(m: &Mat4) -> [[f32; 4]; 4] {
+    [
+        *m.as_array()[0].as_array(),
+        *m.as_array()[1].as_array(),
+        *m.as_array()[2].as_array(),
+        *m.as_array()[3].as_array(),
+    ]
+}
+
+/// column-major array suitable for a `mat3` uniform
+pub fn mat3_uniform(m: &Mat3) -> [[f32; 3]; 3] {
+    [
+        *m.as_array()[0].as_array(),
+        *m.as_array()[1].as_array(),
+        *m.as_array()[2].as_array(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::Vec4;
+
+    use super::*;
+
+    fn transform_point(m: &Mat4, p: Vec3) -> Vec3 {
+        let v = *m * Vec4::new(p.x, p.y, p.z, 1.0);
+        Vec3::new(v.x, v.y, v.z)
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(
+            (a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5 && (a.z - b.z).abs() < 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn scales_then_rotates_then_translates() {
+        let transform = Transform::from_translation(Vec3::new(0.0, 0.0, 5.0))
+            .with_rotation(Vec3::new(0.0, 90.0, 0.0))
+            .with_scale(Vec3::new(2.0, 1.0, 1.0));
+        let p = transform_point(&transform.matrix(), Vec3::new(1.0, 0.0, 0.0));
+        assert_close(p, Vec3::new(0.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn rotates_roll_then_pitch_then_yaw() {
+        // pitch turns +y to +z, then yaw turns +z to +x
+        let m = Transform::new()
+            .with_rotation(Vec3::new(90.0, 90.0, 0.0))
+            .matrix();
+        assert_close(
+            transform_point(&m, Vec3::new(0.0, 1.0, 0.0)),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+
+        // roll turns +x to +y, which yaw keeps
+        let m = Transform::new()
+            .with_rotation(Vec3::new(0.0, 90.0, 90.0))
+            .matrix();
+        assert_close(
+            transform_point(&m, Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn quaternion_matches_direct_rotation() {
+        let axis = glm::normalize(Vec3::new(1.0, 2.0, 3.0));
+        let (s, w) = (0.35f32.sin(), 0.35f32.cos());
+        let q = Vec3::new(axis.x * s, axis.y * s, axis.z * s);
+        let m = Transform::new()
+            .with_quaternion([q.x, q.y, q.z, w])
+            .matrix();
+
+        for p in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.3, -0.5, 2.0),
+        ] {
+            // p + 2w (q x p) + 2 q x (q x p)
+            let t = glm::cross(q, p) * 2.0;
+            let expected = p + t * w + glm::cross(q, t);
+            assert_close(transform_point(&m, p), expected);
+        }
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular_under_non_uniform_scale() {
+        let model = Transform::new()
+            .with_scale(Vec3::new(2.0, 1.0, 1.0))
+            .matrix();
+        let tangent = transform_point(&model, Vec3::new(1.0, -1.0, 0.0));
+        let normal = normal_matrix(&model) * Vec3::new(1.0, 1.0, 0.0);
+        assert!(glm::dot(tangent, normal).abs() < 1e-5);
+        // the model matrix itself would tilt the normal off the surface
+        let skewed = transform_point(&model, Vec3::new(1.0, 1.0, 0.0));
+        assert!(glm::dot(tangent, skewed).abs() > 1.0);
+    }
+
+    #[test]
+    fn normal_matrix_falls_back_to_identity_for_zero_scale() {
+        let model = Transform::new().with_uniform_scale(0.0).matrix();
+        let normal = normal_matrix(&model) * Vec3::new(0.0, 1.0, 0.0);
+        assert_close(normal, Vec3::new(0.0, 1.0, 0.0));
+    }
+}
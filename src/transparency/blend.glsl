@@ -0,0 +1,59 @@
+// how a material's colour reaches its target, see src/transparency/mod.rs. opaque and cutout
+// programs write FragColor, programs built with WEIGHTED_OIT add the fragment to the weighted
+// blended order-independent transparency buffers instead
+
+#ifdef WEIGHTED_OIT
+out vec4 accum;
+out float revealage;
+#else
+out vec4 FragColor;
+#endif
+
+// fragments with less alpha are discarded, 0 keeps all of them
+uniform float alpha_cutoff;
+// alpha to coverage: alpha decides how many of the pixel's samples the fragment covers
+uniform bool alpha_coverage;
+
+#ifndef GL_ARB_sample_shading
+// without gl_SampleMask alpha decides how much of a 4x4 block is drawn instead
+const float BAYER[16] = float[16](
+    0.0, 8.0, 2.0, 10.0,
+    12.0, 4.0, 14.0, 6.0,
+    3.0, 11.0, 1.0, 9.0,
+    15.0, 7.0, 13.0, 5.0
+);
+
+float dither_threshold() {
+    ivec2 cell = ivec2(gl_FragCoord.xy) & 3;
+    return (BAYER[cell.y * 4 + cell.x] + 0.5) / 16.0;
+}
+#endif
+
+void write_color(vec4 color) {
+#ifdef GL_ARB_sample_shading
+    // a single sample target keeps the fragments with alpha of at least a half
+    int covered = alpha_coverage ? int(clamp(color.a, 0.0, 1.0) * float(gl_NumSamples) + 0.5) : 32;
+    if (covered == 0) {
+        discard;
+    }
+    // written on every path, an unwritten mask is undefined once the shader writes it anywhere
+    gl_SampleMask[0] = covered >= 32 ? -1 : (1 << covered) - 1;
+    float cutoff = alpha_cutoff;
+#else
+    float cutoff = alpha_coverage ? dither_threshold() : alpha_cutoff;
+#endif
+    if (color.a < cutoff) {
+        discard;
+    }
+#ifdef WEIGHTED_OIT
+    float alpha = clamp(color.a, 0.0, 0.999);
+    // McGuire and Bavoil's depth weight, nearer fragments dominate the average colour
+    float weight = clamp(alpha * max(1e-2, 3e3 * pow(1.0 - gl_FragCoord.z, 3.0)), 1e-2, 3e3);
+    accum = vec4(color.rgb * alpha, alpha) * weight;
+    // the background shows through the product of (1 - alpha), summed as logarithms so that
+    // both buffers blend additively
+    revealage = -log(1.0 - alpha);
+#else
+    FragColor = color;
+#endif
+}
\ No newline at end of file
@@ -0,0 +1,20 @@
+#version 330 core
+
+in vec2 TexCoord;
+
+out vec4 FragColor;
+
+// premultiplied colour and alpha of the transparent fragments, weighted by depth
+uniform sampler2D accum_texture;
+// -log of the fraction of the background that shows through
+uniform sampler2D revealage_texture;
+
+// blended over the opaque scene with the coverage as alpha
+void main() {
+    vec4 accum = texture(accum_texture, TexCoord);
+    float coverage = 1.0 - exp(-texture(revealage_texture, TexCoord).r);
+    if (coverage < 1e-4) {
+        discard;
+    }
+    FragColor = vec4(accum.rgb / max(accum.a, 1e-5), coverage);
+}
\ No newline at end of file
@@ -0,0 +1,213 @@
+//! alpha handling of materials. every lit program includes `blend.glsl` and ends with
+//! `write_color`, the material's [`BlendMode`] decides what happens to the alpha it passes.
+//!
+//! transparent surfaces use weighted blended order-independent transparency (McGuire and
+//! Bavoil 2013): instead of sorting, every fragment adds its depth-weighted colour and its
+//! coverage to two buffers, and [`OitBuffers::composite`] blends their average over the opaque
+//! scene. thousands of crossing strands need no sorting this way, at the price of an
+//! approximate order where fragments are close in depth.
+
+use std::rc::Rc;
+
+use glium::{
+    Blend, BlendingFunction, Display, DrawParameters, LinearBlendingFactor, Program, Surface,
+    uniform,
+};
+use glium::draw_parameters::BackfaceCullingMode;
+use glium::framebuffer::MultiOutputFrameBuffer;
+use glium::texture::{DepthTexture2d, MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler};
+use serde::{Deserialize, Serialize};
+
+use crate::render_target::FullscreenPass;
+use crate::shader::{ProgramBuilder, ShaderError};
+use crate::shader_source;
+
+/// how a material treats the alpha of its colour
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum BlendMode {
+    /// alpha is ignored
+    #[default]
+    Opaque,
+    /// alpha tested, fragments below `cutoff` are discarded. for hair cards whose texture
+    /// cuts out the gaps between the strands
+    Cutout { cutoff: f32 },
+    /// alpha decides how many samples of a pixel a surface covers, which softens the edges of
+    /// cutout hair cards without sorting. glium cannot switch on the hardware's alpha to
+    /// coverage, so `blend.glsl` writes `gl_SampleMask` itself. drivers without
+    /// `GL_ARB_sample_shading` fall back to an ordered dither over 4x4 pixel blocks
+    AlphaToCoverage,
+    /// blended through the order-independent transparency buffers
+    Transparent,
+}
+
+impl BlendMode {
+    pub fn is_transparent(self) -> bool {
+        self == BlendMode::Transparent
+    }
+
+    /// value of the `alpha_cutoff` uniform
+    pub fn alpha_cutoff(self) -> f32 {
+        match self {
+            BlendMode::Cutout { cutoff } => cutoff,
+            BlendMode::Opaque | BlendMode::AlphaToCoverage | BlendMode::Transparent => 0.0,
+        }
+    }
+
+    /// value of the `alpha_coverage` uniform
+    pub fn alpha_coverage(self) -> bool {
+        self == BlendMode::AlphaToCoverage
+    }
+
+    /// the scene's parameters with this mode's blending. transparent surfaces are tested
+    /// against the opaque depth but do not write it, and show their back faces
+    pub fn draw_parameters<'a>(self, base: &DrawParameters<'a>) -> DrawParameters<'a> {
+        match self {
+            BlendMode::Opaque | BlendMode::Cutout { .. } | BlendMode::AlphaToCoverage => {
+                base.clone()
+            }
+            BlendMode::Transparent => {
+                let additive = BlendingFunction::Addition {
+                    source: LinearBlendingFactor::One,
+                    destination: LinearBlendingFactor::One,
+                };
+                DrawParameters {
+                    depth: glium::Depth {
+                        write: false,
+                        ..base.depth
+                    },
+                    blend: Blend {
+                        color: additive,
+                        alpha: additive,
+                        constant_value: (0.0, 0.0, 0.0, 0.0),
+                    },
+                    backface_culling: BackfaceCullingMode::CullingDisabled,
+                    ..base.clone()
+                }
+            }
+        }
+    }
+}
+
+/// make `#include "blend.glsl"` available, writing into the transparency buffers when
+/// `transparent` is set
+pub fn include_blending(builder: ProgramBuilder, transparent: bool) -> ProgramBuilder {
+    let builder = builder
+        .include("blend.glsl", shader_source!("blend.glsl"))
+        .extension("GL_ARB_sample_shading");
+    if transparent {
+        builder.define("WEIGHTED_OIT", "1")
+    } else {
+        builder
+    }
+}
+
+/// a program built for both passes, so a material can change its blend mode at runtime.
+/// clones share the programs
+#[derive(Clone)]
+pub struct BlendPrograms {
+    opaque: Rc<Program>,
+    transparent: Rc<Program>,
+}
+
+impl BlendPrograms {
+    pub fn build(display: &Display, builder: ProgramBuilder) -> Result<Self, ShaderError> {
+        Ok(Self::from_programs(
+            include_blending(builder.clone(), false).build(display)?,
+            include_blending(builder, true).build(display)?,
+        ))
+    }
+
+    pub fn from_programs(opaque: Program, transparent: Program) -> Self {
+        Self {
+            opaque: Rc::new(opaque),
+            transparent: Rc::new(transparent),
+        }
+    }
+
+    pub fn set_opaque(&mut self, program: Program) {
+        self.opaque = Rc::new(program);
+    }
+
+    pub fn set_transparent(&mut self, program: Program) {
+        self.transparent = Rc::new(program);
+    }
+
+    /// the program drawing with `mode`
+    pub fn get(&self, mode: BlendMode) -> &Program {
+        if mode.is_transparent() {
+            &self.transparent
+        } else {
+            &self.opaque
+        }
+    }
+}
+
+/// the accumulated colour and coverage of the transparent surfaces of a frame
+pub struct OitBuffers {
+    accum: Texture2d,
+    revealage: Texture2d,
+    composite: FullscreenPass,
+}
+
+impl OitBuffers {
+    pub fn new(display: &Display, dimensions: (u32, u32)) -> Self {
+        let (accum, revealage) = create_buffers(display, dimensions);
+        Self {
+            accum,
+            revealage,
+            composite: FullscreenPass::new(display, shader_source!("composite.frag")),
+        }
+    }
+
+    /// match the size of the scene target
+    pub fn resize(&mut self, display: &Display, dimensions: (u32, u32)) {
+        if dimensions != self.accum.dimensions() {
+            (self.accum, self.revealage) = create_buffers(display, dimensions);
+        }
+    }
+
+    /// the cleared buffers with the scene's depth, for the transparent pass
+    pub fn framebuffer<'a>(
+        &'a self,
+        display: &Display,
+        depth: &'a DepthTexture2d,
+    ) -> MultiOutputFrameBuffer<'a> {
+        let outputs = [("accum", &self.accum), ("revealage", &self.revealage)];
+        let mut target =
+            MultiOutputFrameBuffer::with_depth_buffer(display, outputs, depth).unwrap();
+        target.clear_color(0.0, 0.0, 0.0, 0.0);
+        target
+    }
+
+    /// blend the transparent surfaces over the opaque scene in `target`
+    pub fn composite<S: Surface>(&self, target: &mut S) {
+        let nearest = |texture| {
+            Sampler::new(texture)
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+        };
+        let uniforms = uniform! {
+            accum_texture: nearest(&self.accum),
+            revealage_texture: nearest(&self.revealage),
+        };
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+        self.composite.draw(target, &uniforms, &params);
+    }
+}
+
+fn create_buffers(display: &Display, (width, height): (u32, u32)) -> (Texture2d, Texture2d) {
+    let (width, height) = (width.max(1), height.max(1));
+    let buffer = |format| {
+        Texture2d::empty_with_format(display, format, MipmapsOption::NoMipmap, width, height)
+            .unwrap()
+    };
+    (
+        buffer(UncompressedFloatFormat::F16F16F16F16),
+        buffer(UncompressedFloatFormat::F16),
+    )
+}